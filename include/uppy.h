@@ -0,0 +1,35 @@
+/* C interface to the uppy upload engine, built as uppy.dll by `cargo build --release` */
+
+#ifndef UPPY_H
+#define UPPY_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define UPPY_OK 0
+/* A pointer was null or a string wasn't UTF-8 */
+#define UPPY_INVALID_ARGUMENT 1
+/* The configuration couldn't be read or describes a host uppy can't talk to */
+#define UPPY_CONFIG 2
+/* The host didn't take the file */
+#define UPPY_UPLOAD 3
+
+/*
+ * Uploads the file at path to the host in the configuration, or in its profile unless that is
+ * NULL, and points out_url at its URL. out_url is only written to on success and has to be
+ * freed with uppy_free.
+ */
+int uppy_upload(const char *path, const char *profile, char **out_url);
+
+/* Why the last call on this thread failed, NULL if none has. Valid until the next failure */
+const char *uppy_last_error(void);
+
+/* Frees a string uppy handed out, doing nothing with NULL */
+void uppy_free(char *text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
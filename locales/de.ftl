@@ -0,0 +1,120 @@
+# Deutsche Meldungen, die Schlüssel stehen in en.ftl
+
+## Uploads
+
+uploaded = Hochgeladen: { $url }
+signature-uploaded = Signatur: { $url }
+manifest-uploaded = Manifest: { $url }
+already-uploaded = Schon hochgeladen: { $url }
+copied = URL in die Zwischenablage kopiert!
+config-created = Konfigurationsordner in .config angelegt
+cancelling = Wird beendet, nochmal Strg+C zum sofortigen Beenden
+cancelled = Abgebrochen, { $done } von { $count ->
+    [one] { $count } Datei
+   *[other] { $count } Dateien
+} hochgeladen, uppy resume lädt den Rest hoch
+rate-limited = Limit von { $limit } erreicht, warte { $wait }
+zip-password = Zip-Passwort:
+zip-password-repeat = Passwort wiederholen:
+minisign-password = Passwort von { $path }:
+duplicates = { $count ->
+    [one] { $count } Datei hatte denselben Inhalt wie eine andere und wurde nicht nochmal hochgeladen
+   *[other] { $count } Dateien hatten denselben Inhalt wie andere und wurden nicht nochmal hochgeladen
+}
+synced = { $count ->
+    [one] { $count } Datei abgeglichen
+   *[other] { $count } Dateien abgeglichen
+}, { $unchanged } unverändert, die URLs stehen in { $path }
+
+## Dateien nach dem Hochladen löschen
+
+delete-prompt = Soll die Datei gelöscht werden? (J/N)
+answer-yes = j, ja
+answer-no = n, nein
+moved-to-recycle-bin = Datei in den Papierkorb verschoben!
+deleted-file = Datei gelöscht!
+shredded = Datei geschreddert!
+recycle-failed = Die Datei konnte nicht in den Papierkorb verschoben werden: { $error }
+delete-failed = Die Datei konnte nicht gelöscht werden: { $error }
+shred-failed = Die Datei konnte nicht geschreddert werden: { $error }
+invalid-choice = Ungültige Antwort, die Datei bleibt erhalten
+
+## Stapel und die Offline-Warteschlange
+
+resuming = Setze den Stapel vom { $date } fort, { $count ->
+    [one] { $count } Datei ist schon hochgeladen
+   *[other] { $count } Dateien sind schon hochgeladen
+}
+retrying = Lade { $count ->
+    [one] { $count } Datei
+   *[other] { $count } Dateien
+} aus dem Stapel vom { $date } erneut hoch
+queued = Host nicht erreichbar, { $count ->
+    [one] { $count } Datei wird
+   *[other] { $count } Dateien werden
+} hochgeladen, sobald er wieder da ist
+queue-flushing = Die Warteschlange wird schon abgearbeitet
+queue-empty = Die Warteschlange ist leer
+queue-uploading = Lade { $count ->
+    [one] { $count } wartende Datei
+   *[other] { $count } wartende Dateien
+} hoch
+queue-uploaded = Aus der Warteschlange hochgeladen
+queue-paused = Uploads im Hintergrund angehalten, uppy queue resume setzt sie fort
+queue-resumed = Uploads im Hintergrund laufen weiter
+queue-cleared = { $count ->
+    [one] { $count } Datei aus der Warteschlange entfernt
+   *[other] { $count } Dateien aus der Warteschlange entfernt
+}
+pick-help = { $checked }/{ $total } ausgewählt, Leertaste wählt aus, a wählt alle, Enter lädt hoch, Esc bricht ab
+picker-help = { $matched }/{ $total } Dateien, { $picked } ausgewählt, Tab wählt aus, Enter lädt hoch, Esc bricht ab
+
+## Tui
+
+tui-files = Dateien ({ $checked } ausgewählt)
+tui-uploads = Uploads ({ $done }/{ $count })
+tui-history = Verlauf
+tui-files-help = Tippen filtert, Tab wählt aus, Enter lädt hoch, ←/→ wechselt die Ansicht, Esc beendet
+tui-uploads-help = Enter kopiert die URL, ←/→ wechselt die Ansicht, Esc beendet und bricht den Rest ab
+tui-history-help = Tippen sucht, Enter kopiert die URL, Entf löscht vom Host, Esc beendet
+tui-waiting = wartet
+tui-queued = { $count ->
+    [one] { $count } Datei eingereiht
+   *[other] { $count } Dateien eingereiht
+}
+tui-copied = { $url } kopiert
+tui-confirm-delete = Nochmal Entf drücken, um { $url } vom Host zu löschen
+
+## Status
+
+status-idle = Gerade lädt kein uppy hoch
+status-running = Prozess { $pid } seit { $since }: { $command }
+status-paused = Uploads im Hintergrund sind angehalten, uppy queue resume setzt sie fort
+status-queued = { $count ->
+    [one] { $count } Datei wartet
+   *[other] { $count } Dateien warten
+}
+status-batch = Letzter Stapel von { $since }: { $done } fertig, { $failed } fehlgeschlagen, { $pending } offen
+status-recent = Letzte Uploads
+
+## Andere Befehle
+
+watching-clipboard = Beobachte die Zwischenablage, Strg+C zum Beenden
+watching-dir = Beobachte { $path } auf neue Dateien, Strg+C zum Beenden
+downloaded = { $size } nach { $path } heruntergeladen
+decrypted = Nach { $path } entschlüsselt
+gallery-written = { $path } geschrieben
+deleted-upload = { $url } gelöscht
+restored = { $path } wiederhergestellt
+nothing-to-clean = Nichts aufzuräumen
+would-reclaim = Würde { $size } freigeben
+reclaimed = { $size } freigegeben
+downloading-part = Lade Teil { $part }/{ $parts } herunter
+joined = { $path } zusammengesetzt ({ $size })
+
+## Updates
+
+update-available = uppy { $version } ist verfügbar, `uppy self-update` installiert es
+update-latest = uppy { $version } ist die neueste Version
+update-newer = uppy { $latest } ist verfügbar, dies ist { $current }
+updated = Auf { $version } aktualisiert
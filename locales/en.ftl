@@ -0,0 +1,122 @@
+# Messages uppy prints, see src/i18n.rs
+# Arguments like { $url } are filled in by uppy, plurals follow the CLDR categories of the language
+
+## Uploads
+
+uploaded = Uploaded URL: { $url }
+signature-uploaded = Signature URL: { $url }
+manifest-uploaded = Manifest URL: { $url }
+already-uploaded = Already uploaded: { $url }
+copied = Copied URL to clipboard!
+config-created = Configuration directory created in .config
+cancelling = Stopping, press Ctrl+C again to quit right away
+cancelled = Cancelled with { $done } of { $count ->
+    [one] { $count } file
+   *[other] { $count } files
+} uploaded, uppy resume uploads the rest
+rate-limited = Rate limit of { $limit } reached, waiting { $wait }
+zip-password = Zip password:
+zip-password-repeat = Repeat the password:
+minisign-password = Password of { $path }:
+duplicates = { $count ->
+    [one] { $count } file had the same contents as another and wasn't uploaded again
+   *[other] { $count } files had the same contents as others and weren't uploaded again
+}
+synced = { $count ->
+    [one] Synced { $count } file
+   *[other] Synced { $count } files
+}, { $unchanged } unchanged, the URLs are in { $path }
+
+## Deleting files after uploading
+
+delete-prompt = Would you like to delete the file? (Y/N)
+# The answers that mean yes and no separated by commas, y and n always work
+answer-yes = y, yes
+answer-no = n, no
+moved-to-recycle-bin = File moved to the Recycle Bin!
+deleted-file = File deleted!
+shredded = File shredded!
+recycle-failed = Something went wrong while moving the file to the Recycle Bin: { $error }
+delete-failed = Something went wrong while deleting the file: { $error }
+shred-failed = Something went wrong while shredding the file: { $error }
+invalid-choice = Invalid choice, keeping the file
+
+## Batches and the offline queue
+
+resuming = Resuming the batch from { $date }, { $count ->
+    [one] { $count } file already uploaded
+   *[other] { $count } files already uploaded
+}
+retrying = Retrying { $count ->
+    [one] { $count } file
+   *[other] { $count } files
+} from the batch from { $date }
+queued = Couldn't reach the host, queued { $count ->
+    [one] { $count } file
+   *[other] { $count } files
+} to upload once it is back
+queue-flushing = The queue is already being flushed
+queue-empty = The queue is empty
+queue-uploading = Uploading { $count ->
+    [one] { $count } queued file
+   *[other] { $count } queued files
+}
+queue-uploaded = Uploaded from the queue
+queue-paused = Paused uploads in the background, uppy queue resume carries on with them
+queue-resumed = Uploads in the background carry on
+queue-cleared = { $count ->
+    [one] Removed { $count } file from the queue
+   *[other] Removed { $count } files from the queue
+}
+pick-help = { $checked }/{ $total } checked, Space checks, a checks all, Enter uploads, Esc cancels
+picker-help = { $matched }/{ $total } files, { $picked } picked, Tab picks, Enter uploads, Esc cancels
+
+## Tui
+
+tui-files = Files ({ $checked } checked)
+tui-uploads = Uploads ({ $done }/{ $count })
+tui-history = History
+tui-files-help = Type to filter, Tab checks, Enter uploads, ←/→ switch views, Esc quits
+tui-uploads-help = Enter copies the URL, ←/→ switch views, Esc quits and aborts what is left
+tui-history-help = Type to search, Enter copies the URL, Delete deletes from the host, Esc quits
+tui-waiting = waiting
+tui-queued = { $count ->
+    [one] Queued { $count } file
+   *[other] Queued { $count } files
+}
+tui-copied = Copied { $url }
+tui-confirm-delete = Press Delete again to delete { $url } from the host
+
+## Status
+
+status-idle = No uppy is uploading right now
+status-running = Process { $pid } since { $since }: { $command }
+status-paused = Uploads in the background are paused, uppy queue resume carries on with them
+status-queued = { $count ->
+    [one] { $count } file is queued
+   *[other] { $count } files are queued
+}
+status-batch = Last batch from { $since }: { $done } done, { $failed } failed, { $pending } left
+status-recent = Latest uploads
+
+## Other commands
+
+watching-clipboard = Watching the clipboard, press Ctrl+C to stop
+watching-dir = Watching { $path } for new files, press Ctrl+C to stop
+downloaded = Downloaded { $size } to { $path }
+decrypted = Decrypted to { $path }
+gallery-written = Wrote { $path }
+deleted-upload = Deleted { $url }
+restored = Restored { $path }
+nothing-to-clean = Nothing to clean up
+would-reclaim = Would reclaim { $size }
+reclaimed = Reclaimed { $size }
+downloading-part = Downloading part { $part }/{ $parts }
+joined = Joined { $path } ({ $size })
+
+## Updates
+
+update-available = uppy { $version } available, run `uppy self-update`
+update-latest = uppy { $version } is the latest version
+update-newer = uppy { $latest } is available, this is { $current }
+updated = Updated to { $version }
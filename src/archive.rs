@@ -0,0 +1,316 @@
+use clap::ValueEnum;
+use mime_guess::mime;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use tracing::debug;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::error::Error;
+use crate::i18n::t;
+use crate::temp::TempCopy;
+use crate::winpath;
+use uppy::process;
+
+/// What zstd itself uses when not given a level
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// How hard and with how many threads archives are compressed
+pub struct Packing {
+    pub format: ArchiveFormat,
+    /// Deflate level of zips, 0 to 9, zlib's 6 when not set
+    pub zip_level: Option<i64>,
+    pub zstd_level: i32,
+    pub threads: usize,
+}
+
+/// How `--archive` packs the files
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// A zip that only compresses files that aren't compressed already, leaving photos and videos as they are
+    #[default]
+    Auto,
+    /// A zip with every file compressed
+    Zip,
+    /// A zip with every file stored as it is
+    Store,
+    /// A tar compressed with zstd, made with the tar that comes with Windows
+    #[value(name = "tar.zst")]
+    #[serde(rename = "tar.zst")]
+    TarZst,
+}
+
+/// Asks for the archive password twice without echoing it
+pub fn prompt_password() -> Result<String, Error> {
+    let password =
+        rpassword::prompt_password(format!("{} ", t!("zip-password"))).map_err(Error::Password)?;
+    let confirmation = rpassword::prompt_password(format!("{} ", t!("zip-password-repeat")))
+        .map_err(Error::Password)?;
+    match (password.is_empty(), password == confirmation) {
+        (true, _) => Err(Error::EmptyPassword),
+        (false, true) => Ok(password),
+        (false, false) => Err(Error::PasswordMismatch),
+    }
+}
+
+/// Whether the contents of `file` are compressed already so compressing them again wastes time,
+/// going by its type
+fn is_compressed(file: &Path) -> bool {
+    let mime = mime_guess::from_path(file).first_or_octet_stream();
+    match (mime.type_(), mime.subtype().as_str()) {
+        (mime::IMAGE, "bmp" | "tiff" | "svg" | "x-icon" | "vnd.microsoft.icon") => false,
+        (mime::AUDIO, "wav" | "x-wav" | "vnd.wave" | "aiff" | "x-aiff") => false,
+        (mime::IMAGE | mime::VIDEO | mime::AUDIO, _) => true,
+        (mime::APPLICATION, subtype) => matches!(
+            subtype,
+            "zip"
+                | "gzip"
+                | "x-gzip"
+                | "zstd"
+                | "x-xz"
+                | "x-bzip2"
+                | "x-7z-compressed"
+                | "vnd.rar"
+                | "x-rar-compressed"
+                | "epub+zip"
+                | "java-archive"
+                | "vnd.android.package-archive"
+        ),
+        _ => false,
+    }
+}
+
+/// Name of the archive, after the file or directory when there is only one
+fn archive_name(roots: &[PathBuf], format: ArchiveFormat) -> String {
+    let stem = match roots {
+        [root] => root
+            .file_stem()
+            .unwrap_or(OsStr::new("files"))
+            .to_string_lossy()
+            .into_owned(),
+        _ => "files".to_string(),
+    };
+    match format {
+        ArchiveFormat::TarZst => format!("{}.tar.zst", stem),
+        _ => format!("{}.zip", stem),
+    }
+}
+
+/// Where a file goes in the archive
+///
+/// Files found in a directory that was given keep their path under it, starting with its name
+fn entry_path(file: &Path, roots: &[PathBuf]) -> PathBuf {
+    let extended = winpath::extended(file);
+    for root in roots {
+        let Ok(inside) = extended.strip_prefix(winpath::extended(root)) else {
+            continue;
+        };
+        if let (Some(name), false) = (root.file_name(), inside.as_os_str().is_empty()) {
+            return Path::new(name).join(inside);
+        }
+    }
+    PathBuf::from(file.file_name().unwrap_or(OsStr::new("file")))
+}
+
+/// The files with the names they get in the archive, sorted by name so the same files always
+/// make the same archive however they were given
+///
+/// Names are numbered when several files share one
+fn entries(files: &[PathBuf], roots: &[PathBuf]) -> Vec<(PathBuf, String)> {
+    let mut entries: Vec<(PathBuf, String)> = files
+        .iter()
+        .map(|file| {
+            let path = entry_path(file, roots);
+            let name = path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (file.clone(), name)
+        })
+        .collect();
+    entries.sort_by(|(a_file, a), (b_file, b)| a.cmp(b).then_with(|| a_file.cmp(b_file)));
+
+    let mut taken: Vec<String> = Vec::new();
+    for (_, name) in &mut entries {
+        let mut candidate = name.clone();
+        let mut n = 1;
+        while taken.contains(&candidate) {
+            n += 1;
+            candidate = format!("{} ({})", name, n);
+        }
+        *name = candidate.clone();
+        taken.push(candidate);
+    }
+    entries
+}
+
+/// Adds `file` to the zip as `name`
+fn add<W: Write + io::Seek>(
+    writer: &mut ZipWriter<W>,
+    file: &Path,
+    name: &str,
+    packing: &Packing,
+    password: Option<&str>,
+) -> io::Result<()> {
+    let method = match packing.format {
+        ArchiveFormat::Auto if is_compressed(file) => CompressionMethod::Stored,
+        ArchiveFormat::Store => CompressionMethod::Stored,
+        _ => CompressionMethod::Deflated,
+    };
+    // Nothing about when or where the archive was made goes in, so the same files make the same zip
+    let mut options = SimpleFileOptions::default()
+        .compression_method(method)
+        .last_modified_time(DateTime::DEFAULT)
+        .unix_permissions(0o644);
+    if method == CompressionMethod::Deflated {
+        options = options.compression_level(packing.zip_level);
+    }
+    if let Some(password) = password {
+        options = options.with_aes_encryption(AesMode::Aes256, password);
+    }
+    debug!("Adding {} to the archive as {}", file.display(), name);
+    writer.start_file(name, options)?;
+    io::copy(&mut File::open(file)?, writer)?;
+    Ok(())
+}
+
+fn zip(
+    copy: &TempCopy,
+    entries: &[(PathBuf, String)],
+    packing: &Packing,
+    password: Option<&str>,
+) -> io::Result<()> {
+    let mut writer = ZipWriter::new(File::create(&copy.path)?);
+    if packing.threads < 2 || entries.len() < 2 {
+        for (file, name) in entries {
+            add(&mut writer, file, name, packing, password)?;
+        }
+        writer.finish()?;
+        return Ok(());
+    }
+
+    // Every file is compressed into a zip of its own on a thread, then copied over as it is
+    let part = |i: usize| copy.path.with_extension(format!("{}.part", i));
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..packing.threads.min(entries.len()))
+            .map(|_| {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((file, name)) = entries.get(i) else {
+                        return Ok(());
+                    };
+                    let mut writer = ZipWriter::new(File::create(part(i))?);
+                    add(&mut writer, file, name, packing, password)?;
+                    writer.finish()?;
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("a thread panicked")))?;
+        }
+        Ok::<_, io::Error>(())
+    })?;
+    for i in 0..entries.len() {
+        let mut archive = ZipArchive::new(File::open(part(i))?)?;
+        writer.raw_copy_file(archive.by_index_raw(0)?)?;
+        drop(archive);
+        let _ = fs::remove_file(part(i));
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// `text` escaped for an mtree file, which takes anything but plain characters as octal
+fn mtree_escape(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'/' | b'+' | b',' => {
+                (byte as char).to_string()
+            }
+            byte => format!("\\{:03o}", byte),
+        })
+        .collect()
+}
+
+/// Has the tar of Windows pack the files
+///
+/// It reads them from an mtree file listing each with a fixed owner, mode and time, so the same
+/// files make the same archive, and so there is no limit on how many
+fn tar_zst(copy: &TempCopy, entries: &[(PathBuf, String)], packing: &Packing) -> io::Result<()> {
+    let mut list = String::from("#mtree\n");
+    for (file, name) in entries {
+        list.push_str(&format!(
+            "{} type=file mode=0644 uid=0 gid=0 time=0.0 contents={}\n",
+            mtree_escape(name),
+            mtree_escape(&winpath::plain(file).display().to_string())
+        ));
+    }
+    let list_path = copy.path.with_extension("mtree");
+    File::create(&list_path)?.write_all(list.as_bytes())?;
+
+    let tar = PathBuf::from(env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into()))
+        .join("System32")
+        .join("tar.exe");
+    let output = process::hidden(
+        Command::new(tar)
+            .arg("--zstd")
+            .arg("--options")
+            .arg(format!(
+                "zstd:compression-level={},zstd:threads={}",
+                packing.zstd_level, packing.threads
+            ))
+            .arg("-cf")
+            .arg(&copy.path)
+            .arg(format!("@{}", list_path.display())),
+    )
+    .output();
+    let _ = fs::remove_file(&list_path);
+    let output = output?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!(
+            "tar failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Packs the files into an archive in the temp directory, keeping the paths of those found in
+/// the directories among `roots`
+///
+/// The same files always make the same bytes, whatever order they were found in and whenever
+/// they were changed, unless a zip has every entry encrypted with AES-256 for a password
+pub fn pack(
+    files: &[PathBuf],
+    roots: &[PathBuf],
+    packing: &Packing,
+    password: Option<&str>,
+) -> io::Result<TempCopy> {
+    let copy = TempCopy::new(OsStr::new(&archive_name(roots, packing.format)))?;
+    let entries = entries(files, roots);
+    match packing.format {
+        ArchiveFormat::TarZst => tar_zst(&copy, &entries, packing)?,
+        _ => zip(&copy, &entries, packing, password)?,
+    }
+    debug!(
+        "Archived {} files into {} bytes",
+        files.len(),
+        fs::metadata(&copy.path)
+            .map(|meta| meta.len())
+            .unwrap_or_default()
+    );
+    Ok(copy)
+}
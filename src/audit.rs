@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+use uppy::config::CONFIG_PATH;
+
+/// Past this size the log is moved aside and a new one started
+const MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// How many logs that were moved aside are kept, `audit.1.jsonl` being the newest
+const KEEP: usize = 5;
+
+/// Held while a line is written, so uploads running in parallel don't rotate the log twice
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// A line of `audit.jsonl`, written for every request to upload a file whatever came of it
+#[derive(Serialize)]
+pub struct Attempt<'a> {
+    /// When the attempt finished, in RFC 3339
+    pub at: String,
+    pub user: String,
+    pub machine: String,
+    pub host: &'a str,
+    pub path: &'a Path,
+    /// What the host was told the file is called, when it wasn't its own name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub uploaded: bool,
+    /// HTTP status the host answered with, missing when it never answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u128,
+}
+
+impl<'a> Attempt<'a> {
+    pub fn new(host: &'a str, path: &'a Path, duration: Duration) -> Self {
+        Attempt {
+            at: chrono::Local::now().to_rfc3339(),
+            user: env::var("USERNAME").unwrap_or_default(),
+            machine: env::var("COMPUTERNAME").unwrap_or_default(),
+            host,
+            path,
+            name: None,
+            size: None,
+            uploaded: false,
+            status: None,
+            url: None,
+            error: None,
+            duration_ms: duration.as_millis(),
+        }
+    }
+}
+
+fn log_path(n: usize) -> PathBuf {
+    match n {
+        0 => CONFIG_PATH.join("audit.jsonl"),
+        n => CONFIG_PATH.join(format!("audit.{}.jsonl", n)),
+    }
+}
+
+/// Shifts every log one number up, dropping the oldest
+fn rotate() -> io::Result<()> {
+    let _ = fs::remove_file(log_path(KEEP));
+    for n in (0..KEEP).rev() {
+        match fs::rename(log_path(n), log_path(n + 1)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn append(attempt: &Attempt) -> io::Result<()> {
+    let mut line = serde_json::to_vec(attempt)?;
+    line.push(b'\n');
+
+    let _writing = WRITING.lock();
+    if fs::metadata(log_path(0)).is_ok_and(|meta| meta.len() >= MAX_SIZE) {
+        rotate()?;
+    }
+    // A single write per line, so lines from separate runs of uppy don't interleave
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(0))?
+        .write_all(&line)
+}
+
+/// Appends `attempt` to `.config\uppy\audit.jsonl`, which never gets rewritten, only rotated
+pub fn record(attempt: &Attempt) {
+    if let Err(err) = append(attempt) {
+        warn!("Failed to write the audit log: {}", err);
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::history;
+use uppy::config::CONFIG_PATH;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    InProgress,
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub status: Status,
+    pub url: Option<String>,
+}
+
+/// Every file of the most recent batch and how far it got, rewritten whenever one of them moves on
+#[derive(Serialize, Deserialize)]
+pub struct Batch {
+    /// The flags of the run without its files, see [`crate::cli::flags`]
+    pub flags: Vec<String>,
+    pub started_at: i64,
+    /// Set once every file was dealt with, a batch without it was cut short
+    pub finished: bool,
+    pub files: Vec<Entry>,
+}
+
+fn batch_path() -> PathBuf {
+    CONFIG_PATH.join("batch.json")
+}
+
+/// The most recent batch, finished or not
+pub fn last() -> io::Result<Option<Batch>> {
+    let json = match fs::read_to_string(batch_path()) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(io::Error::from)
+}
+
+impl Batch {
+    /// Starts a batch of `files`, carrying over the files an earlier run already uploaded
+    pub fn start(flags: Vec<String>, files: &[PathBuf], done: Vec<Entry>) -> Self {
+        let mut batch = Batch {
+            flags,
+            started_at: history::now(),
+            finished: false,
+            files: done,
+        };
+        batch.files.extend(files.iter().map(|path| Entry {
+            path: path.clone(),
+            status: Status::Pending,
+            url: None,
+        }));
+        batch.save();
+        batch
+    }
+
+    /// Written next to the batch and renamed over it, so a crash never leaves half a file behind
+    fn save(&self) {
+        let temp = batch_path().with_extension("json.new");
+        let saved = serde_json::to_string_pretty(self)
+            .map_err(io::Error::from)
+            .and_then(|json| fs::write(&temp, json))
+            .and_then(|_| fs::rename(&temp, batch_path()));
+        if let Err(err) = saved {
+            warn!("Failed to save the state of the batch: {}", err);
+        }
+    }
+
+    fn set(&mut self, path: &Path, status: Status, url: Option<String>) {
+        let Some(entry) = self.files.iter_mut().find(|entry| entry.path == path) else {
+            return;
+        };
+        entry.status = status;
+        entry.url = url;
+        self.save();
+    }
+
+    pub fn start_file(&mut self, path: &Path) {
+        self.set(path, Status::InProgress, None);
+    }
+
+    /// Marks the file done when it got a URL, failed otherwise
+    pub fn finish_file(&mut self, path: &Path, url: Option<String>) {
+        match url {
+            Some(url) => self.set(path, Status::Done, Some(url)),
+            None => self.set(path, Status::Failed, None),
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.finished = true;
+        self.save();
+    }
+
+    /// Files that never finished uploading, the ones still in progress get uploaded again
+    pub fn unfinished(&self) -> Vec<PathBuf> {
+        self.with_status(&[Status::Pending, Status::InProgress])
+    }
+
+    pub fn with_status(&self, statuses: &[Status]) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|entry| statuses.contains(&entry.status))
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
+    pub fn done(&self) -> Vec<Entry> {
+        self.files
+            .iter()
+            .filter(|entry| entry.status == Status::Done)
+            .cloned()
+            .collect()
+    }
+}
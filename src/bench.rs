@@ -0,0 +1,66 @@
+use rand::RngCore;
+use std::ffi::OsStr;
+use std::time::Duration;
+use tracing::warn;
+use uppy::{Client, UploadRequest};
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::temp::TempCopy;
+use crate::template::{human_duration, human_size};
+
+/// Nearest-rank percentile of durations that are already sorted
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn delete(client: &Client, url: &str, deletion_url: Option<&str>) {
+    let deleted = match deletion_url {
+        Some(deletion_url) => client.delete_with_url(deletion_url),
+        None => client.delete(url),
+    };
+    if let Err(err) = deleted {
+        warn!("Failed to delete benchmark upload {}: {}", url, err);
+    }
+}
+
+/// Uploads `count` random payloads of every size and prints latency percentiles and throughput
+///
+/// The payloads are random so hosts can't compress or deduplicate them, and every upload
+/// is deleted again unless `keep` is set
+pub fn run(client: &Client, sizes: &[u64], count: u32, keep: bool) -> Result<Exit, Error> {
+    println!(
+        "{:>10}  {:>4}  {:>8}  {:>8}  {:>8}  {:>12}",
+        "size", "runs", "p50", "p90", "p99", "throughput"
+    );
+
+    for &size in sizes {
+        let mut payload = vec![0; size as usize];
+        let mut latencies = Vec::new();
+        for run in 0..count {
+            rand::thread_rng().fill_bytes(&mut payload);
+            let name = format!("uppy-bench-{}-{}.bin", size, run);
+            let file = TempCopy::write(OsStr::new(&name), &payload).map_err(Error::Bench)?;
+            let res = client.upload(&UploadRequest::new(&file.path))?;
+            latencies.push(res.elapsed);
+            if !keep {
+                delete(client, &res.url, res.deletion_url.as_deref());
+            }
+        }
+
+        latencies.sort();
+        let total: Duration = latencies.iter().sum();
+        let throughput = (size * count as u64) as f64 / total.as_secs_f64().max(0.001);
+        println!(
+            "{:>10}  {:>4}  {:>8}  {:>8}  {:>8}  {:>10}/s",
+            human_size(size),
+            count,
+            human_duration(percentile(&latencies, 50)),
+            human_duration(percentile(&latencies, 90)),
+            human_duration(percentile(&latencies, 99)),
+            human_size(throughput as u64)
+        );
+    }
+    Ok(Exit::Success)
+}
@@ -0,0 +1,52 @@
+//! Stopping on Ctrl+C without leaving temp files behind or losing track of the batch
+//!
+//! The first Ctrl+C only asks uppy to stop, uploads in flight are aborted and everything is
+//! cleaned up on the way out. A second one ends uppy right away like it normally would
+
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+use tracing::{debug, warn};
+
+use crate::i18n::t;
+use crate::{style, sys};
+
+const CTRL_C_EVENT: u32 = 0;
+const CTRL_BREAK_EVENT: u32 = 1;
+
+lazy_static! {
+    static ref CANCELLED: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+static INSTALL: Once = Once::new();
+
+/// Called by Windows on a thread of its own, returning 0 hands the event on to the default
+/// handler, which ends the process
+unsafe extern "system" fn handler(event: u32) -> i32 {
+    if !matches!(event, CTRL_C_EVENT | CTRL_BREAK_EVENT) || CANCELLED.swap(true, Ordering::Relaxed)
+    {
+        return 0;
+    }
+    eprintln!("{}", style::dim(&t!("cancelling")));
+    1
+}
+
+/// Catches Ctrl+C from now on instead of dying on it, see [`requested`]
+pub fn install() {
+    INSTALL.call_once(|| {
+        // Safety: the handler is a plain function that lives as long as the process
+        match unsafe { sys::SetConsoleCtrlHandler(Some(handler), 1) } {
+            0 => warn!("Failed to catch Ctrl+C, it will end uppy without cleaning up"),
+            _ => debug!("Catching Ctrl+C"),
+        }
+    });
+}
+
+/// Whether Ctrl+C was pressed, after which nothing new should be started
+pub fn requested() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// The flag for [`uppy::Client::with_cancel`], set by Ctrl+C
+pub fn flag() -> Arc<AtomicBool> {
+    Arc::clone(&CANCELLED)
+}
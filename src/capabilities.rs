@@ -0,0 +1,208 @@
+//! Which version of Zipline a host runs, so upload headers another version names differently are
+//! sent under the name it knows or left out rather than getting the upload refused
+//!
+//! The host is asked once and the answer kept in `.config\uppy\capabilities.json` for a day
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+use crate::clock;
+use crate::config::CONFIG_PATH;
+
+/// After this the host is asked again, it may have been updated meanwhile
+const MAX_AGE_SECS: u64 = 24 * 60 * 60;
+/// Where the version is in the answer of `/api/version`, which differs between versions
+const VERSION_POINTERS: [&str; 4] = [
+    "/version",
+    "/details/version",
+    "/versions/current",
+    "/data/version",
+];
+/// Upload headers as Zipline 3 and Zipline 4 call them, `None` where a version has no such header
+const HEADERS: [(Option<&str>, Option<&str>); 7] = [
+    (Some("format"), Some("x-zipline-format")),
+    (Some("embed"), None),
+    (Some("expires-at"), Some("x-zipline-deletes-at")),
+    (Some("original-name"), Some("x-zipline-original-name")),
+    (
+        Some("image-compression-percent"),
+        Some("x-zipline-image-compression-percent"),
+    ),
+    (Some("max-views"), Some("x-zipline-max-views")),
+    (None, Some("x-zipline-folder")),
+];
+
+/// The versions of Zipline whose headers differ
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Flavor {
+    V3,
+    V4,
+}
+
+impl Flavor {
+    /// `None` for versions before 3, which uppy doesn't know the headers of
+    pub(crate) fn from_major(major: u32) -> Option<Self> {
+        match major {
+            3 => Some(Flavor::V3),
+            4.. => Some(Flavor::V4),
+            _ => None,
+        }
+    }
+}
+
+/// What a host answered when it was asked for its version
+#[derive(Serialize, Deserialize)]
+struct Probed {
+    /// Major version, `None` when the host has no `/api/version`
+    major: Option<u32>,
+    checked_at: u64,
+}
+
+fn cache_path() -> PathBuf {
+    CONFIG_PATH.join("capabilities.json")
+}
+
+fn read_cache() -> HashMap<String, Probed> {
+    fs::read(cache_path())
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+/// The major version `host` answered with less than a day ago, `Some(None)` when it has no
+/// `/api/version`
+pub(crate) fn cached(host: &str) -> Option<Option<u32>> {
+    read_cache()
+        .remove(host)
+        .filter(|probed| clock::now().as_secs().saturating_sub(probed.checked_at) < MAX_AGE_SECS)
+        .map(|probed| probed.major)
+}
+
+pub(crate) fn remember(host: &str, major: Option<u32>) {
+    let mut cache = read_cache();
+    cache.insert(
+        host.to_string(),
+        Probed {
+            major,
+            checked_at: clock::now().as_secs(),
+        },
+    );
+    let saved = fs::create_dir_all(&*CONFIG_PATH)
+        .and_then(|_| serde_json::to_vec(&cache).map_err(io::Error::from))
+        .and_then(|json| fs::write(cache_path(), json));
+    if let Err(err) = saved {
+        warn!("Failed to remember the version of {}: {}", host, err);
+    }
+}
+
+/// The major version in an answer of `/api/version`, like `4` for `{"version": "4.0.1"}`
+pub(crate) fn major_version(body: &str) -> Option<u32> {
+    let json: Value = serde_json::from_str(body).ok()?;
+    let version = VERSION_POINTERS
+        .iter()
+        .find_map(|pointer| json.pointer(pointer)?.as_str())?;
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Renames the upload headers of the other version to those of `flavor`, leaving out the ones
+/// it has no counterpart to
+///
+/// A header that is already there under the right name is kept as it is
+pub(crate) fn adapt(headers: &mut HeaderMap, flavor: Flavor) {
+    for (v3, v4) in HEADERS {
+        let (theirs, ours) = match flavor {
+            Flavor::V3 => (v4, v3),
+            Flavor::V4 => (v3, v4),
+        };
+        let Some(value) = theirs.and_then(|theirs| headers.remove(theirs)) else {
+            continue;
+        };
+        let Some(ours) = ours else {
+            debug!(
+                "Leaving out {}, the host's version of Zipline has no such header",
+                theirs.unwrap_or_default()
+            );
+            continue;
+        };
+        // Zipline 3 wants the format in capitals and 4 in lowercase
+        let value = match (ours, value.to_str()) {
+            ("format", Ok(format)) => HeaderValue::from_str(&format.to_uppercase()).ok(),
+            ("x-zipline-format", Ok(format)) => HeaderValue::from_str(&format.to_lowercase()).ok(),
+            _ => None,
+        }
+        .unwrap_or(value);
+        let ours = HeaderName::from_static(ours);
+        if !headers.contains_key(&ours) {
+            headers.insert(ours, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|&(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adapt_renames_headers_for_zipline_4() {
+        let mut map = headers(&[("format", "RANDOM"), ("max-views", "3"), ("embed", "true")]);
+        adapt(&mut map, Flavor::V4);
+
+        assert_eq!(
+            map,
+            headers(&[("x-zipline-format", "random"), ("x-zipline-max-views", "3")])
+        );
+    }
+
+    #[test]
+    fn adapt_renames_headers_for_zipline_3() {
+        let mut map = headers(&[
+            ("x-zipline-format", "date"),
+            ("x-zipline-original-name", "true"),
+            ("x-zipline-folder", "abc"),
+        ]);
+        adapt(&mut map, Flavor::V3);
+
+        assert_eq!(
+            map,
+            headers(&[("format", "DATE"), ("original-name", "true")])
+        );
+    }
+
+    #[test]
+    fn adapt_keeps_headers_already_named_right() {
+        let mut map = headers(&[
+            ("x-zipline-max-views", "1"),
+            ("max-views", "5"),
+            ("authorization", "secret"),
+        ]);
+        adapt(&mut map, Flavor::V4);
+
+        assert_eq!(
+            map,
+            headers(&[("x-zipline-max-views", "1"), ("authorization", "secret")])
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Recording the conversation with a host to a file and playing it back later
+//!
+//! Cassettes make backend bugs reproducible without a live server, the token, secret headers
+//! and cookies are redacted so they can be attached to bug reports as they are
+
+use reqwest::blocking::Request;
+use reqwest::header::{
+    HeaderMap, HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+const REDACTED: &str = "[redacted]";
+/// Headers that carry credentials or sessions whether or not they were marked sensitive
+const SECRET_HEADERS: [HeaderName; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+/// A request and the answer it got
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    pub request_headers: BTreeMap<String, String>,
+    /// Name of the uploaded file, multipart bodies themselves aren't kept
+    pub file: Option<String>,
+    pub status: u16,
+    pub response_headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+fn header_map(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match value.is_sensitive() || SECRET_HEADERS.contains(name) {
+                true => REDACTED.to_string(),
+                false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+impl Interaction {
+    /// Starts an interaction from a request, the answer is filled in once it arrives
+    pub(crate) fn new(request: &Request, file: Option<&Path>) -> Self {
+        Interaction {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            request_headers: header_map(request.headers()),
+            file: file
+                .and_then(|file| file.file_name())
+                .map(|name| name.to_string_lossy().into_owned()),
+            status: 0,
+            response_headers: BTreeMap::new(),
+            body: String::new(),
+        }
+    }
+
+    pub(crate) fn answer(&mut self, status: u16, headers: &HeaderMap, body: &str) {
+        self.status = status;
+        self.response_headers = header_map(headers);
+        self.body = body.to_string();
+    }
+}
+
+/// Where requests go instead of, or as well as, the network
+pub enum Cassette {
+    /// Requests are sent as usual and saved to the file after every answer
+    Record {
+        path: PathBuf,
+        interactions: Mutex<Vec<Interaction>>,
+    },
+    /// Requests are answered from the file without touching the network
+    Replay {
+        interactions: Mutex<VecDeque<Interaction>>,
+    },
+}
+
+impl Cassette {
+    /// Records to `path`, replacing whatever was recorded there before
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Cassette::Record {
+            path: path.into(),
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Plays back a cassette written by [`Cassette::record`]
+    pub fn replay(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let interactions: VecDeque<Interaction> = serde_json::from_str(&json)?;
+        Ok(Cassette::Replay {
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Cassette::Replay { .. })
+    }
+
+    pub(crate) fn save(&self, interaction: Interaction) {
+        let Cassette::Record { path, interactions } = self else {
+            return;
+        };
+        let mut interactions = interactions.lock().unwrap_or_else(|err| err.into_inner());
+        interactions.push(interaction);
+
+        let written = serde_json::to_string_pretty(&*interactions)
+            .map_err(io::Error::from)
+            .and_then(|json| fs::write(path, json));
+        match written {
+            Ok(_) => debug!(
+                "Recorded {} interactions to {}",
+                interactions.len(),
+                path.display()
+            ),
+            Err(err) => warn!("Failed to write the cassette: {}", err),
+        }
+    }
+
+    /// The first recorded answer to the same method and URL, each answer is only played once
+    pub(crate) fn answer(&self, request: &Request) -> Option<Interaction> {
+        let Cassette::Replay { interactions } = self else {
+            return None;
+        };
+        let mut interactions = interactions.lock().unwrap_or_else(|err| err.into_inner());
+        let position = interactions.iter().position(|interaction| {
+            interaction.method == request.method().as_str()
+                && interaction.url == request.url().as_str()
+        })?;
+        interactions.remove(position)
+    }
+}
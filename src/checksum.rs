@@ -0,0 +1,126 @@
+//! Checksumming uploads as they are sent and checking them against what the backend stored
+//!
+//! Every hash of the file comes out of the one read that sends it, big files aren't read again
+
+use base64::prelude::*;
+use md5::Md5;
+use reqwest::header::{HeaderMap, ETAG};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use tracing::debug;
+
+use crate::client::UploadError;
+
+/// Running hashes of everything read so far
+#[derive(Default)]
+pub(crate) struct Hashes {
+    /// What uppy itself identifies files by, so it doesn't have to read them again for it
+    blake3: blake3::Hasher,
+    sha256: Sha256,
+    sha1: Sha1,
+    md5: Md5,
+    len: u64,
+}
+
+impl Hashes {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.blake3.update(bytes);
+        self.sha256.update(bytes);
+        self.sha1.update(bytes);
+        self.md5.update(bytes);
+        self.len += bytes.len() as u64;
+    }
+
+    /// Reads the whole file, for when the stream never went out such as a replayed request
+    pub(crate) fn of_file(path: &Path) -> io::Result<Self> {
+        let mut hashes = Hashes::default();
+        let mut file = File::open(path)?;
+        let mut buf = vec![0; 1024 * 1024];
+        loop {
+            match file.read(&mut buf)? {
+                0 => return Ok(hashes),
+                read => hashes.update(&buf[..read]),
+            }
+        }
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(crate) fn finish(self) -> Digests {
+        Digests {
+            blake3: self.blake3.finalize().to_hex().to_string(),
+            sha256: self.sha256.finalize().to_vec(),
+            sha1: self.sha1.finalize().to_vec(),
+            md5: self.md5.finalize().to_vec(),
+        }
+    }
+}
+
+pub(crate) struct Digests {
+    blake3: String,
+    sha256: Vec<u8>,
+    sha1: Vec<u8>,
+    md5: Vec<u8>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A hash the backend computed on its side, the name of the algorithm and the digest in hex
+fn stored_hash(headers: &HeaderMap) -> Option<(&'static str, String)> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(sha256) = header("x-amz-checksum-sha256") {
+        let decoded = BASE64_STANDARD.decode(sha256.trim()).ok()?;
+        return Some(("SHA-256", hex(&decoded)));
+    }
+    if let Some(sha1) = header("x-bz-content-sha1") {
+        return Some(("SHA-1", sha1.trim().to_lowercase()));
+    }
+    // Plenty of servers send an ETag for the JSON they answer with, only S3 puts the MD5 of
+    // the stored object in it, and only for objects that weren't uploaded in parts
+    if header("x-amz-request-id").is_some() {
+        let etag = header(ETAG.as_str())?.trim_matches('"');
+        if etag.len() == 32 && etag.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(("MD5", etag.to_lowercase()));
+        }
+    }
+    None
+}
+
+impl Digests {
+    pub(crate) fn sha256(&self) -> String {
+        hex(&self.sha256)
+    }
+
+    pub(crate) fn blake3(&self) -> String {
+        self.blake3.clone()
+    }
+
+    /// Compares the digests with a hash the backend returned, if it returned one
+    pub(crate) fn verify(&self, headers: &HeaderMap) -> Result<(), UploadError> {
+        let Some((algorithm, stored)) = stored_hash(headers) else {
+            return Ok(());
+        };
+        let expected = match algorithm {
+            "SHA-256" => hex(&self.sha256),
+            "SHA-1" => hex(&self.sha1),
+            _ => hex(&self.md5),
+        };
+        if expected != stored {
+            return Err(UploadError::ChecksumMismatch {
+                algorithm,
+                expected,
+                stored,
+            });
+        }
+        debug!("The backend's {} matches the upload", algorithm);
+        Ok(())
+    }
+}
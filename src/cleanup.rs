@@ -0,0 +1,141 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::io::{Seek, Write};
+use std::path::Path;
+use std::{fs, io};
+use tracing::{debug, error, warn};
+
+use crate::cli::{self, Print};
+use crate::i18n::{self, t};
+use crate::{journal, style};
+
+/// What happens to a file after it has been uploaded
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cleanup {
+    #[default]
+    Ask,
+    Delete,
+    Keep,
+}
+
+/// How a file is deleted once the user agreed to it
+#[derive(Clone, Copy)]
+pub enum Removal {
+    RecycleBin,
+    Permanent,
+    Shred { passes: u32 },
+}
+
+impl Removal {
+    fn done_message(self) -> String {
+        match self {
+            Removal::RecycleBin => t!("moved-to-recycle-bin"),
+            Removal::Permanent => t!("deleted-file"),
+            Removal::Shred { .. } => t!("shredded"),
+        }
+    }
+}
+
+enum DeletionChoice {
+    Yes,
+    No,
+    InvalidChoice,
+}
+
+fn ask(print: Print) -> DeletionChoice {
+    // Keep stdout clean for scripts capturing the output
+    if print != Print::Status {
+        eprintln!("{}", t!("delete-prompt"));
+    } else {
+        println!("{}", t!("delete-prompt"));
+    }
+
+    let mut buf: String = String::new();
+    if let Err(err) = io::stdin().read_line(&mut buf) {
+        warn!("Failed to read the answer, keeping the file: {}", err);
+        return DeletionChoice::No;
+    }
+
+    let answer = buf.trim().to_lowercase();
+    match answer.as_str() {
+        "yes" | "y" => DeletionChoice::Yes,
+        "no" | "n" => DeletionChoice::No,
+        answer if i18n::is_answer("answer-yes", answer) => DeletionChoice::Yes,
+        answer if i18n::is_answer("answer-no", answer) => DeletionChoice::No,
+        _ => DeletionChoice::InvalidChoice,
+    }
+}
+
+/// Removes `file`, saying why it couldn't in the language of the prompt it answers
+fn remove(file: &Path, removal: Removal) -> Result<(), String> {
+    match removal {
+        Removal::RecycleBin => {
+            trash::delete(file).map_err(|err| t!("recycle-failed", error = err.to_string()))
+        }
+        Removal::Permanent => {
+            fs::remove_file(file).map_err(|err| t!("delete-failed", error = err.to_string()))
+        }
+        Removal::Shred { passes } => {
+            shred(file, passes).map_err(|err| t!("shred-failed", error = err.to_string()))
+        }
+    }
+}
+
+/// Overwrites the file with random data before deleting it
+///
+/// This is best-effort, SSDs and copy on write filesystems may keep the old
+/// contents around somewhere the overwrite never reaches
+fn shred(file: &Path, passes: u32) -> io::Result<()> {
+    let len = fs::metadata(file)?.len();
+    let mut f = fs::OpenOptions::new().write(true).open(file)?;
+    let mut buf = vec![0; 1024 * 1024];
+
+    for pass in 0..passes {
+        debug!("Shredding {}, pass {}/{}", file.display(), pass + 1, passes);
+        f.rewind()?;
+
+        let mut written = 0;
+        while written < len {
+            let chunk = buf.len().min((len - written) as usize);
+            rand::thread_rng().fill_bytes(&mut buf[..chunk]);
+            f.write_all(&buf[..chunk])?;
+            written += chunk as u64;
+        }
+        f.sync_all()?;
+    }
+
+    f.set_len(0)?;
+    drop(f);
+    fs::remove_file(file)
+}
+
+pub fn file_cleanup(file: &Path, mode: Cleanup, removal: Removal, print: Print) {
+    let choice = match mode {
+        Cleanup::Delete => DeletionChoice::Yes,
+        Cleanup::Keep => DeletionChoice::No,
+        // Nobody can answer the prompt so keep the file rather than hang
+        Cleanup::Ask if !cli::interactive() => {
+            debug!("Not running in a terminal, keeping {}", file.display());
+            DeletionChoice::No
+        }
+        Cleanup::Ask => ask(print),
+    };
+
+    match choice {
+        DeletionChoice::Yes => match remove(file, removal) {
+            Ok(_) => {
+                if let Removal::RecycleBin = removal {
+                    journal::record(file);
+                }
+                if print == Print::Status {
+                    println!("{}", style::dim(&removal.done_message()));
+                }
+            }
+            Err(err) => error!("{}", err),
+        },
+
+        DeletionChoice::No => (),
+        DeletionChoice::InvalidChoice => warn!("{}", t!("invalid-choice")),
+    }
+}
@@ -0,0 +1,577 @@
+use clap::builder::PossibleValuesParser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ColorChoice, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::archive::ArchiveFormat;
+use crate::cleanup::{Cleanup, Removal};
+use crate::convert::{self, Conversion};
+use crate::crypt;
+use crate::format::CopyFormat;
+use crate::{history, split};
+use uppy::config;
+
+#[derive(Parser, Clone)]
+#[command(
+    version,
+    about = "A cli file uploader",
+    after_help = "Exit codes: 0 success, 1 failure, 2 invalid arguments, 3 configuration error, \
+                  4 authentication error, 5 network error, 6 some files in the batch failed, \
+                  130 stopped with Ctrl+C or Esc in a picker",
+    subcommand_negates_reqs = true
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Files to upload, directories are uploaded with every file inside them, without any they
+    /// are picked from the current directory
+    pub files: Vec<PathBuf>,
+
+    /// Only upload files matching this glob, e.g. "*.png", repeat for more
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Never upload files matching this glob, e.g. "target/", repeat for more
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Follow links inside directories, by default only links given directly are followed
+    #[arg(long, conflicts_with = "no_follow_symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Never follow links, not even ones given directly
+    #[arg(long)]
+    pub no_follow_symlinks: bool,
+
+    /// Configuration profile to use instead of the top level settings
+    #[arg(short, long, global = true)]
+    pub profile: Option<String>,
+
+    /// Format of the text copied to the clipboard
+    #[arg(long, value_enum)]
+    pub copy_format: Option<CopyFormat>,
+
+    /// Template used for stdout and the clipboard, e.g. "{url} ({size}, {mime})"
+    ///
+    /// Placeholders: {url} {raw_url} {path} {name} {size} {mime} {hash} {sha256} {ocr} {host} {elapsed}
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Don't copy anything to the clipboard
+    #[arg(long)]
+    pub no_clipboard: bool,
+
+    /// Show a desktop notification with what was copied once the uploads are done
+    #[arg(long)]
+    pub toast: bool,
+
+    /// What to print to stdout, `url` prints nothing but the result for use in scripts
+    #[arg(long, value_enum, default_value_t = Print::Status)]
+    pub print: Print,
+
+    /// Only print the result and errors, same as `--print url`
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Transcode images to webp or avif before uploading, optionally with a quality such as webp:75
+    #[arg(long, value_name = "FORMAT[:QUALITY]", value_parser = convert::parse)]
+    pub convert: Option<Conversion>,
+
+    /// Scale images down so neither side is larger than this many pixels, keeping the aspect ratio
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_dim: Option<u32>,
+
+    /// Recompress PNGs losslessly before uploading, which often makes screenshots far smaller
+    #[arg(long)]
+    pub optimize: bool,
+
+    /// Remove EXIF, GPS and XMP data from JPEG, PNG and WebP images before uploading a copy
+    #[arg(long)]
+    pub strip_metadata: bool,
+
+    /// Recognize text in uploaded images, for --json and the {ocr} placeholder
+    #[arg(long)]
+    pub ocr: bool,
+
+    /// Upload files under a random name that keeps only the extension
+    #[arg(long)]
+    pub randomize_name: bool,
+
+    /// Sign files with the minisign key and upload the .minisig next to them
+    #[arg(long)]
+    pub sign: bool,
+
+    /// Upload a JSON manifest with the name, SHA-256, size and time of each file next to it
+    #[arg(long)]
+    pub manifest: bool,
+
+    /// A note from the uploader to put in the manifest
+    #[arg(long, requires = "manifest")]
+    pub note: Option<String>,
+
+    /// Strip metadata, randomize names, keep the upload out of the history and the original name out of the webhook
+    #[arg(long)]
+    pub anonymous: bool,
+
+    /// Encrypt files with a random key that only ends up in the URL fragment, open them with `uppy decrypt`
+    #[arg(long)]
+    pub encrypt: bool,
+
+    /// Encrypt files to this age public key before uploading them as <name>.age, repeat for more recipients
+    #[arg(long, value_name = "PUBKEY", value_parser = crypt::parse_recipient, conflicts_with = "encrypt")]
+    pub age_recipient: Vec<age::x25519::Recipient>,
+
+    /// Encrypt files with gpg to this key ID, email or fingerprint from your keyring before uploading them as <name>.gpg, repeat for more recipients
+    #[arg(long, value_name = "KEYID", conflicts_with_all = ["encrypt", "age_recipient"])]
+    pub gpg_recipient: Vec<String>,
+
+    /// Pack the files into a single archive and upload that, directories keep their layout in it
+    ///
+    /// auto makes a zip that leaves photos, videos and other compressed files as they are, and
+    /// the format has to be attached as --archive=tar.zst so it isn't mistaken for a file
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true)]
+    pub archive: Option<Option<ArchiveFormat>>,
+
+    /// Deflate level of zip archives from 0 to 9, higher is smaller and slower
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(i64).range(0..=9))]
+    pub zip_level: Option<i64>,
+
+    /// Compression level of tar.zst archives from 1 to 22, higher is smaller and slower
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(i32).range(1..=22))]
+    pub zstd_level: Option<i32>,
+
+    /// How many threads compress archives, 1 by default
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// Pack the files into a single AES encrypted zip and upload that, asks for the password when given none
+    ///
+    /// The password has to be attached as --zip-password=secret so it isn't mistaken for a file
+    #[arg(long, value_name = "PASSWORD", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    pub zip_password: Option<String>,
+
+    /// Cut files larger than this, e.g. 95MB, into parts uploaded along with a manifest for `uppy join`
+    #[arg(long, value_name = "SIZE", value_parser = split::parse_size)]
+    pub split: Option<u64>,
+
+    /// When the host can't be reached, queue the files and upload them in the background once it can
+    #[arg(long)]
+    pub queue_offline: bool,
+
+    /// How many files to upload at once, over a single HTTP/2 connection when the host supports it
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+
+    /// Which files of a batch are uploaded first, the smallest by default so the quick ones are
+    /// done early
+    #[arg(long, value_enum, default_value_t = Order::SizeAsc)]
+    pub order: Order,
+
+    /// Use HTTP/3 from the first request, experimental and only in builds with the http3 feature
+    #[arg(long, global = true)]
+    pub http3: bool,
+
+    /// Send uploads from this network interface or address, over the local_address configured
+    #[arg(long, global = true, value_name = "NAME|ADDRESS")]
+    pub interface: Option<String>,
+
+    /// Upload files again even when the history or lookup_url shows they were already uploaded, and ones with blocked extensions
+    #[arg(long)]
+    pub force: bool,
+
+    /// Delete files after uploading without asking
+    #[arg(long, conflicts_with = "keep")]
+    pub delete: bool,
+
+    /// Keep files after uploading without asking
+    #[arg(long)]
+    pub keep: bool,
+
+    /// Answer yes to every prompt, which deletes files after uploading
+    #[arg(short, long, conflicts_with = "keep")]
+    pub yes: bool,
+
+    /// Delete files for good instead of moving them to the Recycle Bin
+    #[arg(long)]
+    pub permanent: bool,
+
+    /// Overwrite files with random data before deleting them, best-effort on SSDs
+    #[arg(long, conflicts_with = "permanent")]
+    pub shred: bool,
+
+    /// How many times `--shred` overwrites a file
+    #[arg(long, requires = "shred")]
+    pub shred_passes: Option<u32>,
+
+    /// Show debug output, repeat for trace output
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// When to color output, `auto` respects NO_COLOR and skips colors when not printing to a terminal
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    /// Save every request and answer to a cassette file, with the token and cookies redacted
+    #[arg(long, value_name = "CASSETTE", global = true)]
+    pub record: Option<PathBuf>,
+
+    /// Answer requests from a cassette file saved with `--record` instead of the host
+    #[arg(
+        long,
+        value_name = "CASSETTE",
+        global = true,
+        conflicts_with = "record"
+    )]
+    pub replay: Option<PathBuf>,
+
+    /// Print a JSON object per file instead of status lines, shorthand for `--print json`
+    #[arg(long, conflicts_with = "print")]
+    pub json: bool,
+
+    /// Print the help of every command along with all configuration keys and headers
+    #[arg(long)]
+    pub help_all: bool,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Remove files older versions of uppy left in the temp directory when "deleting" them
+    Gc {
+        /// Only list the files that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Bring back a file that was moved to the Recycle Bin after uploading
+    Restore {
+        /// Restore the most recently deleted file, the default when no path is given
+        #[arg(long, conflicts_with = "path")]
+        last: bool,
+        /// Original path of the file to restore
+        path: Option<PathBuf>,
+    },
+    /// List and search previous uploads
+    History {
+        #[command(flatten)]
+        filter: HistoryFilter,
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+    /// Delete an upload from the host, using its deletion URL when the host gave one
+    Rm {
+        /// URL of the upload or the local path it was uploaded from
+        target: String,
+    },
+    /// Copy the URL of a recent upload to the clipboard again
+    Last {
+        /// Which upload to copy counting back from the most recent, 1 being the last one
+        #[arg(long = "n", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        n: u64,
+    },
+    /// Print a completion script for the shell, profiles in the configuration are completed too
+    Completions { shell: Shell },
+    /// Download and decrypt a file uploaded with --encrypt
+    Decrypt {
+        /// URL of the upload including the #key at the end
+        url: String,
+        /// Where to write the file, its original name in the current directory by default
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Download one of your uploads with the token, so private ones work too
+    Get {
+        /// URL of the upload or the name the host gave it
+        target: String,
+        /// Where to write the file, its name in the current directory by default
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Download the parts of a file uploaded with --split and put it back together
+    Join {
+        /// URL or local path of the manifest
+        manifest: String,
+        /// Where to write the file, its original name in the current directory by default
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Download a file and upload it to the host, flags for the upload go before `fetch`
+    Fetch {
+        /// URL of the file
+        url: String,
+    },
+    /// Upload the files in a directory that changed since the last sync and write a map of their
+    /// URLs, flags for the uploads go before `sync`
+    Sync {
+        dir: PathBuf,
+        /// Where to write the map, `.uppy-sync.json` in the directory by default
+        #[arg(long)]
+        map: Option<PathBuf>,
+        /// Check that the host still has the unchanged files and upload those it lost again
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Upload the files copied in Explorer, or the copied image when there are none
+    Clip,
+    /// Check off which files of a directory to upload in a list that previews images
+    Pick { dir: PathBuf },
+    /// Pick files, follow their uploads and go through the history on a screen of its own
+    Tui {
+        /// Where the files to pick are, the current directory by default
+        dir: Option<PathBuf>,
+    },
+    /// Upload every file that shows up in a directory from now on, once it is done being written
+    Watch {
+        dir: PathBuf,
+        /// Milliseconds a file has to stay the same before it is uploaded, 2000 by default
+        #[arg(long)]
+        debounce: Option<u64>,
+    },
+    /// Run a capture and upload from `pipelines` in the configuration, to bind to a hotkey
+    Pipeline {
+        /// Name of the pipeline
+        name: String,
+    },
+    /// Upload every image copied from now on and put its URL on the clipboard instead
+    WatchClipboard {
+        /// Upload files copied in Explorer too
+        #[arg(long)]
+        files: bool,
+    },
+    /// Answer a browser extension over native messaging, the command its host manifest runs
+    NativeHost {
+        /// The extension origin and window the browser passes along
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+        caller: Vec<String>,
+    },
+    /// Continue a batch that was cut short, uploading the files it didn't get to
+    Resume,
+    /// Upload the files that failed in the last batch again, listing the ones that made it too
+    RetryFailed,
+    /// Upload random payloads to the host and report latency and throughput, deleting them afterwards
+    Bench {
+        /// Payload sizes to try, separated by commas
+        #[arg(long, value_delimiter = ',', value_parser = split::parse_size, default_values = ["100KB", "1MB", "10MB"])]
+        sizes: Vec<u64>,
+        /// Uploads per size
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+        /// Leave the uploads on the host
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Show what every running uppy is uploading, the queue and how the last uploads went
+    Status,
+    /// List the files queued by --queue-offline
+    Queue {
+        #[command(subcommand)]
+        command: Option<QueueCommand>,
+    },
+    /// Print a man page covering every flag, configuration key and header
+    Manpage,
+    /// Replace uppy with the latest release from GitHub after verifying its checksum
+    SelfUpdate {
+        /// Only check whether a newer version is out
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(clap::Args, Clone)]
+pub struct HistoryFilter {
+    /// Show at most this many uploads
+    #[arg(long)]
+    pub limit: Option<usize>,
+    /// Only show uploads newer than this, e.g. 30m, 12h, 7d or 2w
+    #[arg(long, value_parser = parse_age)]
+    pub since: Option<Duration>,
+    /// Only show uploads whose path or URL contains this text
+    #[arg(long)]
+    pub grep: Option<String>,
+}
+
+impl From<&HistoryFilter> for history::Filter {
+    fn from(filter: &HistoryFilter) -> Self {
+        history::Filter {
+            limit: filter.limit,
+            since: filter.since,
+            grep: filter.grep.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand, Clone)]
+pub enum HistoryCommand {
+    /// Write the matching uploads to a self-contained HTML page in the directory
+    Gallery { out: PathBuf },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum QueueCommand {
+    /// Upload the queued files now, which uppy otherwise does in the background
+    Flush {
+        /// Keep retrying with a growing delay until every file is uploaded
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Hold back uploads in the background, the offline queue and uppy watch, until resumed
+    Pause,
+    /// Carry on with the uploads in the background
+    Resume,
+    /// Drop every queued file without uploading it
+    Clear,
+}
+
+fn parse_age(age: &str) -> Result<Duration, String> {
+    let split = age.len() - age.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (amount, unit) = age.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("\"{}\" doesn't start with a number", age))?;
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit \"{}\", use s, m, h, d or w", unit)),
+    };
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("\"{}\" is too long ago", age))
+}
+
+/// The order the files of a batch are uploaded in, the clipboard keeps the order they were given in
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Order {
+    SizeAsc,
+    AsGiven,
+    LargestFirst,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Print {
+    Status,
+    Url,
+    Json,
+}
+
+impl Args {
+    /// Cleanup chosen on the command line, if any
+    pub fn cleanup(&self) -> Option<Cleanup> {
+        if self.delete || self.yes {
+            Some(Cleanup::Delete)
+        } else if self.keep {
+            Some(Cleanup::Keep)
+        } else {
+            None
+        }
+    }
+
+    pub fn removal(&self, shred_passes: u32) -> Removal {
+        if self.shred {
+            Removal::Shred {
+                passes: self.shred_passes.unwrap_or(shred_passes),
+            }
+        } else if self.permanent {
+            Removal::Permanent
+        } else {
+            Removal::RecycleBin
+        }
+    }
+}
+
+/// Writes the completion script to stdout, with the profile names that exist right now baked in
+pub fn completions(shell: Shell) {
+    let profiles = config::profile_names();
+    let mut command = Args::command();
+    if !profiles.is_empty() {
+        command = command.mut_arg("profile", |arg| {
+            arg.value_parser(PossibleValuesParser::new(profiles))
+        });
+    }
+    clap_complete::generate(shell, &mut command, "uppy", &mut io::stdout());
+}
+
+/// The flags this run was given, without its files and subcommand, for running it again on other
+/// files later
+///
+/// Rebuilt from what clap parsed so a value like `--name watch` is kept whole. The password of
+/// `--zip-password` is left out, running them again asks for it
+pub fn flags() -> Vec<String> {
+    let Ok(matches) = Args::command().try_get_matches_from(env::args_os()) else {
+        return Vec::new();
+    };
+    let mut flags = Vec::new();
+    for arg in Args::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        if arg.is_positional() || matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let name = match (arg.get_long(), arg.get_short()) {
+            (Some(long), _) => format!("--{}", long),
+            (None, Some(short)) => format!("-{}", short),
+            (None, None) => continue,
+        };
+        match arg.get_action() {
+            ArgAction::SetTrue | ArgAction::SetFalse => flags.push(name),
+            ArgAction::Count => {
+                flags.extend((0..matches.get_count(id)).map(|_| name.clone()));
+            }
+            _ if id == "zip_password" => flags.push(name),
+            _ => flags.extend(
+                matches
+                    .get_raw(id)
+                    .into_iter()
+                    .flatten()
+                    .map(|raw| format!("{}={}", name, raw.to_string_lossy())),
+            ),
+        }
+    }
+    flags
+}
+
+/// Fails like clap does when the files are missing, for when there is no console to pick them in
+pub fn missing_files() -> ! {
+    Args::command()
+        .error(
+            ErrorKind::MissingRequiredArgument,
+            "the following required arguments were not provided:\n  <FILES>...",
+        )
+        .exit()
+}
+
+/// Whether someone is around to answer prompts and watch progress, false when piped or run from a script
+pub fn interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_age_reads_units() {
+        assert_eq!(parse_age("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_age("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_age("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 24 * 60 * 60)));
+        assert_eq!(parse_age("1w"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+    }
+
+    #[test]
+    fn parse_age_refuses_nonsense() {
+        assert!(parse_age("").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("30y").is_err());
+        assert!(parse_age("-1d").is_err());
+        assert!(parse_age("18446744073709551615w").is_err());
+    }
+}
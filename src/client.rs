@@ -0,0 +1,820 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::blocking::{self, multipart};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_RANGE};
+use reqwest::StatusCode;
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, trace, warn};
+
+use crate::capabilities::{self, Flavor};
+use crate::cassette::{Cassette, Interaction};
+use crate::checksum::Hashes;
+use crate::config::{ClientConfig, ConfigError, FilenameEncoding};
+use crate::interface;
+use crate::oauth::OAuth;
+use crate::partial::Partial;
+use crate::pin;
+use crate::progress::{Progress, ProgressFn, UploadBody};
+use crate::response::Extract;
+
+/// How long idle connections to the host are kept open for the next upload
+const KEEPALIVE: Duration = Duration::from_secs(90);
+/// How often a chunk that didn't make it is sent again, waiting twice as long each time
+const CHUNK_RETRIES: u32 = 5;
+/// What `filename_encoding: percent` encodes, everything but the characters unreserved in URLs
+const FILENAME_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A file to upload
+pub struct UploadRequest {
+    pub path: PathBuf,
+    /// Name the host is told the file has, the name of `path` when unset
+    pub file_name: Option<String>,
+}
+
+impl UploadRequest {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UploadRequest {
+            path: path.into(),
+            file_name: None,
+        }
+    }
+
+    pub fn with_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    fn file_name(&self) -> String {
+        self.file_name.clone().unwrap_or_else(|| {
+            self.path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// What the host answered with after a successful upload
+pub struct UploadResponse {
+    pub url: String,
+    /// Secret link that deletes the file, only some hosts hand these out
+    pub deletion_url: Option<String>,
+    /// How long the request took
+    pub elapsed: Duration,
+    /// SHA-256 of the bytes that were sent, in hex
+    pub sha256: String,
+    /// BLAKE3 of the bytes that were sent, in hex
+    pub blake3: String,
+    /// Which token the upload went through, counting from 0 over `token` and then `tokens`
+    pub token: usize,
+    /// What the host answered with, some 2xx
+    pub status: StatusCode,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    /// The file could not be read
+    #[error("Something went wrong while loading the targeted file: {0}")]
+    IOError(#[source] std::io::Error),
+    /// The request never got an answer
+    #[error("Something went wrong while sending the HTTP request: {0}")]
+    ReqwestError(#[source] reqwest::Error),
+    #[error("A HTTP client error occurred, code: {0}")]
+    HTTPClientError(StatusCode),
+    #[error("A HTTP server error occured, code: {0}")]
+    HTTPServerError(StatusCode),
+    /// The host answered with something that isn't an upload response
+    #[error("The host sent an invalid response: {0}")]
+    InvalidResponse(String),
+    /// Replaying a cassette that never saw this request
+    #[error("The cassette has no recorded answer for {0}")]
+    NotRecorded(String),
+    /// The token endpoint didn't hand out an access token
+    #[error("Failed to refresh the OAuth access token: {0}")]
+    OAuth(String),
+    /// The backend stored something other than what was sent
+    #[error(
+        "The upload was corrupted, the host's {algorithm} is {stored} but the file's is {expected}"
+    )]
+    ChecksumMismatch {
+        algorithm: &'static str,
+        expected: String,
+        stored: String,
+    },
+    /// The flag passed to [`Client::with_cancel`] was set
+    #[error("The upload was cancelled")]
+    Cancelled,
+}
+
+/// Uploads files to a Zipline host
+///
+/// Connections are kept alive between requests, so one client reused for a whole batch only
+/// pays for the TLS handshake once
+pub struct Client {
+    host: String,
+    headers: HeaderMap,
+    /// Every token to try in turn, starting with the one that was last accepted
+    tokens: Vec<HeaderValue>,
+    token: AtomicUsize,
+    /// Takes the place of the tokens for hosts behind OAuth
+    oauth: Option<OAuth>,
+    http: blocking::Client,
+    cassette: Option<Cassette>,
+    progress: Option<Arc<ProgressFn>>,
+    cancel: Option<Arc<AtomicBool>>,
+    extract: Extract,
+    filename_encoding: FilenameEncoding,
+    lookup_url: Option<String>,
+    chunk_size: Option<u64>,
+    /// `zipline_version`, otherwise asked of the host on the first upload
+    zipline_version: Option<u32>,
+    flavor: OnceLock<Option<Flavor>>,
+    /// Kept to build the HTTP/3 client with
+    #[cfg_attr(not(feature = "http3"), allow(dead_code))]
+    transport: Transport,
+}
+
+/// The parts of an answer uppy looks at, whether it came from the host or a cassette
+struct Reply {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+/// What Zipline wants with every request, then the `headers` of the configuration
+fn construct_headers(config: &ClientConfig) -> Result<HeaderMap, ConfigError> {
+    let mut headers = HeaderMap::new();
+    headers.insert("Format", HeaderValue::from_static("RANDOM"));
+    headers.insert("Embed", HeaderValue::from_static("true"));
+    for (name, value) in &config.headers {
+        let invalid = || ConfigError::InvalidHeader(name.clone());
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+        if value.is_empty() {
+            headers.remove(&name);
+            continue;
+        }
+        let mut value = HeaderValue::from_str(value).map_err(|_| invalid())?;
+        // API keys end up in these as often as not
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+/// `token` followed by `tokens`, hosts that need no token still get the empty one
+fn construct_tokens(config: &ClientConfig) -> Result<Vec<HeaderValue>, ConfigError> {
+    let mut tokens: Vec<&String> = iter::once(&config.token)
+        .chain(&config.tokens)
+        .filter(|token| !token.is_empty())
+        .collect();
+    if tokens.is_empty() {
+        tokens.push(&config.token);
+    }
+    tokens
+        .into_iter()
+        .map(|token| {
+            let mut token = HeaderValue::from_str(token).map_err(|_| ConfigError::InvalidToken)?;
+            // Keeps the token out of debug logs
+            token.set_sensitive(true);
+            Ok(token)
+        })
+        .collect()
+}
+
+/// Answers that another token might not get, refused ones and rate limits
+fn try_next_token(err: &UploadError) -> bool {
+    matches!(
+        err,
+        UploadError::HTTPClientError(
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        )
+    )
+}
+
+/// How connections to the host are made, from the configuration
+struct Transport {
+    /// Set up for `pinned_sha256`
+    tls: Option<rustls::ClientConfig>,
+    /// `resolve`, with port 0 standing for the one in the URL
+    resolve: Vec<(String, SocketAddr)>,
+    /// `local_address`, or the address of the interface it names
+    local_address: Option<IpAddr>,
+}
+
+/// `10.0.0.5:443` or just `10.0.0.5`, IPv6 addresses in brackets when they come with a port
+fn parse_address(address: &str) -> Option<SocketAddr> {
+    let address = address.trim();
+    address
+        .parse()
+        .or_else(|_| address.parse().map(|ip: IpAddr| SocketAddr::new(ip, 0)))
+        .ok()
+}
+
+impl Transport {
+    fn new(config: &ClientConfig) -> Result<Self, ConfigError> {
+        let tls = match &config.pinned_sha256 {
+            Some(pin) => Some(pin::tls_config(&config.host, pin)?),
+            None => None,
+        };
+        let resolve = config
+            .resolve
+            .iter()
+            .map(|(domain, address)| {
+                let address = parse_address(address)
+                    .ok_or_else(|| ConfigError::InvalidAddress(address.clone()))?;
+                Ok((domain.clone(), address))
+            })
+            .collect::<Result<_, ConfigError>>()?;
+        let local_address = match &config.local_address {
+            Some(interface) => Some(interface::local_address(interface)?),
+            None => None,
+        };
+        Ok(Transport {
+            tls,
+            resolve,
+            local_address,
+        })
+    }
+
+    /// HTTP/2 is negotiated when the host offers it, so parallel uploads share a single connection
+    fn builder(&self) -> blocking::ClientBuilder {
+        let mut builder = blocking::Client::builder()
+            .tcp_keepalive(KEEPALIVE)
+            .pool_idle_timeout(KEEPALIVE)
+            .http2_adaptive_window(true);
+        if let Some(tls) = &self.tls {
+            builder = builder.use_preconfigured_tls(tls.clone());
+        }
+        for (domain, address) in &self.resolve {
+            builder = builder.resolve(domain, *address);
+        }
+        if let Some(address) = self.local_address {
+            builder = builder.local_address(address);
+        }
+        builder
+    }
+
+    /// Fails rather than falling back on a default client, which would drop the pin, `resolve`
+    /// and `local_address`
+    fn build(&self) -> Result<blocking::Client, ConfigError> {
+        self.builder().build().map_err(|err| match self.tls {
+            Some(_) => ConfigError::Tls(err.to_string()),
+            None => ConfigError::HttpClient(err.to_string()),
+        })
+    }
+}
+
+fn check_status(reply: Reply) -> Result<Reply, UploadError> {
+    if reply.status.is_client_error() {
+        Err(UploadError::HTTPClientError(reply.status))
+    } else if reply.status.is_server_error() {
+        Err(UploadError::HTTPServerError(reply.status))
+    } else {
+        Ok(reply)
+    }
+}
+
+impl Client {
+    /// Fails when the token or one of the `headers` can't be sent, or `response_url_regex` isn't
+    /// a valid regex
+    pub fn new(config: &ClientConfig) -> Result<Self, ConfigError> {
+        let transport = Transport::new(config)?;
+        Ok(Client {
+            host: config.host.clone(),
+            headers: construct_headers(config)?,
+            tokens: construct_tokens(config)?,
+            token: AtomicUsize::new(0),
+            oauth: config
+                .oauth
+                .as_ref()
+                .map(|oauth| OAuth::new(oauth, &config.host)),
+            http: transport.build()?,
+            cassette: None,
+            progress: None,
+            cancel: None,
+            extract: Extract::new(config)?,
+            filename_encoding: config.filename_encoding,
+            lookup_url: config.lookup_url.clone(),
+            chunk_size: config.chunk_size.filter(|&size| size > 0),
+            zipline_version: config.zipline_version,
+            flavor: OnceLock::new(),
+            transport,
+        })
+    }
+
+    /// Sends requests to `host` instead of the configured one, e.g. a mock server in tests
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Sends requests through a pre-configured reqwest client, for custom TLS, proxies and the like
+    ///
+    /// The token and upload headers are still added to every request, `pinned_sha256` is up to
+    /// the client
+    pub fn with_http_client(mut self, http: blocking::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Calls `progress` as each file is sent, from whichever thread reqwest reads the body on
+    pub fn with_progress(mut self, progress: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Aborts uploads once `cancel` is set, even halfway through sending a file, failing them
+    /// with [`UploadError::Cancelled`]
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Talks HTTP/3 to the host from the first request, so only use it for hosts known to support it
+    ///
+    /// Experimental, see the `http3` feature
+    #[cfg(feature = "http3")]
+    pub fn with_http3(mut self) -> reqwest::Result<Self> {
+        self.http = self.transport.builder().http3_prior_knowledge().build()?;
+        Ok(self)
+    }
+
+    /// Records every request to the cassette or answers them from it, see [`Cassette`]
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    fn send(
+        &self,
+        request: blocking::RequestBuilder,
+        file: Option<&Path>,
+    ) -> Result<Reply, UploadError> {
+        let request = request.build().map_err(UploadError::ReqwestError)?;
+        let mut interaction = Interaction::new(&request, file);
+
+        if let Some(cassette) = self
+            .cassette
+            .as_ref()
+            .filter(|cassette| cassette.is_replay())
+        {
+            let recorded = cassette.answer(&request).ok_or_else(|| {
+                UploadError::NotRecorded(format!("{} {}", request.method(), request.url()))
+            })?;
+            debug!(status = recorded.status, "Replayed response");
+            let status = StatusCode::from_u16(recorded.status)
+                .map_err(|err| UploadError::InvalidResponse(err.to_string()))?;
+            let headers = recorded
+                .response_headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.parse().ok()?, HeaderValue::from_str(value).ok()?))
+                })
+                .collect();
+            return check_status(Reply {
+                status,
+                headers,
+                body: recorded.body,
+            });
+        }
+
+        let res = self
+            .http
+            .execute(request)
+            .map_err(UploadError::ReqwestError)?;
+        debug!(status = %res.status(), headers = ?res.headers(), "Received response");
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.text().map_err(UploadError::ReqwestError)?;
+        trace!(body = %body, "Response body");
+
+        if let Some(cassette) = &self.cassette {
+            interaction.answer(status.as_u16(), &headers, &body);
+            cassette.save(interaction);
+        }
+        check_status(Reply {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// The upload headers with the token at `token`, or the OAuth access token
+    fn headers(&self, token: usize) -> Result<HeaderMap, UploadError> {
+        let mut headers = self.headers.clone();
+        // Hosts that authenticate through `headers` get no token
+        if self.oauth.is_none() && headers.contains_key(AUTHORIZATION) {
+            return Ok(headers);
+        }
+        let replay = self.cassette.as_ref().is_some_and(Cassette::is_replay);
+        let authorization = match &self.oauth {
+            // Replayed answers don't care, and refreshing would go over the network
+            Some(oauth) if !replay => oauth.header(&self.http)?,
+            _ => self.tokens[token].clone(),
+        };
+        headers.insert(AUTHORIZATION, authorization);
+        Ok(headers)
+    }
+
+    /// Which version of Zipline the host runs, asking it the first time unless it was asked
+    /// recently, `None` when it didn't say
+    fn flavor(&self, token: usize) -> Option<Flavor> {
+        *self.flavor.get_or_init(|| {
+            if let Some(major) = self.zipline_version {
+                return Flavor::from_major(major);
+            }
+            // Requests the cassette doesn't expect would fail replaying it
+            if self.cassette.is_some() {
+                return None;
+            }
+            let major = match capabilities::cached(&self.host) {
+                Some(major) => major,
+                None => {
+                    let endpoint = format!("{}/api/version", self.host);
+                    let reply = self.headers(token).and_then(|headers| {
+                        self.send(self.http.get(&endpoint).headers(headers), None)
+                    });
+                    // Only a version or a host without the endpoint is worth remembering, anything
+                    // else is asked again on the next run
+                    let major = match reply {
+                        Ok(reply) => match capabilities::major_version(&reply.body) {
+                            Some(major) => Some(major),
+                            None => {
+                                debug!("The host answered /api/version without a version");
+                                return None;
+                            }
+                        },
+                        Err(UploadError::HTTPClientError(StatusCode::NOT_FOUND)) => None,
+                        Err(err) => {
+                            debug!("Failed to ask the host for its version: {}", err);
+                            return None;
+                        }
+                    };
+                    capabilities::remember(&self.host, major);
+                    major
+                }
+            };
+            debug!(major = ?major, "Host runs Zipline");
+            major.and_then(Flavor::from_major)
+        })
+    }
+
+    /// [`Client::headers`] for uploads, under the names the version of the host knows
+    fn upload_headers(&self, token: usize) -> Result<HeaderMap, UploadError> {
+        let mut headers = self.headers(token)?;
+        if let Some(flavor) = self.flavor(token) {
+            capabilities::adapt(&mut headers, flavor);
+        }
+        Ok(headers)
+    }
+
+    /// Runs `attempt` with the token that was last accepted, moving on to the next one when
+    /// the host refuses it until every token was tried
+    ///
+    /// An OAuth access token the host refuses is refreshed once and tried again
+    fn with_token<T>(
+        &self,
+        attempt: impl Fn(usize) -> Result<T, UploadError>,
+    ) -> Result<T, UploadError> {
+        let mut token = self.token.load(Ordering::Relaxed);
+        let mut refreshed = false;
+        for tried in 1.. {
+            match attempt(token) {
+                Err(UploadError::HTTPClientError(StatusCode::UNAUTHORIZED))
+                    if !refreshed && self.oauth.is_some() =>
+                {
+                    debug!("The host refused the access token, refreshing it");
+                    if let Some(oauth) = &self.oauth {
+                        oauth.expire();
+                    }
+                    refreshed = true;
+                }
+                Err(err) if try_next_token(&err) && tried < self.tokens.len() => {
+                    let next = (token + 1) % self.tokens.len();
+                    warn!(
+                        "Token {} was refused ({}), trying token {}",
+                        token + 1,
+                        err,
+                        next + 1
+                    );
+                    self.token.store(next, Ordering::Relaxed);
+                    token = next;
+                }
+                result => return result,
+            }
+        }
+        unreachable!("the loop only ends by returning")
+    }
+
+    /// Base URL of the host uploads go to
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn upload(&self, request: &UploadRequest) -> Result<UploadResponse, UploadError> {
+        if self.cancelled() {
+            return Err(UploadError::Cancelled);
+        }
+        match self.with_token(|token| self.upload_with(request, token)) {
+            // Whatever cutting the body short turned into
+            Err(_) if self.cancelled() => Err(UploadError::Cancelled),
+            result => result,
+        }
+    }
+
+    fn upload_with(
+        &self,
+        request: &UploadRequest,
+        token: usize,
+    ) -> Result<UploadResponse, UploadError> {
+        let file = File::open(&request.path).map_err(UploadError::IOError)?;
+        let meta = file.metadata().map_err(UploadError::IOError)?;
+        let len = meta.len();
+        if let Some(chunk_size) = self.chunk_size.filter(|&size| len > size) {
+            return self.upload_chunked(request, token, file, &meta, chunk_size);
+        }
+        let hashes = Arc::new(Mutex::new(Hashes::default()));
+        // The same name and type `Form::file` would send, only read through the hasher
+        let body = UploadBody::new(
+            file,
+            &request.path,
+            len,
+            Arc::clone(&hashes),
+            self.progress.clone(),
+            self.cancel.clone(),
+        );
+        let form = multipart::Form::new().part("file", self.part(request, body, len)?);
+
+        let endpoint = format!("{}/api/upload", self.host);
+        let headers = self.upload_headers(token)?;
+        debug!(url = %endpoint, headers = ?headers, file = %request.path.display(), "Sending upload request");
+
+        let started = Instant::now();
+        let builder = self.http.post(endpoint).multipart(form).headers(headers);
+        let reply = self.send(builder, Some(&request.path))?;
+        let elapsed = started.elapsed();
+
+        let streamed = hashes
+            .lock()
+            .map(|mut hashes| std::mem::take(&mut *hashes))
+            .ok()
+            .filter(|hashes| hashes.len() == len);
+        let digests = match streamed {
+            Some(hashes) => hashes,
+            // Nothing went out when the answer came from a cassette
+            None => Hashes::of_file(&request.path).map_err(UploadError::IOError)?,
+        }
+        .finish();
+        digests.verify(&reply.headers)?;
+
+        let (url, deletion_url) = self.extract.urls(&reply.body)?;
+        Ok(UploadResponse {
+            url,
+            deletion_url,
+            elapsed,
+            sha256: digests.sha256(),
+            blake3: digests.blake3(),
+            token,
+            status: reply.status,
+        })
+    }
+
+    /// The file part of the upload form, with the same name and type `Form::file` would send
+    fn part(
+        &self,
+        request: &UploadRequest,
+        body: impl Read + Send + 'static,
+        len: u64,
+    ) -> Result<multipart::Part, UploadError> {
+        let file_name = request.file_name();
+        multipart::Part::reader_with_length(body, len)
+            .file_name(match self.filename_encoding {
+                FilenameEncoding::Utf8 => file_name,
+                FilenameEncoding::Percent => {
+                    utf8_percent_encode(&file_name, FILENAME_ENCODE_SET).to_string()
+                }
+            })
+            .mime_str(
+                mime_guess::from_path(&request.path)
+                    .first_or_octet_stream()
+                    .as_ref(),
+            )
+            .map_err(UploadError::ReqwestError)
+    }
+
+    /// Sends the file in chunks of `chunk_size` that Zipline puts back together, see [`Partial`]
+    ///
+    /// A chunk that doesn't make it is sent again a few times before giving up, and the chunks
+    /// the host confirmed are skipped by the next attempt
+    fn upload_chunked(
+        &self,
+        request: &UploadRequest,
+        token: usize,
+        mut file: File,
+        meta: &Metadata,
+        chunk_size: u64,
+    ) -> Result<UploadResponse, UploadError> {
+        let len = meta.len();
+        let mut partial = Partial::resume_or_start(&self.host, &request.path, meta, chunk_size);
+        let endpoint = format!("{}/api/upload", self.host);
+        let mime = mime_guess::from_path(&request.path).first_or_octet_stream();
+        // Headers can't carry anything but ASCII
+        let file_name = utf8_percent_encode(&request.file_name(), FILENAME_ENCODE_SET).to_string();
+
+        // Chunks are hashed once the host has them, unless earlier ones went out in another run
+        let mut streamed = (partial.confirmed == 0).then(Hashes::default);
+        let started = Instant::now();
+        let reply = loop {
+            let start = partial.confirmed;
+            let end = (start + chunk_size).min(len);
+            let last = end == len;
+            let mut chunk = Vec::with_capacity((end - start) as usize);
+            file.seek(SeekFrom::Start(start))
+                .and_then(|_| (&mut file).take(end - start).read_to_end(&mut chunk))
+                .map_err(UploadError::IOError)?;
+
+            let mut retries = 0;
+            let reply = loop {
+                let hashes = Arc::new(Mutex::new(Hashes::default()));
+                let body = UploadBody::new(
+                    io::Cursor::new(chunk.clone()),
+                    &request.path,
+                    len,
+                    hashes,
+                    self.progress.clone(),
+                    self.cancel.clone(),
+                )
+                .starting_at(start);
+                let form =
+                    multipart::Form::new().part("file", self.part(request, body, end - start)?);
+                let mut headers = self.upload_headers(token)?;
+                let header = |value: String| {
+                    HeaderValue::from_str(&value)
+                        .map_err(|err| UploadError::InvalidResponse(err.to_string()))
+                };
+                headers.insert(
+                    CONTENT_RANGE,
+                    header(format!("bytes {}-{}/{}", start, end - 1, len))?,
+                );
+                headers.insert("x-zipline-partial-filename", header(file_name.clone())?);
+                headers.insert("x-zipline-partial-mimetype", header(mime.to_string())?);
+                headers.insert(
+                    "x-zipline-partial-identifier",
+                    header(partial.identifier.clone())?,
+                );
+                headers.insert("x-zipline-partial-lastchunk", header(last.to_string())?);
+                debug!(url = %endpoint, range = ?headers.get(CONTENT_RANGE), "Sending a chunk");
+
+                let builder = self.http.post(&endpoint).multipart(form).headers(headers);
+                match self.send(builder, Some(&request.path)) {
+                    Err(err @ (UploadError::ReqwestError(_) | UploadError::HTTPServerError(_)))
+                        if retries < CHUNK_RETRIES && !self.cancelled() =>
+                    {
+                        retries += 1;
+                        let wait = Duration::from_secs(1 << retries);
+                        warn!(
+                            "The chunk at {} of {} didn't make it ({}), sending it again in {:?}",
+                            start,
+                            request.path.display(),
+                            err,
+                            wait
+                        );
+                        thread::sleep(wait);
+                    }
+                    result => break result?,
+                }
+            };
+            if let Some(hashes) = &mut streamed {
+                hashes.update(&chunk);
+            }
+            if last {
+                break reply;
+            }
+            partial.confirm(end);
+        };
+        let elapsed = started.elapsed();
+        partial.finish();
+
+        let digests = match streamed {
+            Some(hashes) => hashes,
+            None => Hashes::of_file(&request.path).map_err(UploadError::IOError)?,
+        }
+        .finish();
+        digests.verify(&reply.headers)?;
+        let (url, deletion_url) = self.extract.urls(&reply.body)?;
+        Ok(UploadResponse {
+            url,
+            deletion_url,
+            elapsed,
+            sha256: digests.sha256(),
+            blake3: digests.blake3(),
+            token,
+            status: reply.status,
+        })
+    }
+
+    /// Asks `lookup_url` for the URL of a file the host already has with this SHA-256, `None`
+    /// when it doesn't or there is no `lookup_url`
+    pub fn lookup(&self, sha256: &str) -> Result<Option<String>, UploadError> {
+        let Some(lookup_url) = &self.lookup_url else {
+            return Ok(None);
+        };
+        let url = lookup_url.replace("{sha256}", sha256);
+        debug!("Looking the file up through {}", url);
+
+        let reply = self
+            .with_token(|token| self.send(self.http.get(&url).headers(self.headers(token)?), None));
+        match reply {
+            Err(UploadError::HTTPClientError(StatusCode::NOT_FOUND)) => Ok(None),
+            Err(err) => Err(err),
+            Ok(reply) if reply.status == StatusCode::NO_CONTENT => Ok(None),
+            Ok(reply) => self.extract.found(&reply.body),
+        }
+    }
+
+    /// Deletes an upload through the authenticated API, Zipline looks files up by name
+    pub fn delete(&self, url: &str) -> Result<(), UploadError> {
+        let name = url
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+        let endpoint = format!("{}/api/user/files/{}", self.host, name);
+        debug!("Deleting through {}", endpoint);
+
+        self.with_token(|token| {
+            self.send(
+                self.http.delete(&endpoint).headers(self.headers(token)?),
+                None,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Streams an upload into `out`, returning how many bytes were written
+    ///
+    /// The token only goes along to URLs on this host, where it lets private uploads through.
+    /// Downloads always go over the network, cassettes only cover the other requests
+    pub fn download(&self, url: &str, out: &mut impl Write) -> Result<u64, UploadError> {
+        debug!("Downloading {}", url);
+        let mut request = self.http.get(url);
+        if url.starts_with(&format!("{}/", self.host.trim_end_matches('/'))) {
+            request = request.headers(self.headers(self.token.load(Ordering::Relaxed))?);
+        }
+        let mut res = request.send().map_err(UploadError::ReqwestError)?;
+        debug!(status = %res.status(), headers = ?res.headers(), "Received response");
+
+        let status = res.status();
+        if status.is_client_error() {
+            return Err(UploadError::HTTPClientError(status));
+        } else if status.is_server_error() {
+            return Err(UploadError::HTTPServerError(status));
+        }
+        io::copy(&mut res, out).map_err(UploadError::IOError)
+    }
+
+    /// Whether an upload is still on the host, going by a HEAD request answered with 404 or 410
+    /// once it is gone
+    ///
+    /// Like [`Client::download`] the token only goes along to URLs on this host
+    pub fn exists(&self, url: &str) -> Result<bool, UploadError> {
+        debug!("Checking whether {} is still there", url);
+        let mut request = self.http.head(url);
+        if url.starts_with(&format!("{}/", self.host.trim_end_matches('/'))) {
+            request = request.headers(self.headers(self.token.load(Ordering::Relaxed))?);
+        }
+        let status = request.send().map_err(UploadError::ReqwestError)?.status();
+        debug!(status = %status, "Received response");
+
+        match status {
+            StatusCode::NOT_FOUND | StatusCode::GONE => Ok(false),
+            status if status.is_client_error() => Err(UploadError::HTTPClientError(status)),
+            status if status.is_server_error() => Err(UploadError::HTTPServerError(status)),
+            _ => Ok(true),
+        }
+    }
+
+    /// Deletes an upload by visiting the secret deletion link hosts like 0x0.st and uguu hand out
+    pub fn delete_with_url(&self, deletion_url: &str) -> Result<(), UploadError> {
+        debug!("Deleting through {}", deletion_url);
+        self.send(self.http.get(deletion_url), None)?;
+        Ok(())
+    }
+}
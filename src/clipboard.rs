@@ -0,0 +1,120 @@
+use clipboard_win::{formats, get_clipboard, raw};
+use image::ImageFormat;
+use std::ffi::OsStr;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::cancel;
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::temp::TempCopy;
+
+/// How often the clipboard is checked for something new
+const POLL: Duration = Duration::from_millis(500);
+/// How long picking a region may take before the capture is given up on
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Something worth uploading that was copied
+pub enum Copied {
+    /// A screenshot or copied image, saved as a PNG
+    Image(TempCopy),
+    /// Files copied in Explorer
+    Files(Vec<PathBuf>),
+}
+
+fn image() -> Result<TempCopy, String> {
+    let bitmap: Vec<u8> = get_clipboard(formats::Bitmap).map_err(|err| err.to_string())?;
+    let image = image::load_from_memory_with_format(&bitmap, ImageFormat::Bmp)
+        .map_err(|err| err.to_string())?;
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| err.to_string())?;
+
+    let name = chrono::Local::now()
+        .format("clipboard-%Y%m%d-%H%M%S.png")
+        .to_string();
+    TempCopy::write(OsStr::new(&name), &png).map_err(|err| err.to_string())
+}
+
+/// What is on the clipboard, if it is something to upload
+fn copied(files: bool) -> Result<Option<Copied>, String> {
+    if files && raw::is_format_avail(formats::CF_HDROP) {
+        let paths: Vec<String> = get_clipboard(formats::FileList).map_err(|err| err.to_string())?;
+        return Ok(Some(Copied::Files(
+            paths.into_iter().map(PathBuf::from).collect(),
+        )));
+    }
+    if raw::is_format_avail(formats::CF_BITMAP) {
+        return image().map(|copy| Some(Copied::Image(copy)));
+    }
+    Ok(None)
+}
+
+/// What `uppy clip` uploads, the files copied in Explorer or otherwise a copied image
+pub fn read() -> Result<Copied, Error> {
+    copied(true)
+        .map_err(Error::ReadClipboard)?
+        .ok_or(Error::EmptyClipboard)
+}
+
+/// Opens the snipping overlay Windows has built in and returns the region that was picked,
+/// which it leaves on the clipboard
+pub fn capture_region() -> Result<TempCopy, Error> {
+    let seen = raw::seq_num();
+    Command::new("explorer.exe")
+        .arg("ms-screenclip:")
+        .spawn()
+        .map_err(Error::Capture)?;
+
+    let started = Instant::now();
+    while started.elapsed() < CAPTURE_TIMEOUT {
+        thread::sleep(POLL);
+        if raw::seq_num() != seen && raw::is_format_avail(formats::CF_BITMAP) {
+            return image().map_err(Error::ReadClipboard);
+        }
+    }
+    Err(Error::NoCapture)
+}
+
+/// Uploads every image copied from now on, and with `files` every file copied in Explorer,
+/// until Ctrl+C
+///
+/// `upload` puts the URL on the clipboard in place of what was copied
+pub fn watch(
+    files: bool,
+    mut upload: impl FnMut(Vec<PathBuf>) -> Result<Exit, Error>,
+) -> Result<Exit, Error> {
+    println!("{}", t!("watching-clipboard"));
+    cancel::install();
+    let mut seen = raw::seq_num();
+    while !cancel::requested() {
+        thread::sleep(POLL);
+        if raw::seq_num() == seen {
+            continue;
+        }
+        seen = raw::seq_num();
+
+        let (paths, _copy) = match copied(files) {
+            Ok(Some(Copied::Image(copy))) => (vec![copy.path.clone()], Some(copy)),
+            Ok(Some(Copied::Files(paths))) => (paths, None),
+            Ok(None) => continue,
+            Err(err) => {
+                warn!("Failed to read the clipboard: {}", err);
+                continue;
+            }
+        };
+        debug!(files = ?paths, "Uploading from the clipboard");
+        if let Err(err) = upload(paths) {
+            warn!("{}", err);
+        }
+        // The URL that was just copied isn't something to upload
+        seen = raw::seq_num();
+    }
+    Ok(Exit::Success)
+}
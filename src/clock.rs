@@ -0,0 +1,13 @@
+//! The time since the Unix epoch, which the timestamps uppy keeps on disk count from
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Zero when the clock is set before 1970
+pub fn now() -> Duration {
+    since_epoch(SystemTime::now())
+}
+
+/// `time` counted the same way, for modified times and the like, zero before 1970
+pub fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
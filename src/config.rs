@@ -0,0 +1,399 @@
+//! Reading the configuration file shared by the command line tool and the library
+
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+use tracing::{debug, warn};
+
+pub use crate::oauth::OAuthConfig;
+
+lazy_static! {
+    /// `%USERPROFILE%\.config\uppy`, where the configuration and all other state lives
+    ///
+    /// This is relative when USERPROFILE isn't set, go through [`config_dir`] first to catch that
+    pub static ref CONFIG_PATH: PathBuf = {
+        let user_profile = env::var("USERPROFILE").unwrap_or_default();
+        let config_path_str = format!("{}\\.config\\uppy", user_profile);
+        PathBuf::from(config_path_str)
+    };
+}
+
+/// Why the configuration couldn't be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Somehow your USERPROFILE isn't set, congratulations on getting this error")]
+    NoUserProfile,
+    #[error("Failed to read the configuration file: {0}")]
+    Read(#[source] io::Error),
+    #[error("Failed to write the configuration file, please fill it out manually: {0}")]
+    Write(#[source] io::Error),
+    #[error("The configuration file is not formatted properly: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Failed to read {path}: {source}", path = .path.display())]
+    ReadLayer { path: PathBuf, source: io::Error },
+    #[error("{path} is not formatted properly: {message}", path = .path.display())]
+    ParseLayer { path: PathBuf, message: String },
+    #[error("profile \"{0}\" does not exist")]
+    UnknownProfile(String),
+    /// Usually a newline pasted along with the token
+    #[error("The token contains characters that can't be sent in a header")]
+    InvalidToken,
+    #[error("The header \"{0}\" can't be sent, check its name and value")]
+    InvalidHeader(String),
+    #[error("pinned_sha256 \"{0}\" isn't a SHA-256 in base64 or hex")]
+    InvalidPin(String),
+    #[error("\"{0}\" in resolve isn't an IP address, with or without a port")]
+    InvalidAddress(String),
+    #[error("Can't send from {interface}: {reason}")]
+    Interface { interface: String, reason: String },
+    #[error("Failed to set up TLS for pinned_sha256: {0}")]
+    Tls(String),
+    #[error("Failed to set up the HTTP client: {0}")]
+    HttpClient(String),
+    #[error("response_url_regex isn't a valid regex: {0}")]
+    InvalidRegex(String),
+}
+
+/// [`CONFIG_PATH`], as long as it could actually be worked out
+pub fn config_dir() -> Result<&'static Path, ConfigError> {
+    match env::var_os("USERPROFILE") {
+        Some(_) => Ok(&CONFIG_PATH),
+        None => Err(ConfigError::NoUserProfile),
+    }
+}
+
+/// The part of the configuration needed to talk to a host
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ClientConfig {
+    /// Base URL of the Zipline instance, e.g. `https://files.example.com`
+    pub host: String,
+    /// API token sent in the `Authorization` header
+    #[serde(default)]
+    pub token: String,
+    /// More tokens to fall back on in turn when one is refused or rate limited
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<String>,
+    /// Where the URL is in the answer of hosts that don't answer like Zipline, a JSON pointer
+    /// such as `/data/link` or a path such as `$.data.link`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_url_path: Option<String>,
+    /// Regex the URL is cut out of the answer with, or out of what `response_url_path` found,
+    /// the first group when it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_url_regex: Option<String>,
+    /// Refreshes OAuth2 access tokens and sends them instead of `token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<OAuthConfig>,
+    /// Where the deletion URL is in the answer, like `response_url_path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_deletion_url_path: Option<String>,
+    #[serde(default)]
+    pub filename_encoding: FilenameEncoding,
+    /// URL that looks files up by their SHA-256, filled in for `{sha256}`, so ones the host
+    /// already has aren't uploaded again. A 404 or an empty answer means it doesn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookup_url: Option<String>,
+    /// Where the URL is in the answer of `lookup_url`, like `response_url_path`, otherwise the
+    /// whole answer is the URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookup_url_path: Option<String>,
+    /// Files larger than this many bytes are uploaded in chunks of it, which carry on from the
+    /// last chunk the host confirmed when the connection drops
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<u64>,
+    /// Major version of Zipline on the host, 3 or 4, asked of the host on the first upload when
+    /// unset so headers are sent under the names it knows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zipline_version: Option<u32>,
+    /// Sent with every request to the host over the ones Zipline wants, an empty value leaves
+    /// one of those out and an `Authorization` takes the place of the token
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// SHA-256 of the public key the certificate of the host has to carry, as `sha256//<base64>`
+    /// like curl's `--pinnedpubkey` or hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_sha256: Option<String>,
+    /// Addresses to connect to in place of looking domains up, like curl's `--resolve`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub resolve: HashMap<String, String>,
+    /// Address or name of the network interface uploads go out from, for hosts only reachable over a VPN
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_address: Option<String>,
+}
+
+/// How the file name is written in the upload form
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilenameEncoding {
+    /// As UTF-8, the way browsers send names
+    #[default]
+    Utf8,
+    /// Percent encoded like in a URL, for hosts that mangle anything past ASCII and decode the name themselves
+    Percent,
+}
+
+/// Names a project configuration can have, looked for in this order
+const PROJECT_FILES: [&str; 2] = [".uppy.json", ".uppy.toml"];
+/// Keys a project configuration can't set, checking out a repository shouldn't run its programs,
+/// send uploads or headers elsewhere, open up files to the browser, delete or shred files, or
+/// change what is kept of the uploads
+const UNTRUSTED_KEYS: [&str; 13] = [
+    "pre_upload_hook",
+    "post_upload_hook",
+    "malware_scan",
+    "resolve",
+    "notify_webhook",
+    "native_host_dirs",
+    "url_rewrite",
+    "cleanup",
+    "shred_passes",
+    "audit_log",
+    "headers",
+    "pipelines",
+    "history",
+];
+/// Keys that say where requests go, a project that sets one doesn't get the credentials of the
+/// layers before it along with them
+const DESTINATION_KEYS: [&str; 3] = ["host", "lookup_url", "oauth"];
+/// What is sent to the host along with requests
+const CREDENTIAL_KEYS: [&str; 4] = ["token", "tokens", "oauth", "headers"];
+
+/// `%ProgramData%\uppy\config.json`, the configuration shared by everyone on the machine
+pub fn system_config_path() -> Option<PathBuf> {
+    let data = env::var_os("ProgramData")?;
+    Some(PathBuf::from(data).join("uppy").join("config.json"))
+}
+
+/// `.uppy.json` or `.uppy.toml` in the current directory, or the closest parent that has one
+pub fn project_config_path() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    dir.ancestors()
+        .flat_map(|dir| PROJECT_FILES.map(|name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Whether a machine wide or project configuration exists, which uppy can run on without a
+/// `config.json` of the user
+pub fn has_shared_config() -> bool {
+    system_config_path().is_some_and(|path| path.is_file()) || project_config_path().is_some()
+}
+
+fn read_layer(path: &Path) -> Result<Value, ConfigError> {
+    let text = fs::read_to_string(path).map_err(|source| ConfigError::ReadLayer {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let parsed = match path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        true => toml::from_str(&text).map_err(|err| err.to_string()),
+        false => serde_json::from_str(&text).map_err(|err| err.to_string()),
+    };
+    parsed.map_err(|message| ConfigError::ParseLayer {
+        path: path.to_path_buf(),
+        message,
+    })
+}
+
+/// Puts the keys of `over` over those of `base`, objects such as `profiles` are merged key by key
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+fn remove_untrusted(layer: &mut Value, path: &Path) {
+    let mut removed = [false; UNTRUSTED_KEYS.len()];
+    let mut remove = |object: &mut Value| {
+        let Some(object) = object.as_object_mut() else {
+            return;
+        };
+        for (key, removed) in UNTRUSTED_KEYS.iter().zip(&mut removed) {
+            *removed |= object.remove(*key).is_some();
+        }
+    };
+    remove(layer);
+    if let Some(profiles) = layer.get_mut("profiles").and_then(Value::as_object_mut) {
+        profiles.values_mut().for_each(&mut remove);
+    }
+    let removed: Vec<&str> = UNTRUSTED_KEYS
+        .into_iter()
+        .zip(removed)
+        .filter_map(|(key, removed)| removed.then_some(key))
+        .collect();
+    if !removed.is_empty() {
+        warn!(
+            "Ignoring {} in {}, only config.json can set them",
+            removed.join(", "),
+            path.display()
+        );
+    }
+}
+
+/// The keys sent to the host along with every request, emptied so they override those of the
+/// top level too
+fn drop_credentials(object: &mut Map<String, Value>) {
+    let empty = [
+        Value::String(String::new()),
+        Value::Array(Vec::new()),
+        Value::Null,
+        Value::Object(Map::new()),
+    ];
+    for (key, empty) in CREDENTIAL_KEYS.into_iter().zip(empty) {
+        object.insert(key.to_string(), empty);
+    }
+}
+
+/// Drops the credentials in `root` that would go to a destination `project` changes, it brings
+/// its own when it needs any
+///
+/// That is the top level when the project sets another host there, every profile that inherits
+/// it and every profile the project points elsewhere itself
+fn keep_credentials_home(root: &mut Value, project: &Value, path: &Path) {
+    let Some(root) = root.as_object_mut() else {
+        return;
+    };
+    // Whether `over` sends requests elsewhere than `base` does, falling back on `top` for the
+    // keys `base` inherits
+    let changes = |over: &Value, base: Option<&Value>, top: &Map<String, Value>| {
+        DESTINATION_KEYS.into_iter().any(|key| {
+            let before = base.and_then(|base| base.get(key)).or_else(|| top.get(key));
+            over.get(key).is_some_and(|value| Some(value) != before)
+        })
+    };
+    let changed: Vec<&str> = DESTINATION_KEYS
+        .into_iter()
+        .filter(|key| {
+            project
+                .get(key)
+                .is_some_and(|value| Some(value) != root.get(*key))
+        })
+        .collect();
+    let profiles = root.get("profiles");
+    let redirected: Vec<String> = project
+        .get("profiles")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter(|(name, profile)| {
+            changes(
+                profile,
+                profiles.and_then(|profiles| profiles.get(*name)),
+                root,
+            )
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    if changed.is_empty() && redirected.is_empty() {
+        return;
+    }
+    debug!(
+        "{} points uploads elsewhere, the credentials before it aren't sent there",
+        path.display()
+    );
+
+    if !changed.is_empty() {
+        drop_credentials(root);
+    }
+    let profiles = root
+        .entry("profiles")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Some(profiles) = profiles.as_object_mut() else {
+        return;
+    };
+    for name in &redirected {
+        profiles
+            .entry(name.as_str())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    for (name, profile) in profiles.iter_mut() {
+        let Some(profile) = profile.as_object_mut() else {
+            continue;
+        };
+        // One that inherits the new host inherits the credentials that come with it too
+        let inherits = changed.iter().any(|key| !profile.contains_key(*key));
+        match redirected.contains(name) {
+            true => drop_credentials(profile),
+            false if inherits => {
+                for key in CREDENTIAL_KEYS {
+                    profile.remove(key);
+                }
+            }
+            false => (),
+        }
+    }
+}
+
+/// The machine wide configuration, then the `config.json` of the user, then the project
+/// configuration, each taking precedence over the ones before it
+fn read_root() -> Result<Value, ConfigError> {
+    let mut root = Value::Object(Map::new());
+    if let Some(path) = system_config_path().filter(|path| path.is_file()) {
+        debug!("Reading the machine wide configuration {}", path.display());
+        merge(&mut root, read_layer(&path)?);
+    }
+
+    let user = config_dir()?.join("config.json");
+    match fs::read_to_string(&user) {
+        Ok(json) => merge(&mut root, serde_json::from_str(&json)?),
+        // The shared configuration may well be all there is
+        Err(err) if err.kind() == io::ErrorKind::NotFound && has_shared_config() => (),
+        Err(err) => return Err(ConfigError::Read(err)),
+    }
+
+    if let Some(path) = project_config_path() {
+        debug!("Reading the project configuration {}", path.display());
+        let mut project = read_layer(&path)?;
+        remove_untrusted(&mut project, &path);
+        keep_credentials_home(&mut root, &project, &path);
+        merge(&mut root, project);
+    }
+    Ok(root)
+}
+
+/// Reads the configuration into `T`, applying the keys of `profile` over the top level ones
+///
+/// The configuration is the machine wide one, the `config.json` of the user and the project
+/// one merged in that order. Profiles only need to contain the keys they change, anything else
+/// falls back to the top level
+pub fn read_config<T: DeserializeOwned>(profile: Option<&str>) -> Result<T, ConfigError> {
+    let mut root = read_root()?;
+
+    if let Some(name) = profile {
+        let overrides = match root.get("profiles").and_then(|p| p.get(name)) {
+            Some(Value::Object(profile)) => profile.clone(),
+            _ => return Err(ConfigError::UnknownProfile(name.to_string())),
+        };
+        for (key, value) in overrides {
+            root[key] = value;
+        }
+    }
+
+    Ok(serde_json::from_value(root)?)
+}
+
+/// Names of the profiles in the configuration, empty when it can't be read
+pub fn profile_names() -> Vec<String> {
+    match read_root() {
+        Ok(Value::Object(mut root)) => match root.remove("profiles") {
+            Some(Value::Object(profiles)) => profiles.keys().cloned().collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
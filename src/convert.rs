@@ -0,0 +1,208 @@
+use clap::ValueEnum;
+use image::codecs::avif::AvifEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use tracing::debug;
+
+use crate::format::is_image;
+use crate::temp::TempCopy;
+
+const DEFAULT_QUALITY: u8 = 80;
+/// Trades encoding time for size, 1 is slowest and 10 fastest
+const AVIF_SPEED: u8 = 6;
+/// oxipng's own default, higher presets take far longer for a few more bytes
+const OPTIMIZE_PRESET: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Target {
+    Webp,
+    Avif,
+}
+
+impl Target {
+    fn extension(self) -> &'static str {
+        match self {
+            Target::Webp => "webp",
+            Target::Avif => "avif",
+        }
+    }
+}
+
+/// Format and quality given to `--convert`
+#[derive(Clone, Copy)]
+pub struct Conversion {
+    pub target: Target,
+    pub quality: u8,
+}
+
+/// Parses `webp`, `avif` or either with a quality from 1 to 100 such as `webp:75`
+pub fn parse(conversion: &str) -> Result<Conversion, String> {
+    let (target, quality) = match conversion.split_once(':') {
+        Some((target, quality)) => (target, Some(quality)),
+        None => (conversion, None),
+    };
+    let target = Target::from_str(target, true)?;
+    let quality = match quality {
+        Some(quality) => match quality.parse() {
+            Ok(quality @ 1..=100) => quality,
+            _ => {
+                return Err(format!(
+                    "quality must be between 1 and 100, not {}",
+                    quality
+                ))
+            }
+        },
+        None => DEFAULT_QUALITY,
+    };
+    Ok(Conversion { target, quality })
+}
+
+/// Decodes the image with its EXIF orientation applied, as the encoders don't carry it over
+fn decode(file: &Path) -> Result<DynamicImage, String> {
+    let mut decoder = ImageReader::open(file)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| err.to_string())?
+        .into_decoder()
+        .map_err(|err| err.to_string())?;
+    let orientation = decoder.orientation().map_err(|err| err.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|err| err.to_string())?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn encode(
+    image: &DynamicImage,
+    conversion: Option<Conversion>,
+    file: &Path,
+) -> Result<Vec<u8>, String> {
+    let Some(conversion) = conversion else {
+        // Only resized, so it stays in the format it came in
+        let format = ImageFormat::from_path(file).map_err(|err| err.to_string())?;
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .map_err(|err| err.to_string())?;
+        return Ok(bytes);
+    };
+
+    let rgba = image.to_rgba8();
+    match conversion.target {
+        Target::Webp => {
+            let encoder = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height());
+            Ok(encoder.encode(conversion.quality as f32).to_vec())
+        }
+        Target::Avif => {
+            let mut avif = Vec::new();
+            let encoder =
+                AvifEncoder::new_with_speed_quality(&mut avif, AVIF_SPEED, conversion.quality);
+            DynamicImage::ImageRgba8(rgba)
+                .write_with_encoder(encoder)
+                .map_err(|err| err.to_string())?;
+            Ok(avif)
+        }
+    }
+}
+
+/// What happens to images before they are uploaded
+#[derive(Clone, Copy, Default)]
+pub struct Transform {
+    pub conversion: Option<Conversion>,
+    /// Longest side in pixels, larger images are scaled down to fit
+    pub max_dim: Option<u32>,
+}
+
+/// Converts and downscales an image to a copy in the temp directory, leaving the original untouched
+///
+/// Returns `None` for anything that isn't a still image or doesn't need changing, and for
+/// conversions that wouldn't make the file any smaller
+pub fn transform(file: &Path, transform: Transform) -> Result<Option<TempCopy>, String> {
+    let name = file.to_string_lossy().to_lowercase();
+    // Re-encoding would throw away the animation
+    if !is_image(&name) || name.ends_with(".gif") {
+        return Ok(None);
+    }
+    let conversion = transform
+        .conversion
+        .filter(|conversion| !name.ends_with(&format!(".{}", conversion.target.extension())));
+    let fits = |(width, height): (u32, u32)| match transform.max_dim {
+        Some(max) => width <= max && height <= max,
+        None => true,
+    };
+    // Saves decoding images that are already small enough
+    if conversion.is_none() && image::image_dimensions(file).is_ok_and(fits) {
+        return Ok(None);
+    }
+
+    let mut image = decode(file)?;
+    let resized = !fits(image.dimensions());
+    if let (true, Some(max)) = (resized, transform.max_dim) {
+        debug!(
+            "Scaling {} down from {}x{}",
+            file.display(),
+            image.width(),
+            image.height()
+        );
+        // Fits within a max by max box, keeping the aspect ratio
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+    if conversion.is_none() && !resized {
+        return Ok(None);
+    }
+
+    let encoded = encode(&image, conversion, file)?;
+    let original = fs::metadata(file)
+        .map(|meta| meta.len())
+        .unwrap_or(u64::MAX);
+    if !resized && encoded.len() as u64 >= original {
+        debug!(
+            "Converting {} wouldn't make it smaller, uploading it as is",
+            file.display()
+        );
+        return Ok(None);
+    }
+    debug!(
+        "Transformed {}, {} bytes down from {}",
+        file.display(),
+        encoded.len(),
+        original
+    );
+
+    let name = match conversion {
+        Some(conversion) => file.with_extension(conversion.target.extension()),
+        None => file.to_path_buf(),
+    };
+    let name = name.file_name().ok_or("no file name")?;
+    TempCopy::write(name, &encoded)
+        .map(Some)
+        .map_err(|err| err.to_string())
+}
+
+/// Recompresses a PNG without losing anything, `None` for other files or when nothing was saved
+pub fn optimize(file: &Path) -> Result<Option<TempCopy>, String> {
+    let bytes = fs::read(file).map_err(|err| err.to_string())?;
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Ok(None);
+    }
+
+    let options = oxipng::Options::from_preset(OPTIMIZE_PRESET);
+    let optimized =
+        oxipng::optimize_from_memory(&bytes, &options).map_err(|err| err.to_string())?;
+    if optimized.len() >= bytes.len() {
+        debug!("{} is already as small as it gets", file.display());
+        return Ok(None);
+    }
+    debug!(
+        "Optimized {}, {} bytes down from {}",
+        file.display(),
+        optimized.len(),
+        bytes.len()
+    );
+
+    let name = file.file_name().ok_or("no file name")?;
+    TempCopy::write(name, &optimized)
+        .map(Some)
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,148 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::prelude::*;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::download::download;
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::temp::TempCopy;
+use uppy::process;
+
+const NONCE_LEN: usize = 12;
+
+/// Encrypts `file` into a copy in the temp directory, returning it with the key to put in the URL fragment
+///
+/// The original name is encrypted along with the contents and the copy gets a meaningless one,
+/// so the host learns nothing but the size
+pub fn encrypt(file: &Path) -> Result<(TempCopy, String), String> {
+    let contents = fs::read(file).map_err(|err| err.to_string())?;
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // A two byte name length, the name and then the contents
+    let mut plaintext = Vec::with_capacity(2 + name.len() + contents.len());
+    plaintext.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    plaintext.extend_from_slice(name.as_bytes());
+    plaintext.extend_from_slice(&contents);
+
+    let key = Aes256Gcm::generate_key(OsRng);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(&key)
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|err| err.to_string())?;
+
+    let mut encrypted = nonce.to_vec();
+    encrypted.extend_from_slice(&ciphertext);
+    let copy_name = format!("{:016x}.bin", rand::random::<u64>());
+    let copy =
+        TempCopy::write(OsStr::new(&copy_name), &encrypted).map_err(|err| err.to_string())?;
+    Ok((copy, BASE64_URL_SAFE_NO_PAD.encode(key)))
+}
+
+/// Parses an age public key such as `age1...` for `--age-recipient`
+pub fn parse_recipient(recipient: &str) -> Result<age::x25519::Recipient, String> {
+    recipient.parse().map_err(|err: &str| err.to_string())
+}
+
+/// Encrypts `file` to the age recipients into `<name>.age` in the temp directory
+pub fn encrypt_age(file: &Path, recipients: &[age::x25519::Recipient]) -> Result<TempCopy, String> {
+    let contents = fs::read(file).map_err(|err| err.to_string())?;
+    let recipients = recipients
+        .iter()
+        .map(|recipient| recipient as &dyn age::Recipient);
+    let encryptor = age::Encryptor::with_recipients(recipients).map_err(|err| err.to_string())?;
+
+    let mut encrypted = Vec::new();
+    let written = encryptor
+        .wrap_output(&mut encrypted)
+        .and_then(|mut writer| {
+            writer.write_all(&contents)?;
+            writer.finish()
+        });
+    written.map_err(|err| err.to_string())?;
+
+    let mut name = file.file_name().ok_or("no file name")?.to_os_string();
+    name.push(".age");
+    TempCopy::write(&name, &encrypted).map_err(|err| err.to_string())
+}
+
+/// Encrypts `file` to the gpg recipients into `<name>.gpg` in the temp directory
+///
+/// Runs the `gpg` on the `PATH`, so the keys and trust settings of the keyring apply
+pub fn encrypt_gpg(file: &Path, recipients: &[String]) -> Result<TempCopy, String> {
+    let mut name = file.file_name().ok_or("no file name")?.to_os_string();
+    name.push(".gpg");
+    let copy = TempCopy::new(&name).map_err(|err| err.to_string())?;
+
+    let mut gpg = Command::new("gpg");
+    gpg.args(["--batch", "--yes", "--encrypt"]);
+    for recipient in recipients {
+        gpg.arg("--recipient").arg(recipient);
+    }
+    let output = process::hidden(gpg.arg("--output").arg(&copy.path).arg("--").arg(file))
+        .output()
+        .map_err(|err| format!("failed to run gpg: {}", err))?;
+    match output.status.success() {
+        true => Ok(copy),
+        // Lines like `gpg: <key>: skipped: No public key` say which recipient it was
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+    }
+}
+
+/// The original name and contents of a file uploaded with `--encrypt`
+fn decrypt(encrypted: &[u8], key: &str) -> Option<(String, Vec<u8>)> {
+    let key = BASE64_URL_SAFE_NO_PAD.decode(key).ok()?;
+    if key.len() != 32 || encrypted.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()?;
+
+    let name_len = u16::from_be_bytes(plaintext.get(..2)?.try_into().ok()?) as usize;
+    let name = String::from_utf8_lossy(plaintext.get(2..2 + name_len)?).into_owned();
+    Some((name, plaintext[2 + name_len..].to_vec()))
+}
+
+/// Downloads an upload made with `--encrypt` and writes the decrypted file to `out`
+///
+/// Without `out` the file is saved under its original name in the current directory,
+/// never replacing a file that is already there
+pub fn run(url: &str, out: Option<&Path>) -> Result<Exit, Error> {
+    let Some((url, key)) = url.split_once('#') else {
+        return Err(Error::MissingKey);
+    };
+    let encrypted = download(&reqwest::blocking::Client::new(), url)?;
+    let (name, contents) = decrypt(&encrypted, key).ok_or(Error::Decrypt)?;
+
+    // Only the last component so a crafted name can't write outside the directory
+    let name = Path::new(&name)
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("decrypted"));
+    let path = out.map(Path::to_path_buf).unwrap_or(name);
+    let written = match out {
+        Some(_) => fs::write(&path, contents),
+        None => fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&contents)),
+    };
+    written.map_err(|source| Error::Write {
+        path: path.clone(),
+        source,
+    })?;
+
+    println!("{}", t!("decrypted", path = path.display().to_string()));
+    Ok(Exit::Success)
+}
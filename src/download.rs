@@ -0,0 +1,82 @@
+use reqwest::blocking;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use uppy::Client;
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::{name, template};
+
+/// Fetches an upload through its raw URL so hosts serve the file rather than an embed page
+///
+/// Takes the client so several downloads from one host share its connections
+pub fn download(http: &blocking::Client, url: &str) -> Result<Vec<u8>, Error> {
+    let raw_url = template::raw_url(url);
+    debug!("Downloading {}", raw_url);
+    http.get(&raw_url)
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.bytes())
+        .map(|bytes| bytes.to_vec())
+        .map_err(|source| Error::Download {
+            url: url.to_string(),
+            source,
+        })
+}
+
+/// Downloads one of your uploads by URL or by the name the host gave it, like `4kTq1.png`
+pub fn get(client: &Client, target: &str, out: Option<&Path>) -> Result<Exit, Error> {
+    let url = match target.contains("://") {
+        true => template::raw_url(target),
+        false => format!("{}/r/{}", client.host().trim_end_matches('/'), target),
+    };
+    let name = url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let path = out
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(name::sanitize(name)));
+
+    let write = |source| Error::Write {
+        path: path.clone(),
+        source,
+    };
+    // Never overwrites a file unless it was asked for with -o
+    let mut file = match out {
+        Some(_) => File::create(&path),
+        None => fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path),
+    }
+    .map_err(write)?;
+
+    match client.download(&url, &mut file) {
+        Ok(size) => {
+            println!(
+                "{}",
+                t!(
+                    "downloaded",
+                    size = template::human_size(size),
+                    path = path.display().to_string()
+                )
+            );
+            Ok(Exit::Success)
+        }
+        Err(err) => {
+            drop(file);
+            if let Err(err) = fs::remove_file(&path) {
+                warn!(
+                    "Failed to remove the partial download {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+            Err(err.into())
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::io;
+use std::path::PathBuf;
+use uppy::{ConfigError, UploadError};
+
+/// Anything that stops a command short, printed by `main` before exiting with the matching code
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+    #[error("Failed to delete {url}: {source}")]
+    Delete { url: String, source: UploadError },
+    #[error("Failed to read the cassette: {0}")]
+    Cassette(#[source] io::Error),
+    #[error("Failed to check for updates: {0}")]
+    UpdateCheck(#[source] reqwest::Error),
+    #[error("The latest release has no {0}")]
+    NoReleaseAsset(String),
+    #[error("The download isn't signed with the release key, not installing it")]
+    BadSignature,
+    #[error(
+        "This build has no release key to check updates with, update it the way it was installed"
+    )]
+    NoReleaseKey,
+    #[error("Failed to install the update: {0}")]
+    Install(#[source] io::Error),
+    #[error("The URL has no key, it should end in #<key>")]
+    MissingKey,
+    #[error("Failed to download {url}: {source}")]
+    Download { url: String, source: reqwest::Error },
+    #[error("Failed to decrypt, the key is wrong or the file was changed")]
+    Decrypt,
+    #[error("Failed to write {path}: {source}", path = .path.display())]
+    Write { path: PathBuf, source: io::Error },
+    #[error("{} isn't a directory", .0.display())]
+    NotADirectory(PathBuf),
+    #[error("Failed to read the sync map {path}: {source}", path = .path.display())]
+    SyncMap { path: PathBuf, source: io::Error },
+    #[error("Failed to create the archive: {0}")]
+    Archive(#[source] io::Error),
+    #[error("tar.zst archives can't have a password, use --archive=zip with --zip-password")]
+    TarPassword,
+    #[error("Failed to split the file: {0}")]
+    Split(#[source] io::Error),
+    #[error("Failed to read the manifest: {0}")]
+    Manifest(#[source] io::Error),
+    #[error("{0} doesn't match its checksum, it may have been changed on the host")]
+    Corrupted(String),
+    #[cfg(feature = "http3")]
+    #[error("Failed to set up HTTP/3: {0}")]
+    Http3(#[source] reqwest::Error),
+    #[error("This build of uppy has no HTTP/3 support, it needs the http3 feature")]
+    NoHttp3,
+    #[error("Invalid --include or --exclude glob: {0}")]
+    Filter(#[source] ignore::Error),
+    #[error("Failed to save the download: {0}")]
+    Fetch(#[source] io::Error),
+    #[error("Failed to write the benchmark payload: {0}")]
+    Bench(#[source] io::Error),
+    #[error("Failed to read the last batch: {0}")]
+    Batch(#[source] io::Error),
+    #[error("The last batch finished, there is nothing to resume")]
+    NothingToResume,
+    #[error("No file of the last batch failed")]
+    NothingToRetry,
+    #[error("The flags of the last batch can't be used anymore: {0}")]
+    BatchArgs(#[source] clap::Error),
+    #[error("The queued upload was refused its flags, keeping it queued")]
+    QueuedArgs,
+    #[error("Failed to flush the offline queue: {0}")]
+    Queue(#[source] io::Error),
+    #[error("Failed to read the password: {0}")]
+    Password(#[source] io::Error),
+    #[error("The password can't be empty")]
+    EmptyPassword,
+    #[error("The passwords don't match")]
+    PasswordMismatch,
+    #[error("Failed to read the keys pressed: {0}")]
+    Terminal(#[source] io::Error),
+    #[error("There are no files in {} to pick from", .0.display())]
+    NothingToPick(PathBuf),
+    #[error("Failed to read the minisign key {path}: {source}", path = .path.display())]
+    SigningKey { path: PathBuf, source: io::Error },
+    #[error("{} isn't a minisign secret key", .0.display())]
+    InvalidSigningKey(PathBuf),
+    #[error("The password of the minisign key is wrong")]
+    WrongSigningPassword,
+    #[error("Failed to write to stdout: {0}")]
+    Stdout(#[source] io::Error),
+    #[error("Failed to get executed directory: {0}")]
+    CurrentDir(#[source] io::Error),
+    #[error("Failed to read the upload history: {0}")]
+    History(#[from] rusqlite::Error),
+    #[error("{0} isn't in the upload history")]
+    NotInHistory(String),
+    #[error("The history only has {0} uploads")]
+    HistoryTooShort(usize),
+    #[error("{url} was uploaded to {host}, pick the profile for that host with --profile")]
+    WrongHost { url: String, host: String },
+    #[error("Something went wrong while copying URL to clipboard: {0}")]
+    Clipboard(clipboard_win::ErrorCode),
+    #[error("Failed to read the clipboard: {0}")]
+    ReadClipboard(String),
+    #[error("Nothing to upload, copy files in Explorer or an image first")]
+    EmptyClipboard,
+    #[error("Failed to open the snipping overlay: {0}")]
+    Capture(#[source] io::Error),
+    #[error("No region was captured")]
+    NoCapture,
+    #[error("There is no pipeline called {0} in the configuration")]
+    UnknownPipeline(String),
+    #[error("The args of the pipeline can't be used: {0}")]
+    PipelineArgs(#[source] clap::Error),
+    #[error("Failed to write the gallery: {0}")]
+    Gallery(#[source] io::Error),
+    #[error("Nothing to restore")]
+    NothingToRestore,
+    #[error("Failed to read the Recycle Bin: {0}")]
+    RecycleBin(#[source] trash::Error),
+    #[error("Failed to restore {path}: {source}", path = .path.display())]
+    Restore { path: PathBuf, source: trash::Error },
+}
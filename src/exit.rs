@@ -0,0 +1,85 @@
+use reqwest::StatusCode;
+use std::process::ExitCode;
+use uppy::UploadError;
+
+use crate::error::Error;
+
+/// Exit codes uppy finishes with, documented in the README so scripts can rely on them
+///
+/// 2 is left out as clap already uses it for invalid arguments
+#[derive(Clone, Copy, PartialEq)]
+pub enum Exit {
+    Success = 0,
+    Failure = 1,
+    Config = 3,
+    Auth = 4,
+    Network = 5,
+    PartialFailure = 6,
+    /// Stopped with Ctrl+C or Esc in a picker, 128 plus the number of SIGINT like shells do
+    Cancelled = 130,
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+impl Exit {
+    /// Picks the exit code for a whole batch given the failure of each file, `None` meaning it uploaded
+    pub fn for_batch(failures: &[Option<Exit>]) -> Exit {
+        let failed: Vec<Exit> = failures.iter().flatten().copied().collect();
+
+        if failed.is_empty() {
+            Exit::Success
+        } else if failed.len() < failures.len() {
+            Exit::PartialFailure
+        } else if failed.contains(&Exit::Auth) {
+            Exit::Auth
+        } else if failed.contains(&Exit::Network) {
+            Exit::Network
+        } else {
+            Exit::Failure
+        }
+    }
+}
+
+impl From<&UploadError> for Exit {
+    fn from(err: &UploadError) -> Self {
+        match err {
+            UploadError::ReqwestError(_) => Exit::Network,
+            UploadError::HTTPClientError(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                Exit::Auth
+            }
+            UploadError::OAuth(_) => Exit::Auth,
+            UploadError::Cancelled => Exit::Cancelled,
+            _ => Exit::Failure,
+        }
+    }
+}
+
+impl From<&Error> for Exit {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::Config(_) => Exit::Config,
+            Error::Upload(err) | Error::Delete { source: err, .. } => Exit::from(err),
+            Error::UpdateCheck(_) | Error::Download { .. } => Exit::Network,
+            _ => Exit::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_batch_picks_the_code_of_the_batch() {
+        assert!(Exit::for_batch(&[]) == Exit::Success);
+        assert!(Exit::for_batch(&[None, None]) == Exit::Success);
+        assert!(Exit::for_batch(&[None, Some(Exit::Auth)]) == Exit::PartialFailure);
+        assert!(Exit::for_batch(&[Some(Exit::Network), Some(Exit::Auth)]) == Exit::Auth);
+        assert!(Exit::for_batch(&[Some(Exit::Failure), Some(Exit::Network)]) == Exit::Network);
+        assert!(Exit::for_batch(&[Some(Exit::Failure), Some(Exit::Config)]) == Exit::Failure);
+    }
+}
@@ -0,0 +1,107 @@
+use percent_encoding::percent_decode_str;
+use reqwest::blocking::{self, Response};
+use reqwest::header::CONTENT_DISPOSITION;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::debug;
+use uppy::Progress;
+
+use crate::error::Error;
+use crate::name;
+use crate::temp::TempCopy;
+use crate::transfer::Meter;
+
+/// `filename*=UTF-8''%E2%9C%A8.png` from RFC 5987, which takes precedence over a plain `filename`
+fn extended_name(value: &str) -> Option<String> {
+    let (charset, rest) = value.split_once('\'')?;
+    let (_language, encoded) = rest.split_once('\'')?;
+    let decoded = percent_decode_str(encoded);
+    match charset.eq_ignore_ascii_case("utf-8") {
+        true => decoded.decode_utf8().ok().map(String::from),
+        // ISO-8859-1, the only other charset the RFC allows
+        false => Some(decoded.map(char::from).collect()),
+    }
+}
+
+/// The name the server gives the file, otherwise the last part of the URL it ended up at
+fn file_name(res: &Response) -> String {
+    // Some servers send the name as raw UTF-8, which `to_str` would refuse
+    let disposition = res
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default();
+    let params: Vec<(&str, &str)> = disposition
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    };
+    let disposed = param("filename*")
+        .and_then(extended_name)
+        .or_else(|| param("filename").map(|name| name.trim_matches('"').to_string()));
+    let name = disposed.or_else(|| {
+        res.url()
+            .path_segments()?
+            .next_back()
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+    });
+    name::sanitize(name.as_deref().unwrap_or("download"))
+}
+
+/// Streams `url` into the temp directory, drawing its progress on `meter`
+pub fn fetch(url: &str, meter: Option<&Meter>) -> Result<TempCopy, Error> {
+    let download = |source| Error::Download {
+        url: url.to_string(),
+        source,
+    };
+    let mut res = blocking::Client::new()
+        .get(url)
+        .send()
+        .and_then(|res| res.error_for_status())
+        .map_err(download)?;
+
+    let name = file_name(&res);
+    debug!("Downloading {} as {}", url, name);
+    let copy = TempCopy::new(OsStr::new(&name)).map_err(Error::Fetch)?;
+    let mut file = File::create(&copy.path).map_err(Error::Fetch)?;
+
+    // Without a length from the server all that can be shown is how fast it arrives
+    let total = res.content_length();
+    if let Some(meter) = meter {
+        meter.start_file(&name, total.unwrap_or_default());
+    }
+    let path: Arc<Path> = Arc::from(copy.path.as_path());
+    let started = Instant::now();
+    let mut buf = vec![0; 64 * 1024];
+    let mut received = 0;
+    loop {
+        let read = res.read(&mut buf).map_err(Error::Fetch)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read]).map_err(Error::Fetch)?;
+        received += read as u64;
+        if let Some(meter) = meter {
+            meter.update(&Progress {
+                path: Arc::clone(&path),
+                sent: received,
+                total: total.unwrap_or(received).max(received),
+                elapsed: started.elapsed(),
+            });
+        }
+    }
+    if let Some(meter) = meter {
+        meter.finish_file();
+    }
+    Ok(copy)
+}
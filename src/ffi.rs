@@ -0,0 +1,121 @@
+//! A C ABI over [`Client`], for embedding uppy in programs that aren't written in Rust
+//!
+//! Declared in `include/uppy.h`. Strings are UTF-8 and NUL terminated, and those uppy hands out
+//! are freed with [`uppy_free`]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+use crate::client::{Client, UploadRequest};
+use crate::config::{read_config, ClientConfig};
+
+pub const UPPY_OK: c_int = 0;
+/// A pointer was null or a string wasn't UTF-8
+pub const UPPY_INVALID_ARGUMENT: c_int = 1;
+/// The configuration couldn't be read or describes a host uppy can't talk to
+pub const UPPY_CONFIG: c_int = 2;
+/// The host didn't take the file
+pub const UPPY_UPLOAD: c_int = 3;
+
+thread_local! {
+    /// What went wrong in the last call on this thread that failed
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(code: c_int, message: impl ToString) -> c_int {
+    // Messages can't contain a NUL, but don't lose all of one that somehow does
+    let message = message.to_string().replace('\0', " ");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+    code
+}
+
+/// # Safety
+///
+/// `text` has to be null or point to a NUL terminated string
+unsafe fn text<'a>(text: *const c_char) -> Option<Result<&'a str, c_int>> {
+    if text.is_null() {
+        return None;
+    }
+    Some(
+        CStr::from_ptr(text)
+            .to_str()
+            .map_err(|err| fail(UPPY_INVALID_ARGUMENT, err)),
+    )
+}
+
+fn upload(path: &str, profile: Option<&str>) -> Result<String, c_int> {
+    let config: ClientConfig = read_config(profile).map_err(|err| fail(UPPY_CONFIG, err))?;
+    let client = Client::new(&config).map_err(|err| fail(UPPY_CONFIG, err))?;
+    let response = client
+        .upload(&UploadRequest::new(path))
+        .map_err(|err| fail(UPPY_UPLOAD, err))?;
+    Ok(response.url)
+}
+
+/// Uploads the file at `path` to the host in the configuration, or in its `profile` unless that
+/// is null, and points `out_url` at its URL
+///
+/// Returns `UPPY_OK` or one of the other `UPPY_*` codes, with the reason in [`uppy_last_error`].
+/// `out_url` is only written to on success and has to be freed with [`uppy_free`]
+///
+/// # Safety
+///
+/// `path` and `profile` have to be null or point to NUL terminated strings, and `out_url` has to
+/// be null or point to a `char *` that can be written to
+#[no_mangle]
+pub unsafe extern "C" fn uppy_upload(
+    path: *const c_char,
+    profile: *const c_char,
+    out_url: *mut *mut c_char,
+) -> c_int {
+    if out_url.is_null() {
+        return fail(UPPY_INVALID_ARGUMENT, "out_url is null");
+    }
+    let path = match text(path) {
+        Some(Ok(path)) => path,
+        Some(Err(code)) => return code,
+        None => return fail(UPPY_INVALID_ARGUMENT, "path is null"),
+    };
+    let profile = match text(profile) {
+        Some(Ok(profile)) => Some(profile),
+        Some(Err(code)) => return code,
+        None => None,
+    };
+
+    let url = match upload(path, profile) {
+        Ok(url) => url,
+        Err(code) => return code,
+    };
+    match CString::new(url) {
+        Ok(url) => {
+            *out_url = url.into_raw();
+            UPPY_OK
+        }
+        Err(err) => fail(UPPY_UPLOAD, err),
+    }
+}
+
+/// Why the last call on this thread failed, null if none has
+///
+/// The string belongs to uppy and stays valid until the next failing call on the same thread
+#[no_mangle]
+pub extern "C" fn uppy_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Frees a string uppy handed out, doing nothing with null
+///
+/// # Safety
+///
+/// `text` has to be null or a string from uppy that hasn't been freed yet
+#[no_mangle]
+pub unsafe extern "C" fn uppy_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
@@ -0,0 +1,106 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "webp", "bmp", "avif", "svg"];
+
+#[derive(Clone, Copy, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyFormat {
+    #[default]
+    Plain,
+    Markdown,
+    #[value(name = "bbcode")]
+    BBCode,
+    Html,
+}
+
+pub fn is_image(name: &str) -> bool {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Backslashes what Markdown would read as markup in link text, so a name can't end the link
+/// early or turn into emphasis
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '[' | ']' | '(' | ')' | '*' | '_' | '`' | '!' | '<' | '>'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Percent-encodes what would end a Markdown link destination
+fn markdown_url(url: &str) -> String {
+    url.replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+}
+
+/// Wraps an uploaded URL in the markup of the chosen format, images get embedded
+/// while everything else becomes a regular link
+pub fn format_url(format: CopyFormat, url: &str, name: &str) -> String {
+    let image = is_image(name);
+    match format {
+        CopyFormat::Plain => url.to_string(),
+        CopyFormat::Markdown if image => {
+            format!("![{}]({})", escape_markdown(name), markdown_url(url))
+        }
+        CopyFormat::Markdown => format!("[{}]({})", escape_markdown(name), markdown_url(url)),
+        CopyFormat::BBCode if image => format!("[img]{}[/img]", url),
+        CopyFormat::BBCode => format!("[url={}]{}[/url]", url, name),
+        CopyFormat::Html if image => format!(
+            "<img src=\"{}\" alt=\"{}\">",
+            escape_html(url),
+            escape_html(name)
+        ),
+        CopyFormat::Html => format!("<a href=\"{}\">{}</a>", escape_html(url), escape_html(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_url_escapes_names_and_urls() {
+        assert_eq!(
+            format_url(
+                CopyFormat::Markdown,
+                "https://a.example/u/x (1).txt",
+                "x](1)*.txt"
+            ),
+            "[x\\]\\(1\\)\\*.txt](https://a.example/u/x%20%281%29.txt)"
+        );
+        assert_eq!(
+            format_url(
+                CopyFormat::Html,
+                "https://a.example/u/\"a&b.png",
+                "<b>'a'.png"
+            ),
+            "<img src=\"https://a.example/u/&quot;a&amp;b.png\" alt=\"&lt;b&gt;&#39;a&#39;.png\">"
+        );
+        assert_eq!(
+            format_url(CopyFormat::Html, "https://a.example/u/a.txt", "a & b.txt"),
+            "<a href=\"https://a.example/u/a.txt\">a &amp; b.txt</a>"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::format::{escape_html, is_image};
+use crate::history::{self, Filter, Upload};
+use crate::i18n::t;
+use crate::{template, thumbnail};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; background: #111; color: #eee; }
+a { color: #8cf; }
+main { display: grid; grid-template-columns: repeat(auto-fill, minmax(220px, 1fr)); gap: 1em; }
+figure { margin: 0; padding: .5em; background: #222; border-radius: 6px; overflow: hidden; }
+figure img { width: 100%; height: 160px; object-fit: cover; border-radius: 4px; }
+figure .file { height: 160px; display: flex; align-items: center; justify-content: center; background: #333; border-radius: 4px; }
+figcaption { font-size: .85em; margin-top: .4em; word-break: break-all; }
+figcaption small { display: block; color: #999; }";
+
+fn figure(upload: &Upload) -> String {
+    let name = escape_html(upload.name());
+    let url = escape_html(&upload.url);
+    let thumbnail = upload.hash.as_deref().and_then(thumbnail::data_uri);
+    let preview = if let Some(thumbnail) = thumbnail {
+        format!("<img src=\"{}\" alt=\"{}\">", thumbnail, name)
+    } else if is_image(upload.name()) {
+        format!(
+            "<img src=\"{}\" alt=\"{}\" loading=\"lazy\">",
+            escape_html(&template::raw_url(&upload.url)),
+            name
+        )
+    } else {
+        let extension = Path::new(upload.name())
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_uppercase())
+            .unwrap_or_default();
+        format!("<div class=\"file\">{}</div>", escape_html(&extension))
+    };
+    let size = upload
+        .size
+        .map(|size| template::human_size(size as u64))
+        .unwrap_or_default();
+
+    format!(
+        "<figure><a href=\"{url}\">{preview}</a><figcaption><a href=\"{url}\">{name}</a>\
+         <small>{} &middot; {}</small></figcaption></figure>\n",
+        upload.date(),
+        size
+    )
+}
+
+/// Writes `index.html` in `out` listing every upload matching the filter
+pub fn export(filter: &Filter, out: &Path) -> Result<Exit, Error> {
+    let uploads = history::search(&history::open()?, filter)?;
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>uppy gallery</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>{} uploads</h1>\n<main>\n",
+        STYLE,
+        uploads.len()
+    );
+    for upload in &uploads {
+        html.push_str(&figure(upload));
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+
+    let index = out.join("index.html");
+    fs::create_dir_all(out)
+        .and_then(|_| fs::write(&index, html))
+        .map_err(Error::Gallery)?;
+    println!(
+        "{}",
+        t!("gallery-written", path = index.display().to_string())
+    );
+    Ok(Exit::Success)
+}
@@ -0,0 +1,110 @@
+use md5::{Digest, Md5};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs};
+use tracing::{debug, error, warn};
+
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::template;
+use uppy::clock;
+
+struct Grave {
+    path: PathBuf,
+    size: u64,
+}
+
+/// Older versions "deleted" files by renaming them to `<md5>.tmp` in the temp directory
+///
+/// Other programs name temp files like that too, so only a file whose contents have the MD5 of
+/// its name counts as one uppy left behind
+fn is_grave(path: &Path) -> bool {
+    let Some(hash) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".tmp"))
+        .filter(|hash| hash.len() == 32 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+    else {
+        return false;
+    };
+    let md5 = File::open(path).and_then(|mut file| {
+        let mut md5 = Md5::new();
+        io::copy(&mut file, &mut md5)?;
+        Ok(format!("{:x}", md5.finalize()))
+    });
+    md5.is_ok_and(|md5| md5.eq_ignore_ascii_case(hash))
+}
+
+/// Graveyard files untouched for at least `min_age`, only those are hashed as hashing them all
+/// would read every `<md5>.tmp` in the temp directory
+fn graveyard(min_age: Duration) -> Vec<Grave> {
+    let entries = match fs::read_dir(env::temp_dir()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("Failed to read the temp directory: {}", err);
+            return Vec::new();
+        }
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let modified = meta.modified().map_or(Duration::ZERO, clock::since_epoch);
+            if clock::now().saturating_sub(modified) < min_age {
+                return None;
+            }
+            is_grave(&entry.path()).then(|| Grave {
+                path: entry.path(),
+                size: meta.len(),
+            })
+        })
+        .collect()
+}
+
+pub fn run(dry_run: bool) -> Exit {
+    let graves = graveyard(Duration::ZERO);
+    if graves.is_empty() {
+        println!("{}", t!("nothing-to-clean"));
+        return Exit::Success;
+    }
+
+    let mut reclaimed = 0;
+    for grave in &graves {
+        println!(
+            "{} ({})",
+            grave.path.display(),
+            template::human_size(grave.size)
+        );
+        if dry_run {
+            reclaimed += grave.size;
+            continue;
+        }
+        match fs::remove_file(&grave.path) {
+            Ok(_) => reclaimed += grave.size,
+            Err(err) => warn!("Failed to remove {}: {}", grave.path.display(), err),
+        }
+    }
+
+    let size = template::human_size(reclaimed);
+    match dry_run {
+        true => println!("{}", t!("would-reclaim", size = size)),
+        false => println!("{}", t!("reclaimed", size = size)),
+    }
+    Exit::Success
+}
+
+/// Quietly removes graveyard files that haven't been touched in `days` days
+pub fn purge_older_than(days: u64) {
+    for grave in graveyard(Duration::from_secs(days.saturating_mul(24 * 60 * 60))) {
+        match fs::remove_file(&grave.path) {
+            Ok(_) => debug!("Purged {}", grave.path.display()),
+            Err(err) => debug!("Failed to purge {}: {}", grave.path.display(), err),
+        }
+    }
+}
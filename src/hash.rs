@@ -0,0 +1,86 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// How much of a file is read at once when it isn't memory mapped
+const READ_SIZE: usize = 1024 * 1024;
+
+/// What a file looked like when it was hashed, a hash is only reused while it still does
+#[derive(PartialEq)]
+struct Stamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Stamp {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+/// BLAKE3 hashes worked out in this run, so spotting duplicates, the history and templates
+/// don't each read the same file again
+static HASHED: OnceLock<Mutex<HashMap<PathBuf, (Stamp, String)>>> = OnceLock::new();
+
+/// BLAKE3 of a file in hex, large files are memory mapped rather than read in chunks
+///
+/// A file that was already hashed and hasn't changed since isn't read again
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let hashed = HASHED.get_or_init(Mutex::default);
+    let stamp = Stamp::of(path)?;
+    let known = hashed.lock().ok().and_then(|hashed| {
+        let (seen, hash) = hashed.get(path)?;
+        (*seen == stamp).then(|| hash.clone())
+    });
+    if let Some(hash) = known {
+        return Ok(hash);
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_mmap(path)?;
+    let hash = hasher.finalize().to_hex().to_string();
+    if let Ok(mut hashed) = hashed.lock() {
+        hashed.insert(path.to_path_buf(), (stamp, hash.clone()));
+    }
+    Ok(hash)
+}
+
+/// Keeps a hash worked out while the file was read for something else, such as uploading it
+pub fn remember(path: &Path, hash: &str) {
+    let (Ok(stamp), Ok(mut hashed)) = (Stamp::of(path), HASHED.get_or_init(Mutex::default).lock())
+    else {
+        return;
+    };
+    hashed.insert(path.to_path_buf(), (stamp, hash.to_string()));
+}
+
+/// Feeds the whole file to `update` in reads of [`READ_SIZE`]
+fn read_chunks(path: &Path, mut update: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; READ_SIZE];
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(()),
+            read => update(&buf[..read]),
+        }
+    }
+}
+
+/// SHA-256 of a file in hex, what hosts that look files up by their checksum go by
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    read_chunks(path, |chunk| hasher.update(chunk))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
@@ -0,0 +1,244 @@
+use chrono::{Local, TimeZone};
+use clipboard_win::{formats, set_clipboard};
+use rusqlite::{params, Connection, Row};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::format::{self, CopyFormat};
+use crate::i18n::t;
+use crate::{style, template, FileReport, FileStatus};
+use uppy::clock;
+use uppy::config::CONFIG_PATH;
+
+/// Each entry upgrades the database by one version, append new ones to the end
+const MIGRATIONS: [&str; 2] = [
+    "CREATE TABLE uploads (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL,
+        url TEXT,
+        deletion_url TEXT,
+        size INTEGER,
+        hash TEXT,
+        uploaded_at INTEGER NOT NULL,
+        host TEXT NOT NULL,
+        status TEXT NOT NULL,
+        error TEXT
+    );
+    CREATE INDEX uploads_hash ON uploads (hash, host);",
+    // `size` is what was sent, which differs from the file once it is converted
+    "ALTER TABLE uploads ADD COLUMN file_size INTEGER;
+    CREATE INDEX uploads_file_size ON uploads (file_size, host);",
+];
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let version = i as i64 + 1;
+        debug!("Migrating the history database to version {}", version);
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", version)?;
+    }
+    Ok(())
+}
+
+/// Opens the upload history in the configuration directory, creating it when needed
+pub fn open() -> rusqlite::Result<Connection> {
+    let conn = Connection::open(CONFIG_PATH.join("history.db"))?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// A timestamp in local time as uppy prints them everywhere
+pub fn date(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(date) => date.format("%Y-%m-%d %H:%M").to_string(),
+        None => String::new(),
+    }
+}
+
+pub fn now() -> i64 {
+    clock::now().as_secs() as i64
+}
+
+pub fn record(conn: &Connection, report: &FileReport, host: &str) {
+    let status = match report.status {
+        FileStatus::Uploaded => "uploaded",
+        FileStatus::Failed => "failed",
+    };
+
+    let inserted = conn.execute(
+        "INSERT INTO uploads (path, url, deletion_url, size, hash, uploaded_at, host, status, error, file_size)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            report.path.to_string_lossy(),
+            report.url,
+            report.deletion_url,
+            report.size.map(|size| size as i64),
+            report.hash,
+            now(),
+            host,
+            status,
+            report.error,
+            report.file_size.map(|size| size as i64),
+        ],
+    );
+    if let Err(err) = inserted {
+        warn!("Failed to record the upload in the history: {}", err);
+    }
+}
+
+/// A successful upload as stored in the history
+pub struct Upload {
+    pub path: String,
+    pub url: String,
+    pub deletion_url: Option<String>,
+    pub size: Option<i64>,
+    pub hash: Option<String>,
+    pub uploaded_at: i64,
+    pub host: String,
+}
+
+impl Upload {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Upload {
+            path: row.get("path")?,
+            url: row.get("url")?,
+            deletion_url: row.get("deletion_url")?,
+            size: row.get("size")?,
+            hash: row.get("hash")?,
+            uploaded_at: row.get("uploaded_at")?,
+            host: row.get("host")?,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.path.rsplit(['\\', '/']).next().unwrap_or(&self.path)
+    }
+
+    pub fn date(&self) -> String {
+        date(self.uploaded_at)
+    }
+}
+
+#[derive(Default)]
+pub struct Filter {
+    pub limit: Option<usize>,
+    pub since: Option<Duration>,
+    pub grep: Option<String>,
+}
+
+/// Successful uploads matching the filter, newest first
+pub fn search(conn: &Connection, filter: &Filter) -> rusqlite::Result<Vec<Upload>> {
+    let since = filter
+        .since
+        .map(|since| now().saturating_sub(since.as_secs().try_into().unwrap_or(i64::MAX)))
+        .unwrap_or(0);
+    // Wildcards in the term are meant literally
+    let grep = filter
+        .grep
+        .as_deref()
+        .unwrap_or_default()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    let grep = format!("%{}%", grep);
+    let limit = filter.limit.map(|limit| limit as i64).unwrap_or(-1);
+
+    let mut statement = conn.prepare(
+        "SELECT * FROM uploads
+         WHERE status = 'uploaded' AND uploaded_at >= ?1
+           AND (path LIKE ?2 ESCAPE '\\' OR url LIKE ?2 ESCAPE '\\')
+         ORDER BY uploaded_at DESC, id DESC
+         LIMIT ?3",
+    )?;
+    let uploads = statement.query_map(params![since, grep, limit], Upload::from_row)?;
+    uploads.collect()
+}
+
+/// Most recent successful upload of a file with this hash to the host
+pub fn find_by_hash(conn: &Connection, hash: &str, host: &str) -> rusqlite::Result<Option<Upload>> {
+    let mut statement = conn.prepare(
+        "SELECT * FROM uploads
+         WHERE status = 'uploaded' AND hash = ?1 AND host = ?2
+         ORDER BY uploaded_at DESC, id DESC
+         LIMIT 1",
+    )?;
+    let mut uploads = statement.query_map(params![hash, host], Upload::from_row)?;
+    uploads.next().transpose()
+}
+
+/// Whether a file of `file_size` bytes was uploaded to `host` before, only then can hashing a
+/// file turn up an earlier upload of it
+///
+/// Uploads recorded before the size of the file was kept apart go by the size that was sent
+pub fn has_size(conn: &Connection, file_size: u64, host: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM uploads
+         WHERE status = 'uploaded' AND hash IS NOT NULL AND host = ?2
+         AND (file_size = ?1 OR (file_size IS NULL AND size = ?1)))",
+        params![file_size as i64, host],
+        |row| row.get(0),
+    )
+}
+
+/// Most recent successful upload with this URL or local path
+pub fn find(conn: &Connection, url_or_path: &str) -> rusqlite::Result<Option<(i64, Upload)>> {
+    let mut statement = conn.prepare(
+        "SELECT * FROM uploads
+         WHERE status = 'uploaded' AND (url = ?1 OR path = ?1)
+         ORDER BY uploaded_at DESC, id DESC
+         LIMIT 1",
+    )?;
+    let mut uploads = statement.query_map(params![url_or_path], |row| {
+        Ok((row.get("id")?, Upload::from_row(row)?))
+    })?;
+    uploads.next().transpose()
+}
+
+/// Marks an upload as deleted from the host so it is no longer listed or reused
+pub fn mark_deleted(conn: &Connection, id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE uploads SET status = 'deleted' WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+pub fn list(filter: &Filter) -> Result<Exit, Error> {
+    let uploads = search(&open()?, filter)?;
+
+    for upload in uploads {
+        let size = upload
+            .size
+            .map(|size| template::human_size(size as u64))
+            .unwrap_or_default();
+        println!(
+            "{}  {}  {}  ({})",
+            upload.date(),
+            upload.url,
+            upload.name(),
+            size
+        );
+    }
+    Ok(Exit::Success)
+}
+
+/// Copies the `n`th most recent upload to the clipboard again
+pub fn last(n: u64, copy_format: CopyFormat) -> Result<Exit, Error> {
+    let filter = Filter {
+        limit: Some(n as usize),
+        ..Default::default()
+    };
+    let uploads = search(&open()?, &filter)?;
+    let Some(upload) = uploads.get(n as usize - 1) else {
+        return Err(Error::HistoryTooShort(uploads.len()));
+    };
+
+    println!("{}  {}", upload.date(), style::success(&upload.url));
+    let text = format::format_url(copy_format, &upload.url, upload.name());
+    set_clipboard(formats::Unicode, text).map_err(Error::Clipboard)?;
+    println!("{}", style::dim(&t!("copied")));
+    Ok(Exit::Success)
+}
@@ -0,0 +1,84 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+
+use crate::template;
+use crate::FileReport;
+
+/// Splits a hook into its program and arguments, filling in placeholders per argument
+/// so values containing spaces stay a single argument
+pub fn command(hook: &str, values: &template::Values) -> Option<Command> {
+    let words = match shell_words::split(hook) {
+        Ok(words) => words,
+        Err(err) => {
+            warn!("Failed to parse hook \"{}\": {}", hook, err);
+            return None;
+        }
+    };
+
+    let mut words = words.iter().map(|word| template::render(word, values));
+    let mut command = Command::new(words.next()?);
+    command.args(words);
+    Some(command)
+}
+
+pub enum PreUpload {
+    /// Upload the file, or the one the hook replaced it with
+    Continue(Option<PathBuf>),
+    Veto(String),
+}
+
+/// Runs the pre upload hook, a non-zero exit code aborts the upload while a path
+/// printed to stdout replaces the file that gets uploaded
+pub fn pre_upload(hook: &str, values: &template::Values) -> PreUpload {
+    let Some(mut command) = command(hook, values) else {
+        return PreUpload::Veto("The pre upload hook could not be parsed".to_string());
+    };
+    debug!(?command, "Running pre upload hook");
+
+    let output = match command.stderr(Stdio::inherit()).output() {
+        Ok(output) => output,
+        Err(err) => return PreUpload::Veto(format!("Failed to run the pre upload hook: {}", err)),
+    };
+    if !output.status.success() {
+        return PreUpload::Veto(format!(
+            "The pre upload hook aborted the upload ({})",
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(path) => PreUpload::Continue(Some(PathBuf::from(path))),
+        None => PreUpload::Continue(None),
+    }
+}
+
+/// Runs the post upload hook with the JSON report of the upload on stdin
+pub fn post_upload(hook: &str, report: &FileReport, values: &template::Values) {
+    let Some(mut command) = command(hook, values) else {
+        return;
+    };
+    debug!(?command, "Running post upload hook");
+
+    // Anything the hook prints goes to stderr so our own stdout stays parseable
+    let mut child = match command.stdin(Stdio::piped()).stdout(io::stderr()).spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            warn!("Failed to run the post upload hook: {}", err);
+            return;
+        }
+    };
+
+    if let (Some(mut stdin), Ok(json)) = (child.stdin.take(), serde_json::to_string(report)) {
+        // The hook is free to ignore its input
+        let _ = stdin.write_all(json.as_bytes());
+    }
+
+    match child.wait() {
+        Ok(status) if status.success() => (),
+        Ok(status) => warn!("The post upload hook exited with {}", status),
+        Err(err) => warn!("Failed to wait on the post upload hook: {}", err),
+    }
+}
@@ -0,0 +1,115 @@
+//! The messages uppy prints, in the language of the user when there is a translation
+//!
+//! Messages live in `locales/<language>.ftl` in the Fluent syntax. Logs and errors stay English,
+//! they end up in bug reports. That includes why an upload failed, a hook vetoing it too, as the
+//! reason is also the `error` of `--json` that scripts match on
+
+use fluent::concurrent::FluentBundle;
+use fluent::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+use unic_langid::LanguageIdentifier;
+
+use crate::sys;
+
+/// Every language with a translation, English first since it's the fallback for missing messages
+const LOCALES: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+];
+
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+struct Messages {
+    bundle: FluentBundle<FluentResource>,
+    english: FluentBundle<FluentResource>,
+}
+
+fn bundle(language: &str, ftl: &str) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = language.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // The marks Fluent puts around arguments turn up as garbage in most consoles
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(ftl.to_string()).unwrap_or_else(|(resource, errors)| {
+        warn!("Messages for {} don't parse: {:?}", language, errors);
+        resource
+    });
+    if let Err(errors) = bundle.add_resource(resource) {
+        warn!("Messages for {} are broken: {:?}", language, errors);
+    }
+    bundle
+}
+
+/// The language Windows is set to show, like `de-DE`
+fn system_language() -> Option<String> {
+    // LOCALE_NAME_MAX_LENGTH
+    let mut name = [0u16; 85];
+    // Safety: the buffer is as long as the length passed along with it
+    let len = unsafe { sys::GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+    // The length includes the terminating NUL, 0 means it failed
+    (len > 1).then(|| String::from_utf16_lossy(&name[..len as usize - 1]))
+}
+
+/// The translation for `language`, going by its primary subtag so `de-AT` gets German too
+fn ftl(language: &str) -> Option<(&'static str, &'static str)> {
+    let primary = language.split(['-', '_']).next()?.to_lowercase();
+    LOCALES.into_iter().find(|(locale, _)| *locale == primary)
+}
+
+fn load(language: Option<&str>) -> Messages {
+    let language = language.map(str::to_string).or_else(system_language);
+    let (locale, messages) = language.as_deref().and_then(ftl).unwrap_or(LOCALES[0]);
+    debug!("Showing messages in {} for {:?}", locale, language);
+    Messages {
+        bundle: bundle(locale, messages),
+        english: bundle(LOCALES[0].0, LOCALES[0].1),
+    }
+}
+
+/// Picks the language messages are shown in, `None` to go by the language of Windows
+///
+/// Only the first call counts, and messages shown before it go by the language of Windows
+pub fn set_language(language: Option<&str>) {
+    let _ = MESSAGES.set(load(language));
+}
+
+/// The message `id`, filled in with `args`
+///
+/// Falls back on the English message when the translation lacks it, and on `id` itself when
+/// there isn't one at all
+pub fn text(id: &str, args: Option<&FluentArgs>) -> String {
+    let messages = MESSAGES.get_or_init(|| load(None));
+    let found = [&messages.bundle, &messages.english]
+        .into_iter()
+        .find_map(|bundle| Some((bundle, bundle.get_message(id)?.value()?)));
+    let Some((bundle, pattern)) = found else {
+        warn!("There is no message {}", id);
+        return id.to_string();
+    };
+    let mut errors = Vec::new();
+    let text = bundle.format_pattern(pattern, args, &mut errors);
+    if !errors.is_empty() {
+        warn!("Failed to fill in message {}: {:?}", id, errors);
+    }
+    text.into_owned()
+}
+
+/// Whether `answer` is one of the comma separated words of the message `id`
+pub fn is_answer(id: &str, answer: &str) -> bool {
+    text(id, None)
+        .split(',')
+        .any(|word| word.trim().eq_ignore_ascii_case(answer))
+}
+
+/// A message in the language of the user, `t!("queued", count = 2)` fills in `$count`
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::text($id, None)
+    };
+    ($id:literal, $($key:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent::FluentArgs::new();
+        $(args.set(stringify!($key), $value);)+
+        $crate::i18n::text($id, Some(&args))
+    }};
+}
+pub(crate) use t;
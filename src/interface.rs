@@ -0,0 +1,44 @@
+//! Finding the address of a network interface to send uploads from
+
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use crate::config::ConfigError;
+use crate::process;
+
+/// Prints the first address of the interface in `UPPY_INTERFACE`, IPv4 before IPv6 and never a link-local one
+const ADDRESS_OF: &str =
+    "Get-NetIPAddress -InterfaceAlias $env:UPPY_INTERFACE -ErrorAction Stop | \
+    Where-Object { -not $_.IPAddress.StartsWith('fe80') } | Sort-Object AddressFamily | \
+    Select-Object -First 1 -ExpandProperty IPAddress";
+
+/// `interface` itself when it is an address, otherwise the address of the interface with that
+/// name, like `WireGuard` or `Ethernet 2`
+pub(crate) fn local_address(interface: &str) -> Result<IpAddr, ConfigError> {
+    if let Ok(address) = interface.trim().parse() {
+        return Ok(address);
+    }
+    let failed = |reason: String| ConfigError::Interface {
+        interface: interface.to_string(),
+        reason,
+    };
+    let output = process::hidden(
+        Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", ADDRESS_OF])
+            .env("UPPY_INTERFACE", interface),
+    )
+    .stderr(Stdio::null())
+    .output()
+    .map_err(|err| failed(err.to_string()))?;
+    let address = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || address.is_empty() {
+        return Err(failed("there is no interface with that name".to_string()));
+    }
+    debug!("Sending from {}, the address of {}", address, interface);
+    // Scoped IPv6 addresses come with a zone after the `%`
+    let address = address.split('%').next().unwrap_or_default();
+    address
+        .parse()
+        .map_err(|_| failed(format!("\"{}\" isn't an address", address)))
+}
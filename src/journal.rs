@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use uppy::clock;
+use uppy::config::CONFIG_PATH;
+
+/// A file that cleanup moved to the Recycle Bin and can still be brought back
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    original: PathBuf,
+    deleted_at: u64,
+}
+
+fn journal_path() -> PathBuf {
+    CONFIG_PATH.join("journal.jsonl")
+}
+
+fn read_entries() -> Vec<Entry> {
+    let journal = match fs::read_to_string(journal_path()) {
+        Ok(journal) => journal,
+        Err(_) => return Vec::new(),
+    };
+    journal
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn write_entries(entries: &[Entry]) {
+    let journal: String = entries
+        .iter()
+        .filter_map(|entry| serde_json::to_string(entry).ok())
+        .map(|line| line + "\n")
+        .collect();
+    if let Err(err) = fs::write(journal_path(), journal) {
+        warn!("Failed to update the deletion journal: {}", err);
+    }
+}
+
+pub fn record(original: &Path) {
+    let entry = Entry {
+        original: original.to_path_buf(),
+        deleted_at: clock::now().as_secs(),
+    };
+
+    let appended = serde_json::to_string(&entry)
+        .map_err(io::Error::from)
+        .and_then(|line| {
+            let mut journal = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(journal_path())?;
+            writeln!(journal, "{}", line)
+        });
+    match appended {
+        Ok(_) => debug!("Recorded deletion of {}", original.display()),
+        Err(err) => warn!("Failed to record the deletion in the journal: {}", err),
+    }
+}
+
+/// Restores `path`, or the most recently deleted file when not given, from the Recycle Bin
+pub fn restore(path: Option<&Path>) -> Result<Exit, Error> {
+    let mut entries = read_entries();
+    let position = match path {
+        Some(path) => entries.iter().rposition(|entry| entry.original == path),
+        None => entries.len().checked_sub(1),
+    };
+    let entry = match position {
+        Some(position) => entries.remove(position),
+        None => return Err(Error::NothingToRestore),
+    };
+
+    let items = trash::os_limited::list().map_err(Error::RecycleBin)?;
+    // The same path may have been deleted more than once, the newest one is ours
+    let item = items
+        .into_iter()
+        .filter(|item| item.original_path() == entry.original)
+        .max_by_key(|item| item.time_deleted);
+
+    match item {
+        Some(item) => match trash::os_limited::restore_all([item]) {
+            Ok(_) => println!(
+                "{}",
+                t!("restored", path = entry.original.display().to_string())
+            ),
+            Err(source) => {
+                return Err(Error::Restore {
+                    path: entry.original,
+                    source,
+                })
+            }
+        },
+        None => warn!(
+            "{} is no longer in the Recycle Bin",
+            entry.original.display()
+        ),
+    }
+
+    write_entries(&entries);
+    Ok(Exit::Success)
+}
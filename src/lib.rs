@@ -0,0 +1,35 @@
+//! The upload engine behind the uppy command line tool
+//!
+//! ```no_run
+//! use uppy::{Client, ClientConfig, UploadRequest};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = Client::new(&ClientConfig {
+//!     host: "https://files.example.com".to_string(),
+//!     token: "secret".to_string(),
+//!     ..Default::default()
+//! })?;
+//! let response = client.upload(&UploadRequest::new("screenshot.png"))?;
+//! println!("{}", response.url);
+//! # Ok(())
+//! # }
+//! ```
+
+mod capabilities;
+pub mod cassette;
+mod checksum;
+mod client;
+pub mod clock;
+pub mod config;
+pub mod ffi;
+mod interface;
+mod oauth;
+mod partial;
+mod pin;
+pub mod process;
+mod progress;
+mod response;
+
+pub use client::{Client, UploadError, UploadRequest, UploadResponse};
+pub use config::{ClientConfig, ConfigError, FilenameEncoding, OAuthConfig};
+pub use progress::Progress;
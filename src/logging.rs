@@ -0,0 +1,66 @@
+use std::path::Path;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+const KEPT_LOG_FILES: usize = 7;
+
+type FileLayer = Option<Box<dyn Layer<Registry> + Send + Sync>>;
+
+/// Allows turning on the log file once the configuration has been read
+pub struct LogHandle(reload::Handle<FileLayer, Registry>);
+
+/// Sets up logging to stderr, warnings and errors are always shown while
+/// `-v` and `-vv` add debug and trace output
+pub fn init(verbosity: u8, color: bool) -> LogHandle {
+    let level = match verbosity {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    let (file, handle) = reload::Layer::new(None);
+    let stderr = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(color)
+        .without_time()
+        .with_target(false)
+        .with_filter(level);
+
+    tracing_subscriber::registry()
+        .with(file)
+        .with(stderr)
+        .init();
+    LogHandle(handle)
+}
+
+impl LogHandle {
+    /// Starts writing debug logs to a daily rotated file in `dir`, only the last week is kept
+    pub fn enable_file(&self, dir: &Path) {
+        let appender = match RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("uppy")
+            .filename_suffix("log")
+            .max_log_files(KEPT_LOG_FILES)
+            .build(dir)
+        {
+            Ok(appender) => appender,
+            Err(err) => {
+                tracing::warn!("Failed to open the log file: {}", err);
+                return;
+            }
+        };
+
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(appender)
+            .with_ansi(false)
+            .with_filter(LevelFilter::DEBUG)
+            .boxed();
+        if let Err(err) = self.0.reload(Some(layer)) {
+            tracing::warn!("Failed to enable the log file: {}", err);
+        }
+    }
+}
@@ -1,229 +1,1834 @@
+use clap::Parser;
 use clipboard_win::{formats, set_clipboard};
-use lazy_static::lazy_static;
-use md5::{Digest, Md5};
-use reqwest::blocking::{self, multipart};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::io::Read;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
-use std::{env, fs, io};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, fs, iter, thread};
+use tracing::{debug, error, warn};
+use uppy::cassette::Cassette;
+use uppy::config::{self, read_config, ConfigError};
+use uppy::{Client, ClientConfig, UploadError, UploadRequest};
 
-lazy_static! {
-    static ref CONFIG_PATH: PathBuf = {
-        let user_profile = std::env::var("USERPROFILE")
-            .expect("Somehow your userprofile isnt set, congratulations on getting this error");
-        let config_path_str = format!("{}\\.config\\uppy", user_profile);
-        PathBuf::from(config_path_str)
-    };
-}
+mod archive;
+mod audit;
+mod batch;
+mod bench;
+mod cancel;
+mod cleanup;
+mod cli;
+mod clipboard;
+mod convert;
+mod crypt;
+mod download;
+mod error;
+mod exit;
+mod fetch;
+mod format;
+mod gallery;
+mod gc;
+mod hash;
+mod history;
+mod hooks;
+mod i18n;
+mod journal;
+mod logging;
+mod manual;
+mod metadata;
+mod minisign;
+mod name;
+mod native;
+mod notify;
+mod ocr;
+mod offline;
+mod picker;
+mod pipeline;
+mod ratelimit;
+mod rm;
+mod scan;
+mod sidecar;
+mod split;
+mod status;
+mod style;
+mod sync;
+mod sys;
+mod temp;
+mod template;
+mod term;
+mod thumbnail;
+mod transfer;
+mod tui;
+mod update;
+mod walk;
+mod watch;
+mod winpath;
+
+use batch::{Batch, Status};
+use cleanup::{Cleanup, Removal};
+use cli::{Args, Command, HistoryCommand, Order, Print, QueueCommand};
+use clipboard::Copied;
+use convert::Transform;
+use error::Error;
+use exit::Exit;
+use format::CopyFormat;
+use hooks::PreUpload;
+use i18n::t;
+use transfer::Meter;
+use walk::Symlinks;
 
 #[derive(Serialize, Deserialize)]
 struct Configuration {
-    host: String,
-    token: String,
+    #[serde(flatten)]
+    client: ClientConfig,
+    #[serde(default)]
+    copy_format: CopyFormat,
+    output: Option<String>,
+    #[serde(default)]
+    log_file: bool,
+    #[serde(default)]
+    cleanup: Cleanup,
+    #[serde(default = "default_shred_passes")]
+    shred_passes: u32,
+    gc_after_days: Option<u64>,
+    /// Language of the messages, like `de`, instead of the one Windows is set to
+    language: Option<String>,
+    pre_upload_hook: Option<String>,
+    /// `defender`, or a command that exits with anything but 0 for files that shouldn't be uploaded
+    malware_scan: Option<String>,
+    post_upload_hook: Option<String>,
+    notify_webhook: Option<String>,
+    update_check_days: Option<u64>,
+    #[serde(default)]
+    strip_metadata: bool,
+    /// Replace spaces, control characters and the like in the names hosts are sent
+    #[serde(default = "default_true")]
+    sanitize_names: bool,
+    #[serde(default)]
+    randomize_names: bool,
+    /// Extensions that are refused without `--force`, such as `pem` or `exe`
+    #[serde(default)]
+    blocked_extensions: Vec<String>,
+    /// When set, only these extensions are uploaded without `--force`
+    allowed_extensions: Option<Vec<String>>,
+    url_rewrite: Option<UrlRewrite>,
+    /// Most uploads to the host in a window, like `"10/minute"`
+    rate_limit: Option<ratelimit::RateLimit>,
+    /// Largest upload the host takes, as bytes or a size like `"95MB"`
+    #[serde(default, deserialize_with = "deserialize_size")]
+    max_size: Option<u64>,
+    /// Extensions and the profile files with them are uploaded to when no profile is picked
+    #[serde(default)]
+    routes: HashMap<String, String>,
+    /// Captures and the flags to upload them with, see [`pipeline::Pipeline`]
+    #[serde(default)]
+    pipelines: HashMap<String, pipeline::Pipeline>,
+    #[serde(default = "default_true")]
+    history: bool,
+    #[serde(default)]
+    audit_log: bool,
+    /// List every upload in `uppy status`, not only those of `uppy watch` and the queue
+    #[serde(default)]
+    publish_status: bool,
+    /// What `--archive` packs files into when not given a format
+    #[serde(default)]
+    archive_format: archive::ArchiveFormat,
+    /// The zstd level of `tar.zst` archives, 3 by default
+    zstd_level: Option<i32>,
+    /// Deflate level of zip archives, 0 to 9
+    zip_level: Option<i64>,
+    /// How many threads compress archives, 1 by default
+    archive_threads: Option<usize>,
+    /// Milliseconds a file `uppy watch` finds has to stay the same before it is uploaded
+    watch_debounce_ms: Option<u64>,
+    /// Globs of the files `uppy watch` leaves alone, downloads that haven't finished by default
+    watch_ignore: Option<Vec<String>>,
+    /// The key `--sign` signs with, instead of the one minisign keeps in `.minisign`
+    minisign_key: Option<PathBuf>,
+    #[serde(default = "default_true")]
+    thumbnails: bool,
+    /// Folders `uppy native-host` uploads files from when a browser extension asks, none by default
+    #[serde(default)]
+    native_host_dirs: Vec<PathBuf>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct JSONResponse {
-    files: Vec<String>,
+/// Swaps the start of returned URLs, for uploads that go to an origin but are linked through a CDN
+#[derive(Serialize, Deserialize)]
+struct UrlRewrite {
+    from: String,
+    to: String,
 }
 
-enum UploadResult {
-    Success(blocking::Response),
-    IOError(std::io::Error),
-    ReqwestError(reqwest::Error),
-    HTTPClientError(reqwest::StatusCode),
-    HTTPServerError(reqwest::StatusCode),
+impl UrlRewrite {
+    fn apply(&self, url: String) -> String {
+        match url.strip_prefix(&self.from) {
+            Some(rest) => format!("{}{}", self.to, rest),
+            None => url,
+        }
+    }
 }
 
-enum DeletionChoice {
-    Yes,
-    No,
-    InvalidChoice,
+fn default_true() -> bool {
+    true
 }
 
-fn read_config() -> serde_json::Result<Configuration> {
-    let json = fs::read_to_string(CONFIG_PATH.join("config.json")).expect("Failed to read file");
-    let c: Configuration =
-        serde_json::from_str(&json).expect("JSON file is not formatted properly");
-    Ok(c)
+fn default_shred_passes() -> u32 {
+    3
 }
 
-fn construct_headers(config: &Configuration) -> HeaderMap {
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&config.token).expect("Failed to convert token to header"),
-    );
-    headers.insert("Format", HeaderValue::from_static("RANDOM"));
-    headers.insert("Embed", HeaderValue::from_static("true"));
-    headers
+fn deserialize_size<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Text(size)) => split::parse_size(&size)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
 }
 
-fn upload_file(path: &PathBuf, config: Configuration, headers: HeaderMap) -> UploadResult {
-    let form = match multipart::Form::new().file("file", path) {
-        Ok(form) => form,
-        Err(err) => return UploadResult::IOError(err),
-    };
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    Uploaded,
+    Failed,
+}
 
-    let client = reqwest::blocking::Client::new();
-    let res = match client
-        .post(format!("{}/api/upload", config.host))
-        .multipart(form)
-        .headers(headers)
-        .send()
-    {
-        Ok(res) => {
-            if res.status().is_client_error() {
-                return UploadResult::HTTPClientError(res.status());
-            } else if res.status().is_server_error() {
-                return UploadResult::HTTPServerError(res.status());
+/// Outcome of a single file, printed as a JSON line with `--json`
+#[derive(Serialize)]
+struct FileReport {
+    path: PathBuf,
+    url: Option<String>,
+    deletion_url: Option<String>,
+    size: Option<u64>,
+    /// Size of the file itself, `size` is that of what was sent
+    #[serde(skip)]
+    file_size: Option<u64>,
+    hash: Option<String>,
+    /// SHA-256 of what was actually sent, which differs from the file once it is converted or encrypted
+    sha256: Option<String>,
+    /// Text recognized in the image with `--ocr`
+    ocr: Option<String>,
+    /// Which of the tokens the upload went through, counting from 1, when there are several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<usize>,
+    /// Where the `--sign` signature of the file went
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature_url: Option<String>,
+    /// Where the `--manifest` of the file went
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest_url: Option<String>,
+    status: FileStatus,
+    /// Whether the URL came from an earlier upload of the same file
+    reused: bool,
+    /// The file earlier in the batch with the same contents, whose upload this one reuses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<PathBuf>,
+    error: Option<String>,
+    #[serde(skip)]
+    elapsed: Duration,
+    #[serde(skip)]
+    failure: Option<Exit>,
+}
+
+impl FileReport {
+    fn new(path: &Path) -> Self {
+        let size = fs::metadata(path).map(|meta| meta.len()).ok();
+        FileReport {
+            path: path.to_path_buf(),
+            url: None,
+            deletion_url: None,
+            size,
+            file_size: size,
+            hash: None,
+            sha256: None,
+            ocr: None,
+            token: None,
+            signature_url: None,
+            manifest_url: None,
+            status: FileStatus::Failed,
+            reused: false,
+            duplicate_of: None,
+            error: None,
+            elapsed: Duration::ZERO,
+            failure: None,
+        }
+    }
+
+    fn fail(mut self, failure: Exit, error: String) -> Self {
+        error!("{}", error);
+        self.error = Some(error);
+        self.failure = Some(failure);
+        self
+    }
+}
+
+/// What the upload threads tell the one keeping track of the batch
+enum Upload {
+    Started(usize),
+    Finished(usize, Box<FileReport>, Vec<String>),
+}
+
+/// Settings resolved from the command line and configuration that apply to every file
+struct Context {
+    copy_format: CopyFormat,
+    output: Option<String>,
+    print: Print,
+    cleanup: Cleanup,
+    removal: Removal,
+    client: Client,
+    /// Shared by the upload threads
+    history: Option<Mutex<rusqlite::Connection>>,
+    force: bool,
+    transform: Transform,
+    optimize: bool,
+    strip_metadata: bool,
+    sanitize_names: bool,
+    randomize_name: bool,
+    ocr: bool,
+    rate_limit: Option<ratelimit::RateLimit>,
+    audit: bool,
+    /// `--manifest`, with the `--note` to put in it
+    manifest: Option<Option<String>>,
+    /// `--sign`, with the key already decrypted
+    signing_key: Option<minisign::SigningKey>,
+    encrypt: bool,
+    age_recipients: Vec<age::x25519::Recipient>,
+    gpg_recipients: Vec<String>,
+    /// Only drawn for someone watching the status output
+    meter: Option<Arc<Meter>>,
+}
+
+fn template_values(template: &str, report: &FileReport, host: &str) -> template::Values {
+    let file = &report.path;
+    let url = report.url.as_deref().unwrap_or_default();
+
+    let mut values = template::Values::new();
+    values.insert("url", url.to_string());
+    values.insert("raw_url", template::raw_url(url));
+    values.insert("path", winpath::plain(file).display().to_string());
+    values.insert(
+        "name",
+        file.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    );
+    values.insert(
+        "size",
+        report.size.map(template::human_size).unwrap_or_default(),
+    );
+    values.insert(
+        "mime",
+        mime_guess::from_path(file)
+            .first_or_octet_stream()
+            .to_string(),
+    );
+    values.insert("host", host.to_string());
+    values.insert("elapsed", template::human_duration(report.elapsed));
+    values.insert("sha256", report.sha256.clone().unwrap_or_default());
+    values.insert("ocr", report.ocr.clone().unwrap_or_default());
+    values.insert(
+        "signature_url",
+        report.signature_url.clone().unwrap_or_default(),
+    );
+    values.insert(
+        "manifest_url",
+        report.manifest_url.clone().unwrap_or_default(),
+    );
+
+    // Hashing means reading the whole file again so only do it when asked for
+    if let Some(hash) = &report.hash {
+        values.insert("hash", hash.clone());
+    } else if template::uses(template, "hash") {
+        match hash::hash_file(file) {
+            Ok(hash) => {
+                values.insert("hash", hash);
             }
-            res
+            Err(err) => warn!("Failed to hash file for the template: {}", err),
         }
-        Err(err) => return UploadResult::ReqwestError(err),
+    }
+    values
+}
+
+/// Why the extension of `file` keeps it from being uploaded without `--force`, if it does
+fn blocked(file: &Path, config: &Configuration) -> Option<String> {
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let listed = |extensions: &[String]| {
+        extensions.iter().any(|listed| {
+            listed
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(&extension)
+        })
     };
 
-    UploadResult::Success(res)
+    if listed(&config.blocked_extensions) {
+        return Some(format!(
+            "{} has a blocked extension, pass --force to upload it anyway",
+            file.display()
+        ));
+    }
+    match &config.allowed_extensions {
+        Some(allowed) if !listed(allowed) => Some(format!(
+            "{} doesn't have an allowed extension, pass --force to upload it anyway",
+            file.display()
+        )),
+        _ => None,
+    }
+}
+
+/// Why `size` bytes are more than the host takes, with a `--split` size that fits
+fn too_large(file: &Path, size: u64, max_size: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    let part = match max_size >= MB {
+        true => format!("{}MB", max_size / MB),
+        false => format!("{}KB", (max_size / 1024).max(1)),
+    };
+    format!(
+        "{} is {}, more than the {} the host takes, upload it in parts with --split {}",
+        file.display(),
+        template::human_size(size),
+        template::human_size(max_size),
+        part
+    )
 }
 
-fn file_cleanup(file: PathBuf) {
-    println!("Would you like to delete the file? (Y/N)");
+fn process_file(target_file: &Path, config: &Configuration, ctx: &Context) -> FileReport {
+    let mut report = FileReport::new(target_file);
 
-    let mut buf: String = String::new();
-    io::stdin().read_line(&mut buf).unwrap();
+    if let (false, Some(reason)) = (ctx.force, blocked(target_file, config)) {
+        return report.fail(Exit::Failure, reason);
+    }
 
-    let choice = match buf.trim().to_lowercase().as_str() {
-        "yes" | "y" => DeletionChoice::Yes,
-        "no" | "n" => DeletionChoice::No,
-        _ => DeletionChoice::InvalidChoice,
+    // The history keeps hashes around for spotting files that were already uploaded, encrypted
+    // uploads are left out so they are never handed out in place of a plain one or the other way round
+    let encrypts = ctx.encrypt || !ctx.age_recipients.is_empty() || !ctx.gpg_recipients.is_empty();
+    let wants_hash = !encrypts && (ctx.print == Print::Json || ctx.history.is_some());
+    // Only looking the file up needs the hash before uploading, and only when a file as large was
+    // uploaded before, otherwise it comes from the upload reading the file
+    let lookup = match (&ctx.history, report.file_size) {
+        (Some(conn), Some(size)) if wants_hash && !ctx.force => conn.lock().map_or(true, |conn| {
+            history::has_size(&conn, size, ctx.client.host())
+                .map_err(|err| warn!("Failed to look the file up in the history: {}", err))
+                .unwrap_or(true)
+        }),
+        (Some(_), None) => wants_hash && !ctx.force,
+        _ => false,
     };
+    if lookup {
+        match hash::hash_file(target_file) {
+            Ok(hash) => report.hash = Some(hash),
+            Err(err) => warn!("Failed to hash file: {}", err),
+        }
+    }
 
-    match choice {
-        DeletionChoice::Yes => {
-            let mut f = fs::File::open(&file).unwrap();
-            let mut buf = [0; 1024];
-            let mut hash = Md5::new();
+    if let (false, Some(conn), Some(hash)) = (ctx.force, &ctx.history, &report.hash) {
+        let found = match conn.lock() {
+            Ok(conn) => history::find_by_hash(&conn, hash, ctx.client.host()),
+            Err(_) => Ok(None),
+        };
+        match found {
+            Ok(Some(upload)) => {
+                debug!(
+                    "{} was already uploaded as {}",
+                    target_file.display(),
+                    upload.url
+                );
+                report.status = FileStatus::Uploaded;
+                report.reused = true;
+                report.url = Some(upload.url);
+                report.deletion_url = upload.deletion_url;
+                return report;
+            }
+            Ok(None) => (),
+            Err(err) => warn!("Failed to look the file up in the history: {}", err),
+        }
+    }
 
-            loop {
-                let bytes_read = match f.read(&mut buf) {
-                    Ok(bytes) => bytes,
-                    Err(err) => {
-                        eprintln!("Error reading file: {}", err);
-                        break;
+    let mut upload_path = target_file.to_path_buf();
+    if let Some(hook) = &config.pre_upload_hook {
+        match hooks::pre_upload(hook, &template_values(hook, &report, ctx.client.host())) {
+            PreUpload::Continue(None) => (),
+            PreUpload::Continue(Some(path)) => {
+                debug!("Pre upload hook replaced the file with {}", path.display());
+                report.size = fs::metadata(&path).map(|meta| meta.len()).ok();
+                upload_path = path;
+            }
+            PreUpload::Veto(reason) => return report.fail(Exit::Failure, reason),
+        }
+    }
+
+    if let Some(scanner) = &config.malware_scan {
+        if let Err(reason) = scan::scan(scanner, &upload_path) {
+            return report.fail(Exit::Failure, reason);
+        }
+    }
+
+    // Kept alive until the upload is done, dropping them removes the copies
+    let mut copies = Vec::new();
+    if ctx.transform.conversion.is_some() || ctx.transform.max_dim.is_some() {
+        match convert::transform(&upload_path, ctx.transform) {
+            Ok(Some(copy)) => copies.push(copy),
+            Ok(None) => (),
+            Err(err) => {
+                return report.fail(
+                    Exit::Failure,
+                    format!("Failed to convert or resize the image: {}", err),
+                )
+            }
+        }
+    }
+    if ctx.optimize {
+        let file = copies
+            .last()
+            .map_or(upload_path.as_path(), |copy| copy.path.as_path());
+        match convert::optimize(file) {
+            Ok(Some(copy)) => copies.push(copy),
+            Ok(None) => (),
+            // Nothing is lost by uploading the PNG as it is
+            Err(err) => warn!("Failed to optimize {}: {}", file.display(), err),
+        }
+    }
+    if ctx.strip_metadata {
+        let file = copies
+            .last()
+            .map_or(upload_path.as_path(), |copy| copy.path.as_path());
+        match metadata::strip(file) {
+            Ok(Some(copy)) => copies.push(copy),
+            Ok(None) => (),
+            Err(err) => {
+                return report.fail(
+                    Exit::Failure,
+                    format!("Failed to strip metadata, not uploading: {}", err),
+                )
+            }
+        }
+    }
+    let mut key = None;
+    if ctx.encrypt {
+        let file = copies
+            .last()
+            .map_or(upload_path.as_path(), |copy| copy.path.as_path());
+        match crypt::encrypt(file) {
+            Ok((copy, encryption_key)) => {
+                copies.push(copy);
+                key = Some(encryption_key);
+            }
+            Err(err) => {
+                return report.fail(
+                    Exit::Failure,
+                    format!("Failed to encrypt, not uploading: {}", err),
+                )
+            }
+        }
+    }
+    if !ctx.age_recipients.is_empty() {
+        let file = copies
+            .last()
+            .map_or(upload_path.as_path(), |copy| copy.path.as_path());
+        match crypt::encrypt_age(file, &ctx.age_recipients) {
+            Ok(copy) => copies.push(copy),
+            Err(err) => {
+                return report.fail(
+                    Exit::Failure,
+                    format!("Failed to encrypt with age, not uploading: {}", err),
+                )
+            }
+        }
+    }
+    if !ctx.gpg_recipients.is_empty() {
+        let file = copies
+            .last()
+            .map_or(upload_path.as_path(), |copy| copy.path.as_path());
+        match crypt::encrypt_gpg(file, &ctx.gpg_recipients) {
+            Ok(copy) => copies.push(copy),
+            Err(err) => {
+                return report.fail(
+                    Exit::Failure,
+                    format!("Failed to encrypt with gpg, not uploading: {}", err),
+                )
+            }
+        }
+    }
+    if let Some(copy) = copies.last() {
+        report.size = fs::metadata(&copy.path).map(|meta| meta.len()).ok();
+        upload_path = copy.path.clone();
+    }
+    // Refused here rather than after streaming the whole file only to get a 413 back
+    if let (Some(max_size), Some(size)) = (config.max_size, report.size) {
+        if size > max_size {
+            return report.fail(Exit::Failure, too_large(target_file, size, max_size));
+        }
+    }
+
+    let rewrite = |url| match &config.url_rewrite {
+        Some(rewrite) => rewrite.apply(url),
+        None => url,
+    };
+    // Encrypted copies are different every time, the host never has them
+    if !ctx.force && !encrypts && config.client.lookup_url.is_some() {
+        let found = hash::sha256_file(&upload_path)
+            .map_err(UploadError::IOError)
+            .and_then(|sha256| Ok((ctx.client.lookup(&sha256)?, sha256)));
+        match found {
+            Ok((Some(url), sha256)) => {
+                debug!("The host already has {} as {}", target_file.display(), url);
+                report.status = FileStatus::Uploaded;
+                report.reused = true;
+                report.url = Some(rewrite(url));
+                report.sha256 = Some(sha256);
+                if wants_hash && report.hash.is_none() {
+                    report.hash = hash::hash_file(target_file).ok();
+                }
+                return report;
+            }
+            Ok((None, _)) => (),
+            Err(err) => warn!("Failed to look the file up on the host: {}", err),
+        }
+    }
+
+    if let Some(limit) = &ctx.rate_limit {
+        limit.wait(ctx.client.host(), ctx.print == Print::Status);
+    }
+
+    let mut request = UploadRequest::new(upload_path);
+    if let Some(name) = name::upload_name(&request.path, ctx.sanitize_names, ctx.randomize_name) {
+        debug!("Uploading {} as {}", target_file.display(), name);
+        request = request.with_file_name(name);
+    }
+    // Signed before uploading, so a file never goes out without the signature it was meant to have
+    let signature = match &ctx.signing_key {
+        Some(key) => {
+            let name = request.file_name.clone().unwrap_or_else(|| {
+                request
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            });
+            match key.sign(&request.path, &name) {
+                Ok(signature) => Some((name, signature)),
+                Err(err) => {
+                    return report.fail(
+                        Exit::Failure,
+                        format!("Failed to sign, not uploading: {}", err),
+                    )
+                }
+            }
+        }
+        None => None,
+    };
+    let streams_original = request.path == target_file;
+    let started = Instant::now();
+    let res = ctx.client.upload(&request);
+    if ctx.audit {
+        let mut attempt = audit::Attempt::new(ctx.client.host(), target_file, started.elapsed());
+        attempt.name = request.file_name.as_deref();
+        attempt.size = report.size;
+        match &res {
+            Ok(res) => {
+                attempt.uploaded = true;
+                attempt.status = Some(res.status.as_u16());
+                attempt.url = Some(&res.url);
+            }
+            Err(err) => {
+                attempt.status = match err {
+                    UploadError::HTTPClientError(status) | UploadError::HTTPServerError(status) => {
+                        Some(status.as_u16())
                     }
+                    _ => None,
                 };
-                if bytes_read == 0 {
-                    break;
-                }
-                hash.update(&buf[..bytes_read]);
+                attempt.error = Some(err.to_string());
             }
+        }
+        audit::record(&attempt);
+    }
+    drop(copies);
+    let res = match res {
+        Ok(res) => res,
+        Err(err @ UploadError::HTTPClientError(StatusCode::PAYLOAD_TOO_LARGE)) => {
+            let reason = format!(
+                "{}, the host refused {} as too large, upload it in parts with --split or set max_size",
+                err,
+                target_file.display()
+            );
+            return report.fail(Exit::from(&err), reason);
+        }
+        Err(err) => return report.fail(Exit::from(&err), err.to_string()),
+    };
 
-            let result = hash.finalize();
+    report.elapsed = res.elapsed;
+    report.status = FileStatus::Uploaded;
+    // Browsers never send the fragment, so the key stays between whoever has the link
+    let url = rewrite(res.url);
+    report.url = Some(match key {
+        Some(key) => format!("{}#{}", url, key),
+        None => url,
+    });
+    report.deletion_url = res.deletion_url;
+    report.sha256 = Some(res.sha256);
+    if wants_hash && report.hash.is_none() {
+        report.hash = match streams_original {
+            true => {
+                hash::remember(target_file, &res.blake3);
+                Some(res.blake3)
+            }
+            // What was sent is a copy, the history goes by the file itself
+            false => hash::hash_file(target_file)
+                .map_err(|err| warn!("Failed to hash file: {}", err))
+                .ok(),
+        };
+    }
+    if !config.client.tokens.is_empty() {
+        report.token = Some(res.token + 1);
+    }
+    if let Some((name, signature)) = signature {
+        let name = format!("{}.minisig", name);
+        match sidecar::upload(&ctx.client, &name, signature.as_bytes(), ctx.sanitize_names) {
+            Ok(url) => report.signature_url = Some(rewrite(url)),
+            Err(err) => warn!(
+                "Failed to upload the signature of {}: {}",
+                target_file.display(),
+                err
+            ),
+        }
+    }
+    if let Some(note) = &ctx.manifest {
+        // Anonymous uploads don't give the original name away here either
+        let name = match &request.file_name {
+            Some(name) if ctx.randomize_name => name.clone(),
+            _ => target_file
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        let uploaded = sidecar::manifest(&report, &name, note.as_deref())
+            .map_err(|err| UploadError::IOError(err.into()))
+            .and_then(|json| {
+                let name = format!("{}.manifest.json", name);
+                sidecar::upload(&ctx.client, &name, &json, ctx.sanitize_names)
+            });
+        match uploaded {
+            Ok(url) => report.manifest_url = Some(rewrite(url)),
+            Err(err) => warn!(
+                "Failed to upload the manifest of {}: {}",
+                target_file.display(),
+                err
+            ),
+        }
+    }
+    // The original rather than a converted copy, the OCR engines can't all read webp or avif
+    if ctx.ocr && format::is_image(&target_file.to_string_lossy()) {
+        match ocr::recognize(target_file) {
+            Ok(text) => report.ocr = Some(text),
+            Err(err) => warn!(
+                "Failed to recognize text in {}: {}",
+                target_file.display(),
+                err
+            ),
+        }
+    }
+    report
+}
 
-            let temp_path = PathBuf::from(env::temp_dir().join(format!("{:x}.tmp", result)));
-            match fs::rename(&file, temp_path) {
-                Ok(_) => println!("File deleted!"),
-                Err(err) => println!(
-                    "Something went wrong while moving the file to the temp dir: {}",
-                    err
-                ),
-            };
+/// Builds the clipboard text for an uploaded file and prints it in the requested way
+fn present(report: &FileReport, ctx: &Context) -> Option<String> {
+    let url = report.url.as_deref()?;
+    let name = report
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    let text = match &ctx.output {
+        Some(output) => {
+            let values = template_values(output, report, ctx.client.host());
+            template::render(output, &values)
+        }
+        None => format::format_url(ctx.copy_format, url, &name),
+    };
+    match (&ctx.output, ctx.print) {
+        (_, Print::Json) => (),
+        (Some(_), _) => println!("{}", text),
+        (None, Print::Url) => println!("{}", url),
+        (None, Print::Status) if report.reused => {
+            println!("{}", t!("already-uploaded", url = style::success(url)))
         }
+        (None, Print::Status) => println!("{}", t!("uploaded", url = style::success(url))),
+    }
+    if let (None, Print::Status) = (&ctx.output, ctx.print) {
+        if let Some(signature) = &report.signature_url {
+            println!(
+                "{}",
+                t!("signature-uploaded", url = style::success(signature))
+            );
+        }
+        if let Some(manifest) = &report.manifest_url {
+            println!(
+                "{}",
+                t!("manifest-uploaded", url = style::success(manifest))
+            );
+        }
+    }
+    Some(text)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let stderr_color = style::init(args.color);
+    let log = logging::init(args.verbose, stderr_color);
+
+    match run(&args, &log) {
+        Ok(exit) => exit.into(),
+        Err(err) => {
+            error!("{}", err);
+            Exit::from(&err).into()
+        }
+    }
+}
 
-        DeletionChoice::No => (),
-        DeletionChoice::InvalidChoice => eprintln!("Invalid choice"),
+/// The client for the configured host, going through the cassette when recording or replaying
+fn client(args: &Args, config: &Configuration) -> Result<Client, Error> {
+    let client = match &args.interface {
+        Some(interface) => Client::new(&ClientConfig {
+            local_address: Some(interface.clone()),
+            ..config.client.clone()
+        })?,
+        None => Client::new(&config.client)?,
+    };
+    #[cfg(feature = "http3")]
+    let client = match args.http3 {
+        true => client.with_http3().map_err(Error::Http3)?,
+        false => client,
+    };
+    #[cfg(not(feature = "http3"))]
+    if args.http3 {
+        return Err(Error::NoHttp3);
     }
+    Ok(match (&args.record, &args.replay) {
+        (Some(path), _) => client.with_cassette(Cassette::record(path)),
+        (_, Some(path)) => client.with_cassette(Cassette::replay(path).map_err(Error::Cassette)?),
+        _ => client,
+    })
 }
 
-fn main() {
-    let config_path_str = format!("{}\\.config\\uppy", std::env::var("USERPROFILE").unwrap());
-    let config_path: &Path = Path::new(&config_path_str);
+fn run(args: &Args, log: &logging::LogHandle) -> Result<Exit, Error> {
+    // These work before uppy is configured, and without creating the configuration
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            cli::completions(shell);
+            return Ok(Exit::Success);
+        }
+        Some(Command::Manpage) => {
+            manual::manpage().map_err(Error::Stdout)?;
+            return Ok(Exit::Success);
+        }
+        Some(Command::SelfUpdate { check }) => return update::run(check),
+        Some(Command::Decrypt { ref url, ref out }) => return crypt::run(url, out.as_deref()),
+        Some(Command::Join {
+            ref manifest,
+            ref out,
+        }) => return split::join(manifest, out.as_deref()),
+        // Each message is uploaded by its own uppy, which reads the rest of the configuration itself
+        Some(Command::NativeHost { .. }) => {
+            let config: Option<Configuration> = read_config(None).ok();
+            return native::run(
+                &config
+                    .map(|config| config.native_host_dirs)
+                    .unwrap_or_default(),
+            );
+        }
+        None if args.help_all => {
+            manual::help_all();
+            return Ok(Exit::Success);
+        }
+        None if args.files.is_empty() && !term::available() => cli::missing_files(),
+        _ => (),
+    }
+
+    let config_path = config::config_dir()?;
 
-    let config: Configuration = match fs::create_dir(&config_path) {
+    let config: Configuration = match fs::create_dir(config_path) {
+        // A machine wide or project configuration says where to upload, the template would only override it
+        Ok(_) if config::has_shared_config() => read_config(args.profile.as_deref())?,
         Ok(_) => {
             let template = json!({
                 "host": "https://",
                 "token": "",
             });
-            let json = serde_json::to_string_pretty(&template).expect("Failed to serialise data");
+            let json = serde_json::to_string_pretty(&template).map_err(ConfigError::from)?;
 
-            fs::write(CONFIG_PATH.join("config.json"), json)
-                .expect("Failed to write to configuration file please fill it out manually");
+            fs::write(config_path.join("config.json"), json).map_err(ConfigError::Write)?;
 
-            println!("Configuration directory created in .config");
-            return;
+            println!("{}", t!("config-created"));
+            return Ok(Exit::Config);
         }
 
-        Err(_) => match read_config() {
-            // Configuration already exists, continue..
-            Ok(c) => c,
-            Err(err) => {
-                eprintln!("Error reading configuration file: {}", err);
-                return;
+        // Configuration already exists, continue..
+        Err(_) => read_config(args.profile.as_deref())?,
+    };
+
+    i18n::set_language(config.language.as_deref());
+    if config.log_file {
+        log.enable_file(&config_path.join("logs"));
+    }
+    if let Some(days) = config.gc_after_days {
+        gc::purge_older_than(days);
+    }
+    let update = config.update_check_days.map(update::check_in_background);
+    // Picks up where a flusher left off, such as after a reboot
+    let flushing = matches!(args.command, Some(Command::Queue { .. }));
+    if !flushing && args.record.is_none() && args.replay.is_none() && offline::pending() {
+        offline::spawn_flusher();
+    }
+
+    let result = match &args.command {
+        Some(Command::Gc { dry_run }) => Ok(gc::run(*dry_run)),
+        Some(Command::Restore { path, .. }) => {
+            let dir = env::current_dir().map_err(Error::CurrentDir)?;
+            journal::restore(path.as_ref().map(|path| dir.join(path)).as_deref())
+        }
+        Some(Command::History { filter, command }) => match command {
+            Some(HistoryCommand::Gallery { out }) => gallery::export(&filter.into(), out),
+            None => history::list(&filter.into()),
+        },
+        Some(Command::Last { n }) => {
+            history::last(*n, args.copy_format.unwrap_or(config.copy_format))
+        }
+        Some(Command::Rm { target }) => rm::run(target, &client(args, &config)?),
+        Some(Command::Bench { sizes, count, keep }) => {
+            bench::run(&client(args, &config)?, sizes, *count, *keep)
+        }
+        Some(Command::Queue { command }) => match command {
+            Some(QueueCommand::Flush { wait }) => offline::flush(*wait),
+            Some(QueueCommand::Pause) => offline::pause(),
+            Some(QueueCommand::Resume) => offline::resume(),
+            Some(QueueCommand::Clear) => offline::clear(),
+            None => Ok(offline::list()),
+        },
+        Some(Command::Status) => status::show(),
+        // Already printed before the configuration was read
+        Some(
+            Command::Completions { .. }
+            | Command::Manpage
+            | Command::SelfUpdate { .. }
+            | Command::Decrypt { .. }
+            | Command::Join { .. }
+            | Command::NativeHost { .. },
+        ) => Ok(Exit::Success),
+        Some(Command::Get { target, out }) => {
+            download::get(&client(args, &config)?, target, out.as_deref())
+        }
+        Some(Command::Fetch { url }) => fetch(args, &config, url),
+        Some(Command::Sync { dir, map, verify }) => {
+            sync(args, &config, dir, map.as_deref(), *verify)
+        }
+        Some(Command::Pick { dir }) => {
+            let flags = cli::flags();
+            let dir = env::current_dir().map_err(Error::CurrentDir)?.join(dir);
+            if !dir.is_dir() {
+                return Err(Error::NotADirectory(dir));
+            }
+            let Some(files) = picker::check(&dir, &filter(args))? else {
+                return Ok(Exit::Cancelled);
+            };
+            upload_picked(args, &config, files, flags)
+        }
+        Some(Command::Tui { dir }) => {
+            let current = env::current_dir().map_err(Error::CurrentDir)?;
+            let dir = current.join(dir.as_deref().unwrap_or(Path::new(".")));
+            if !dir.is_dir() {
+                return Err(Error::NotADirectory(dir));
+            }
+            tui::run(args, &config, &dir)
+        }
+        Some(Command::Pipeline { name }) => run_pipeline(args, &config, name),
+        Some(Command::Clip) => match clipboard::read()? {
+            Copied::Files(files) => {
+                let mut copied = args.clone();
+                copied.command = None;
+                copied.files = files;
+                upload_files(&copied, &config, cli::flags(), Vec::new())
             }
+            Copied::Image(copy) => upload_files(
+                &found_files(args, vec![copy.path.clone()]),
+                &config,
+                cli::flags(),
+                Vec::new(),
+            ),
         },
+        Some(Command::WatchClipboard { files }) => {
+            let flags = cli::flags();
+            clipboard::watch(*files, |paths| {
+                upload_files(
+                    &found_files(args, paths),
+                    &config,
+                    flags.clone(),
+                    Vec::new(),
+                )
+            })
+        }
+        Some(Command::Watch { dir, debounce }) => watch_dir(args, &config, dir, *debounce),
+        Some(Command::Resume) => resume(),
+        Some(Command::RetryFailed) => retry_failed(),
+        None if args.files.is_empty() => {
+            let dir = env::current_dir().map_err(Error::CurrentDir)?;
+            let Some(files) = picker::pick(&dir, &filter(args))? else {
+                return Ok(Exit::Cancelled);
+            };
+            upload_picked(args, &config, files, cli::flags())
+        }
+        None if args.profile.is_none() && !config.routes.is_empty() => route(args, &config),
+        None => upload_files(args, &config, cli::flags(), Vec::new()),
     };
 
-    // I wish to turn this into a 1 liner
-    let file_str: Vec<String> = env::args().collect();
-    let file: &Path = Path::new(&file_str[1]);
+    if let Some(update) = update {
+        update::notify(update);
+    }
+    result
+}
 
-    let executed_path = match env::current_dir() {
-        Ok(dir) => dir,
-        Err(err) => {
-            eprintln!("Failed to get executed directory!\n{}", err);
-            return;
-        }
-    };
-
-    let target_file = executed_path.join(file);
-
-    let headers = construct_headers(&config);
-    match upload_file(&target_file, config, headers) {
-        UploadResult::Success(res) => {
-            let json = res.text().unwrap();
-            let mut urls: JSONResponse =
-                serde_json::from_str(&json).expect("Failed to deserialise JSON response");
-
-            if urls.files.len() == 1 {
-                let url: String = urls.files.pop().unwrap().to_string();
-                println!("Uploaded URL: {}", url);
-                match set_clipboard(formats::Unicode, url) {
-                    Ok(_) => println!("Copied URL to clipboard!"),
-                    Err(err) => {
-                        eprintln!(
-                            "Something went wrong while copying URL to clipboard: {}",
-                            err
-                        );
-                        return;
+/// Uploads a single file and reports on it, collecting what goes on the clipboard
+fn upload_file(
+    file: &Path,
+    config: &Configuration,
+    ctx: &Context,
+    clipboard: &mut Vec<String>,
+) -> FileReport {
+    if let Some(meter) = &ctx.meter {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let size = fs::metadata(file)
+            .map(|meta| meta.len())
+            .unwrap_or_default();
+        meter.start_file(&name, size);
+    }
+    let report = process_file(file, config, ctx);
+    if let Some(meter) = &ctx.meter {
+        meter.finish_file();
+    }
+
+    if ctx.print == Print::Json {
+        match serde_json::to_string(&report) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Failed to serialise the report: {}", err),
+        }
+    }
+    remember(&report, config, ctx);
+    if let Some(text) = present(&report, ctx) {
+        clipboard.push(text);
+    }
+    post_upload(&report, config, ctx);
+    report
+}
+
+/// Keeps the upload in the history along with a thumbnail of it
+fn remember(report: &FileReport, config: &Configuration, ctx: &Context) {
+    if let (Some(Ok(conn)), false) = (ctx.history.as_ref().map(Mutex::lock), report.reused) {
+        history::record(&conn, report, ctx.client.host());
+    }
+    if let (true, FileStatus::Uploaded, Some(hash)) =
+        (config.thumbnails, report.status, &report.hash)
+    {
+        thumbnail::generate(&report.path, hash);
+    }
+}
+
+fn post_upload(report: &FileReport, config: &Configuration, ctx: &Context) {
+    if let (FileStatus::Uploaded, Some(hook)) = (report.status, &config.post_upload_hook) {
+        hooks::post_upload(
+            hook,
+            report,
+            &template_values(hook, report, ctx.client.host()),
+        );
+    }
+}
+
+/// The files with the same contents as one before them, by index along with the index of that one
+///
+/// Only files of the same size get hashed, most batches have nothing to read twice
+fn duplicates(files: &[PathBuf]) -> HashMap<usize, usize> {
+    let sizes: Vec<Option<u64>> = files
+        .iter()
+        .map(|file| fs::metadata(file).ok().map(|meta| meta.len()))
+        .collect();
+    let mut first = HashMap::new();
+    let mut duplicates = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        let Some(size) = sizes[i] else {
+            continue;
+        };
+        if sizes.iter().filter(|other| **other == Some(size)).count() < 2 {
+            continue;
+        }
+        match hash::hash_file(file) {
+            Ok(hash) => match first.get(&hash) {
+                Some(&original) => {
+                    duplicates.insert(i, original);
+                }
+                None => {
+                    first.insert(hash, i);
+                }
+            },
+            Err(err) => warn!("Failed to hash {}: {}", file.display(), err),
+        }
+    }
+    duplicates
+}
+
+/// The report of a file that wasn't uploaded since `original` has the same contents
+fn duplicate(file: &Path, original: &FileReport) -> FileReport {
+    let mut report = FileReport::new(file);
+    report.duplicate_of = Some(original.path.clone());
+    if original.status != FileStatus::Uploaded {
+        let reason = format!(
+            "Not uploading {}, it has the same contents as {} which failed",
+            file.display(),
+            original.path.display()
+        );
+        return report.fail(original.failure.unwrap_or(Exit::Failure), reason);
+    }
+    report.status = FileStatus::Uploaded;
+    report.reused = true;
+    report.url = original.url.clone();
+    report.deletion_url = original.deletion_url.clone();
+    report.hash = original.hash.clone();
+    report.sha256 = original.sha256.clone();
+    report.signature_url = original.signature_url.clone();
+    report
+}
+
+/// Queues the files that couldn't reach the host for `--queue-offline`
+///
+/// Parts are temporary, so what gets queued is the files they were cut from
+fn queue_offline(
+    args: &Args,
+    flags: Vec<String>,
+    originals: &[PathBuf],
+    reports: &[FileReport],
+    splits: &[split::Split],
+) {
+    let offline: Vec<&Path> = reports
+        .iter()
+        .filter(|report| report.failure == Some(Exit::Network))
+        .map(|report| report.path.as_path())
+        .collect();
+    if offline.is_empty() {
+        return;
+    }
+    if args.zip_password.is_some() {
+        warn!("Not queueing the archive, its password isn't written to disk");
+        return;
+    }
+
+    let queued = originals
+        .iter()
+        .filter(|&original| {
+            offline.iter().any(|&path| {
+                path == original
+                    || splits
+                        .iter()
+                        .any(|split| &split.original == original && split.contains(path))
+            })
+        })
+        .cloned()
+        .collect();
+    offline::queue(queued, flags);
+}
+
+/// Uploads what was left of the last batch when uppy or the machine died halfway through
+fn resume() -> Result<Exit, Error> {
+    let batch = batch::last()
+        .map_err(Error::Batch)?
+        .filter(|batch| !batch.finished)
+        .ok_or(Error::NothingToResume)?;
+    println!(
+        "{}",
+        t!(
+            "resuming",
+            date = history::date(batch.started_at),
+            count = batch.done().len()
+        )
+    );
+    rerun(&batch, batch.unfinished())
+}
+
+/// Uploads the files of the last batch that failed, or never got their turn when it was cut short
+fn retry_failed() -> Result<Exit, Error> {
+    let batch = batch::last().map_err(Error::Batch)?;
+    let files = batch
+        .as_ref()
+        .map(|batch| batch.with_status(&[Status::Failed, Status::Pending, Status::InProgress]))
+        .unwrap_or_default();
+    let Some(batch) = batch.filter(|_| !files.is_empty()) else {
+        return Err(Error::NothingToRetry);
+    };
+    println!(
+        "{}",
+        t!(
+            "retrying",
+            count = files.len(),
+            date = history::date(batch.started_at)
+        )
+    );
+    rerun(&batch, files)
+}
+
+/// Uploads `files` with the flags the batch was started with, keeping the files it already uploaded
+fn rerun(batch: &Batch, files: Vec<PathBuf>) -> Result<Exit, Error> {
+    let args = Args::try_parse_from(
+        iter::once(OsString::from("uppy"))
+            .chain(batch.flags.iter().map(OsString::from))
+            .chain(files.into_iter().map(OsString::from)),
+    )
+    .map_err(Error::BatchArgs)?;
+    let config: Configuration = read_config(args.profile.as_deref())?;
+    upload_files(&args, &config, batch.flags.clone(), batch.done())
+}
+
+/// `args` for uploading files that didn't come from the command line, which are never cleaned up
+fn found_files(args: &Args, files: Vec<PathBuf>) -> Args {
+    let mut found = args.clone();
+    found.command = None;
+    found.files = files;
+    found.delete = false;
+    found.yes = false;
+    found.keep = true;
+    found
+}
+
+/// Captures what the pipeline called `name` captures and uploads it with the pipeline's args
+fn run_pipeline(args: &Args, config: &Configuration, name: &str) -> Result<Exit, Error> {
+    let pipeline = config
+        .pipelines
+        .get(name)
+        .ok_or_else(|| Error::UnknownPipeline(name.to_string()))?;
+    let copied = match pipeline.capture {
+        pipeline::Capture::Region => Copied::Image(clipboard::capture_region()?),
+        pipeline::Capture::Clipboard => clipboard::read()?,
+    };
+
+    // The profile the pipeline came from is used for its upload too
+    let mut flags: Vec<String> = args
+        .profile
+        .iter()
+        .flat_map(|profile| ["--profile".to_string(), profile.clone()])
+        .collect();
+    flags.extend(pipeline.args.iter().cloned());
+    // The captured image stays in the temp directory until the upload is done
+    let (files, copy) = match copied {
+        Copied::Files(files) => (files, None),
+        Copied::Image(copy) => (vec![copy.path.clone()], Some(copy)),
+    };
+    let mut piped = Args::try_parse_from(
+        iter::once(OsString::from("uppy"))
+            .chain(flags.iter().map(OsString::from))
+            .chain(files.iter().map(OsString::from)),
+    )
+    .map_err(Error::PipelineArgs)?;
+    if copy.is_some() {
+        piped = found_files(&piped, files);
+    }
+    upload_files(&piped, config, flags, Vec::new())
+}
+
+/// Downloads `url` and uploads it with the flags that came before `fetch`
+fn fetch(args: &Args, config: &Configuration, url: &str) -> Result<Exit, Error> {
+    let quiet = args.json || args.quiet || args.print != Print::Status;
+    let meter = (!quiet && io::stderr().is_terminal()).then(Meter::default);
+    let copy = fetch::fetch(url, meter.as_ref())?;
+
+    // The download is removed along with the temp directory either way
+    let fetched = found_files(args, vec![copy.path.clone()]);
+    upload_files(&fetched, config, cli::flags(), Vec::new())
+}
+
+/// Uploads the files of `dir` that changed since the last sync with the flags that came before
+/// `sync`, then writes where each file of it went to the map
+fn sync(
+    args: &Args,
+    config: &Configuration,
+    dir: &Path,
+    map: Option<&Path>,
+    verify: bool,
+) -> Result<Exit, Error> {
+    let executed_path = env::current_dir().map_err(Error::CurrentDir)?;
+    let synced = executed_path.join(dir);
+    if !synced.is_dir() {
+        return Err(Error::NotADirectory(synced));
+    }
+    let map_path = match map {
+        Some(map) => executed_path.join(map),
+        None => synced.join(sync::MAP_NAME),
+    };
+    let mut mapped = sync::read(&map_path)?;
+
+    let client = client(args, config)?;
+    let files: Vec<PathBuf> = walk::expand(vec![synced.clone()], &filter(args))?
+        .into_iter()
+        .filter(|file| winpath::extended(file) != winpath::extended(&map_path))
+        .collect();
+    // Left out where uploading wouldn't reuse earlier uploads either
+    let encrypts = args.encrypt || !args.age_recipient.is_empty() || !args.gpg_recipient.is_empty();
+    let conn = match config.history && args.replay.is_none() && !args.anonymous && !encrypts {
+        true => history::open()
+            .map_err(|err| warn!("Failed to open the upload history: {}", err))
+            .ok(),
+        false => None,
+    };
+    let changed = match args.force {
+        true => files.clone(),
+        false => sync::changed(
+            &mut mapped,
+            &synced,
+            &files,
+            client.host(),
+            conn.as_ref(),
+            verify.then_some(&client),
+        ),
+    };
+
+    let (exit, reports) = match changed.is_empty() {
+        true => (Exit::Success, Vec::new()),
+        false => upload_batch(
+            &found_files(args, changed.clone()),
+            config,
+            cli::flags(),
+            Vec::new(),
+            &mut Vec::new(),
+        )?,
+    };
+    sync::update(&mut mapped, &synced, &files, &reports, client.host());
+    sync::write(&map_path, &mapped)?;
+
+    if !args.json && !args.quiet && args.print == Print::Status {
+        let uploaded = reports
+            .iter()
+            .filter(|report| report.status == FileStatus::Uploaded)
+            .count();
+        println!(
+            "{}",
+            style::dim(&t!(
+                "synced",
+                count = uploaded,
+                unchanged = files.len() - changed.len(),
+                path = winpath::plain(&map_path).display().to_string()
+            ))
+        );
+    }
+    Ok(exit)
+}
+
+/// Uploads the files that show up in `dir` with the flags that came before `watch`
+fn watch_dir(
+    args: &Args,
+    config: &Configuration,
+    dir: &Path,
+    debounce: Option<u64>,
+) -> Result<Exit, Error> {
+    let flags = cli::flags();
+
+    let mut filter = filter(args);
+    filter.exclude.extend(match &config.watch_ignore {
+        Some(globs) => globs.clone(),
+        None => watch::PARTIAL_FILES.map(str::to_string).to_vec(),
+    });
+    let debounce = debounce
+        .or(config.watch_debounce_ms)
+        .unwrap_or(watch::DEFAULT_DEBOUNCE_MS);
+    let dir = env::current_dir().map_err(Error::CurrentDir)?.join(dir);
+    watch::watch(&dir, &filter, Duration::from_millis(debounce), |paths| {
+        upload_files(&found_files(args, paths), config, flags.clone(), Vec::new())
+    })
+}
+
+/// Uploads the files picked in the console like they were given on the command line
+fn upload_picked(
+    args: &Args,
+    config: &Configuration,
+    files: Vec<PathBuf>,
+    flags: Vec<String>,
+) -> Result<Exit, Error> {
+    let mut picked = args.clone();
+    picked.command = None;
+    picked.files = files;
+    match args.profile.is_none() && !config.routes.is_empty() {
+        true => route(&picked, config),
+        false => upload_files(&picked, config, flags, Vec::new()),
+    }
+}
+
+/// Uploads each file to the profile `routes` picks for its extension, anything else to the top level host
+///
+/// Archives are a single upload and directories are only expanded later, so both go to the top level host
+fn route(args: &Args, config: &Configuration) -> Result<Exit, Error> {
+    let mut groups: Vec<(Option<&str>, Vec<PathBuf>)> = vec![(None, Vec::new())];
+    for file in &args.files {
+        let routed = file
+            .extension()
+            .filter(|_| args.archive.is_none() && args.zip_password.is_none() && !file.is_dir())
+            .and_then(|extension| {
+                let extension = extension.to_string_lossy();
+                config.routes.iter().find_map(|(routed, profile)| {
+                    routed
+                        .trim_start_matches('.')
+                        .eq_ignore_ascii_case(&extension)
+                        .then_some(profile.as_str())
+                })
+            });
+        match groups.iter_mut().find(|(profile, _)| *profile == routed) {
+            Some((_, files)) => files.push(file.clone()),
+            None => groups.push((routed, vec![file.clone()])),
+        }
+    }
+
+    // One clipboard for every host, each run puts the URLs of the ones before it back
+    let mut clipboard = Vec::new();
+    let mut exits = Vec::new();
+    for (profile, files) in groups.into_iter().filter(|(_, files)| !files.is_empty()) {
+        let mut routed = args.clone();
+        routed.profile = profile.map(str::to_string);
+        let mut flags = cli::flags();
+        if let Some(profile) = profile {
+            debug!(files = ?files, "Routing to {}", profile);
+            flags.extend(["--profile".to_string(), profile.to_string()]);
+        }
+        routed.files = files;
+        let exit = match profile {
+            Some(profile) => {
+                let config: Configuration = read_config(Some(profile))?;
+                upload_batch(&routed, &config, flags, Vec::new(), &mut clipboard)?.0
+            }
+            None => upload_batch(&routed, config, flags, Vec::new(), &mut clipboard)?.0,
+        };
+        exits.push((exit != Exit::Success).then_some(exit));
+    }
+    Ok(Exit::for_batch(&exits))
+}
+
+/// Uploads the files of a run, `flags` and `done` carrying over a batch that is resumed or retried
+fn upload_files(
+    args: &Args,
+    config: &Configuration,
+    flags: Vec<String>,
+    done: Vec<batch::Entry>,
+) -> Result<Exit, Error> {
+    Ok(upload_batch(args, config, flags, done, &mut Vec::new())?.0)
+}
+
+/// Which files of `args` are uploaded out of the directories given
+fn filter(args: &Args) -> walk::Filter {
+    walk::Filter {
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+        symlinks: match (args.follow_symlinks, args.no_follow_symlinks) {
+            (true, _) => Symlinks::Follow,
+            (_, true) => Symlinks::Skip,
+            _ => Symlinks::Given,
+        },
+    }
+}
+
+/// The settings every file of a batch is uploaded with, sent through `client`
+fn context(
+    args: &Args,
+    config: &Configuration,
+    print: Print,
+    client: Client,
+    meter: Option<Arc<Meter>>,
+) -> Result<Context, Error> {
+    Ok(Context {
+        copy_format: args.copy_format.unwrap_or(config.copy_format),
+        output: args.output.clone().or(config.output.clone()),
+        print,
+        cleanup: args.cleanup().unwrap_or(config.cleanup),
+        removal: args.removal(config.shred_passes),
+        client,
+        // Replayed uploads never happened so they stay out of the history, anonymous ones
+        // shouldn't be traceable back to the file
+        history: match config.history && args.replay.is_none() && !args.anonymous {
+            true => history::open()
+                .map(Mutex::new)
+                .map_err(|err| warn!("Failed to open the upload history: {}", err))
+                .ok(),
+            false => None,
+        },
+        force: args.force,
+        transform: Transform {
+            conversion: args.convert,
+            max_dim: args.max_dim,
+        },
+        optimize: args.optimize,
+        strip_metadata: args.anonymous || args.strip_metadata || config.strip_metadata,
+        sanitize_names: config.sanitize_names,
+        randomize_name: args.anonymous || args.randomize_name || config.randomize_names,
+        ocr: args.ocr,
+        rate_limit: config.rate_limit.clone(),
+        audit: config.audit_log,
+        manifest: args.manifest.then(|| args.note.clone()),
+        signing_key: match args.sign {
+            true => Some(minisign::SigningKey::load(
+                &config
+                    .minisign_key
+                    .clone()
+                    .unwrap_or_else(minisign::default_key_path),
+            )?),
+            false => None,
+        },
+        encrypt: args.encrypt,
+        age_recipients: args.age_recipient.clone(),
+        gpg_recipients: args.gpg_recipient.clone(),
+        meter,
+    })
+}
+
+/// [`upload_files`] adding to `clipboard`, which is copied along with the URLs of this run, and
+/// handing back the reports of the files
+fn upload_batch(
+    args: &Args,
+    config: &Configuration,
+    flags: Vec<String>,
+    done: Vec<batch::Entry>,
+    clipboard: &mut Vec<String>,
+) -> Result<(Exit, Vec<FileReport>), Error> {
+    debug!(profile = ?args.profile, files = ?args.files, "Starting");
+
+    let executed_path = env::current_dir().map_err(Error::CurrentDir)?;
+
+    let print = match args.print {
+        _ if args.json => Print::Json,
+        Print::Status if args.quiet => Print::Url,
+        print => print,
+    };
+    // A single status line can't follow several uploads at once
+    let meter = (print == Print::Status && args.jobs == 1 && io::stderr().is_terminal())
+        .then(Arc::<Meter>::default);
+    cancel::install();
+    let _status = status::wanted(config.publish_status).then(status::announce);
+    let mut client = client(args, config)?.with_cancel(cancel::flag());
+    client = match &meter {
+        Some(meter) => {
+            let meter = Arc::clone(meter);
+            client.with_progress(move |progress| {
+                meter.update(progress);
+                status::progress(progress);
+            })
+        }
+        None => client.with_progress(status::progress),
+    };
+
+    let ctx = context(args, config, print, client, meter)?;
+
+    let roots: Vec<PathBuf> = args
+        .files
+        .iter()
+        .map(|file| executed_path.join(file))
+        .collect();
+    let mut files = walk::expand(roots.clone(), &filter(args))?;
+    if files.is_empty() {
+        warn!("Nothing left to upload after filtering");
+    }
+    let originals = files.clone();
+    let mut batch = Batch::start(flags.clone(), &originals, done.clone());
+    // Only the archive is uploaded, it is removed again once dropped at the end
+    let archive = match (args.archive, &args.zip_password) {
+        (None, None) => None,
+        (format, password) => {
+            let format = format.flatten().unwrap_or(config.archive_format);
+            if password.is_some() && format == archive::ArchiveFormat::TarZst {
+                return Err(Error::TarPassword);
+            }
+            let password = match password {
+                Some(password) if password.is_empty() => Some(archive::prompt_password()?),
+                password => password.clone(),
+            };
+            let packing = archive::Packing {
+                format,
+                zip_level: args.zip_level.or(config.zip_level),
+                zstd_level: args
+                    .zstd_level
+                    .or(config.zstd_level)
+                    .unwrap_or(archive::DEFAULT_ZSTD_LEVEL),
+                threads: args
+                    .threads
+                    .map(usize::from)
+                    .or(config.archive_threads)
+                    .unwrap_or(1),
+            };
+            let archive = archive::pack(&files, &roots, &packing, password.as_deref())
+                .map_err(Error::Archive)?;
+            files = vec![archive.path.clone()];
+            Some(archive)
+        }
+    };
+
+    // Oversized files are uploaded as parts followed by a manifest for `uppy join`
+    let mut splits = Vec::new();
+    if let Some(limit) = args.split {
+        let mut uploads = Vec::new();
+        for file in files {
+            match fs::metadata(&file) {
+                Ok(meta) if meta.len() > limit => {
+                    let split = split::split(&file, limit).map_err(Error::Split)?;
+                    uploads.extend(split.parts.iter().cloned());
+                    splits.push(split);
+                }
+                _ => uploads.push(file),
+            }
+        }
+        files = uploads;
+    }
+
+    let mut reports = Vec::new();
+    // Uploaded by the run that started the batch, listed again so the summary covers all of it
+    for entry in done {
+        let mut report = FileReport::new(&entry.path);
+        report.status = FileStatus::Uploaded;
+        report.reused = true;
+        report.url = entry.url;
+        if ctx.print == Print::Json {
+            match serde_json::to_string(&report) {
+                Ok(json) => println!("{}", json),
+                Err(err) => error!("Failed to serialise the report: {}", err),
+            }
+        }
+        if let Some(text) = present(&report, &ctx) {
+            clipboard.push(text);
+        }
+        reports.push(report);
+    }
+    // What each upload stands for in the batch, the files archives and parts were made from
+    let made_from = |file: &PathBuf| -> Vec<PathBuf> {
+        if archive.is_some() {
+            return originals.clone();
+        }
+        match splits.iter().find(|split| split.contains(file)) {
+            Some(split) => vec![split.original.clone()],
+            None => vec![file.clone()],
+        }
+    };
+    // Only the first of the files with the same contents is uploaded, unless --force
+    let duplicates = match ctx.force || files.len() < 2 {
+        true => HashMap::new(),
+        false => duplicates(&files),
+    };
+    if let Some(meter) = &ctx.meter {
+        let uploads = files
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !duplicates.contains_key(i));
+        let size = uploads
+            .clone()
+            .filter_map(|(_, file)| fs::metadata(file).ok())
+            .map(|meta| meta.len())
+            .sum();
+        meter.start_batch(uploads.count(), size);
+    }
+    let mut queue: Vec<usize> = (0..files.len())
+        .filter(|i| !duplicates.contains_key(i))
+        .collect();
+    if args.order != Order::AsGiven {
+        let sizes: Vec<u64> = files
+            .iter()
+            .map(|file| fs::metadata(file).map_or(0, |meta| meta.len()))
+            .collect();
+        queue.sort_by_key(|&i| sizes[i]);
+        if args.order == Order::LargestFirst {
+            queue.reverse();
+        }
+    }
+    // Up to `--jobs` files are uploaded at once, the batch is only touched from this thread
+    let mut uploaded: Vec<Option<(FileReport, Vec<String>)>> = files.iter().map(|_| None).collect();
+    let (events, received) = mpsc::channel();
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..(args.jobs as usize).min(queue.len()) {
+            let (events, next, files, ctx) = (events.clone(), &next, &files, &ctx);
+            let queue = &queue;
+            scope.spawn(move || {
+                while let Some(&i) = queue.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    // The files that weren't started are left pending for `uppy resume`
+                    if cancel::requested() {
+                        break;
+                    }
+                    let _ = events.send(Upload::Started(i));
+                    let mut texts = Vec::new();
+                    let report = upload_file(&files[i], config, ctx, &mut texts);
+                    let _ = events.send(Upload::Finished(i, Box::new(report), texts));
+                }
+            });
+        }
+        drop(events);
+
+        for event in received {
+            match event {
+                Upload::Started(i) => {
+                    for original in made_from(&files[i]) {
+                        batch.start_file(&original);
+                    }
+                }
+                Upload::Finished(i, report, texts) => {
+                    // Files cut into parts are done once their manifest is uploaded, cancelled
+                    // ones stay in progress so they are uploaded again
+                    let part = splits.iter().any(|split| split.parts.contains(&files[i]));
+                    let cancelled = report.failure == Some(Exit::Cancelled);
+                    if !cancelled && (!part || report.url.is_none()) {
+                        for original in made_from(&files[i]) {
+                            batch.finish_file(&original, report.url.clone());
+                        }
                     }
+                    uploaded[i] = Some((*report, texts));
                 }
             }
         }
-        UploadResult::IOError(err) => {
-            println!(
-                "Something went wrong while loading the targeted file: {}",
-                err
-            );
-            return;
+    });
+    let mut sorted = duplicates.iter().collect::<Vec<_>>();
+    sorted.sort();
+    for (&i, &original) in sorted {
+        let Some((original, _)) = &uploaded[original] else {
+            continue;
+        };
+        let report = duplicate(&files[i], original);
+        debug!(
+            "{} has the same contents as {}",
+            files[i].display(),
+            original.path.display()
+        );
+        if ctx.print == Print::Json {
+            match serde_json::to_string(&report) {
+                Ok(json) => println!("{}", json),
+                Err(err) => error!("Failed to serialise the report: {}", err),
+            }
         }
-        UploadResult::ReqwestError(err) => {
-            println!(
-                "Something went wrong while sending the HTTP request: {}",
-                err
+        let texts = present(&report, &ctx).into_iter().collect();
+        for file in made_from(&files[i]) {
+            batch.finish_file(&file, report.url.clone());
+        }
+        uploaded[i] = Some((report, texts));
+    }
+    if !duplicates.is_empty() && ctx.print == Print::Status {
+        println!(
+            "{}",
+            style::dim(&t!("duplicates", count = duplicates.len()))
+        );
+    }
+    // Back in the order the files were given, however they finished
+    for (report, texts) in uploaded.into_iter().flatten() {
+        reports.push(report);
+        clipboard.extend(texts);
+    }
+    for split in splits.iter().filter(|_| !cancel::requested()) {
+        let urls: Option<Vec<String>> = split
+            .parts
+            .iter()
+            .map(|part| {
+                reports
+                    .iter()
+                    .find(|report| &report.path == part)
+                    .and_then(|report| report.url.clone())
+            })
+            .collect();
+        let Some(urls) = urls else {
+            error!(
+                "Not every part of {} uploaded, skipping its manifest",
+                split.original.display()
             );
-            return;
+            continue;
+        };
+        match split.write_manifest(urls) {
+            Ok(manifest) => {
+                let report = upload_file(&manifest, config, &ctx, clipboard);
+                batch.finish_file(&split.original, report.url.clone());
+                reports.push(report);
+            }
+            Err(err) => error!("Failed to write the manifest: {}", err),
         }
-        UploadResult::HTTPClientError(code) => {
-            println!("A HTTP client error occurred, code: {}", code);
-            return;
+    }
+
+    if !args.no_clipboard && !clipboard.is_empty() {
+        match set_clipboard(formats::Unicode, clipboard.join("\n")) {
+            Ok(_) if ctx.print != Print::Status => (),
+            Ok(_) => println!("{}", style::dim(&t!("copied"))),
+            Err(err) => error!(
+                "Something went wrong while copying URL to clipboard: {}",
+                err
+            ),
         }
-        UploadResult::HTTPServerError(code) => {
-            println!("A HTTP server error occured, code: {}", code);
-            return;
+    }
+
+    if args.toast && !clipboard.is_empty() {
+        notify::desktop(&clipboard.join("\n"));
+    }
+
+    if let Some(webhook) = &config.notify_webhook {
+        notify::notify(webhook, &reports, !args.anonymous);
+    }
+
+    if args.queue_offline {
+        queue_offline(args, flags, &originals, &reports, &splits);
+    }
+
+    let failures: Vec<Option<Exit>> = reports.iter().map(|report| report.failure).collect();
+    for report in &reports {
+        // Archives, parts and manifests are temporary, the originals weren't uploaded themselves
+        let original = originals.contains(&report.path);
+        if report.status == FileStatus::Uploaded && archive.is_none() && original {
+            cleanup::file_cleanup(&report.path, ctx.cleanup, ctx.removal, ctx.print);
         }
     }
-    file_cleanup(target_file)
+
+    // Left unfinished, so `uppy resume` picks up the rest
+    if cancel::requested() {
+        let done = reports
+            .iter()
+            .filter(|report| report.status == FileStatus::Uploaded)
+            .count();
+        println!(
+            "{}",
+            t!(
+                "cancelled",
+                done = done,
+                count = reports.len().max(originals.len())
+            )
+        );
+        return Ok((Exit::Cancelled, reports));
+    }
+    batch.finish();
+    Ok((Exit::for_batch(&failures), reports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_rewrite_replaces_the_prefix_only() {
+        let rewrite = UrlRewrite {
+            from: "http://zipline:3000".to_string(),
+            to: "https://files.example.com".to_string(),
+        };
+
+        assert_eq!(
+            rewrite.apply("http://zipline:3000/u/abc.png".to_string()),
+            "https://files.example.com/u/abc.png"
+        );
+        assert_eq!(
+            rewrite.apply("https://other.example/http://zipline:3000/u/abc.png".to_string()),
+            "https://other.example/http://zipline:3000/u/abc.png"
+        );
+    }
 }
@@ -0,0 +1,292 @@
+use clap::CommandFactory;
+use std::io::{self, Write};
+
+use crate::cli::Args;
+
+/// Every key `config.json` understands, keep in sync with `Configuration`
+const CONFIG_KEYS: [(&str, &str); 51] = [
+    (
+        "host",
+        "Base URL of the Zipline instance, e.g. https://files.example.com",
+    ),
+    ("token", "API token sent in the Authorization header"),
+    (
+        "tokens",
+        "More tokens tried in turn when the host refuses one or rate limits it",
+    ),
+    (
+        "oauth",
+        "{ token_url, client_id, client_secret, refresh_token } for hosts behind OAuth2",
+    ),
+    (
+        "response_url_path",
+        "Where the URL is in the answer of hosts that don't answer like Zipline, e.g. $.data.link",
+    ),
+    (
+        "response_url_regex",
+        "Regex cutting the URL out of the answer, the first group when it has one",
+    ),
+    (
+        "response_deletion_url_path",
+        "Where the deletion URL is in the answer, like response_url_path",
+    ),
+    (
+        "headers",
+        "Headers sent with every request to the host, an empty value drops a default one",
+    ),
+    (
+        "pinned_sha256",
+        "SHA-256 of the public key the certificate of the host has to carry",
+    ),
+    (
+        "resolve",
+        "Addresses to connect to in place of looking domains up, like curl's --resolve",
+    ),
+    (
+        "local_address",
+        "Address or name of the network interface uploads go out from, like --interface",
+    ),
+    (
+        "lookup_url",
+        "URL that finds an upload by {sha256}, files the host already has aren't uploaded again",
+    ),
+    (
+        "lookup_url_path",
+        "Where the URL is in the answer of lookup_url, like response_url_path",
+    ),
+    (
+        "chunk_size",
+        "Bytes above which files go up in chunks of that size, carrying on where a dropped upload stopped",
+    ),
+    (
+        "zipline_version",
+        "Major version of Zipline on the host, 3 or 4, asked of the host on the first upload when unset",
+    ),
+    (
+        "filename_encoding",
+        "utf8 to send file names as they are, percent to percent encode them",
+    ),
+    (
+        "profiles",
+        "Named sets of keys applied over the top level ones with --profile",
+    ),
+    (
+        "routes",
+        "Extensions and the profile their files go to when no --profile is given",
+    ),
+    (
+        "copy_format",
+        "plain, markdown, bbcode or html, what gets copied to the clipboard",
+    ),
+    (
+        "output",
+        "Template for stdout and the clipboard, overrides copy_format",
+    ),
+    (
+        "log_file",
+        "Write debug logs to .config\\uppy\\logs, false by default",
+    ),
+    (
+        "cleanup",
+        "ask, delete or keep, what happens to files after uploading",
+    ),
+    (
+        "shred_passes",
+        "How many times --shred overwrites a file, 3 by default",
+    ),
+    (
+        "gc_after_days",
+        "Purge files older versions left in the temp directory after this many days",
+    ),
+    (
+        "language",
+        "Language of the messages, en or de, instead of the one Windows is set to",
+    ),
+    (
+        "pre_upload_hook",
+        "Command run before each upload, a non-zero exit skips the file",
+    ),
+    (
+        "malware_scan",
+        "defender, or a command such as \"clamdscan --no-summary {path}\", a non-zero exit skips the file",
+    ),
+    (
+        "post_upload_hook",
+        "Command run after each upload with the JSON report on stdin",
+    ),
+    (
+        "notify_webhook",
+        "Discord or Slack compatible webhook told about every batch",
+    ),
+    (
+        "update_check_days",
+        "Look for a new release this often and mention it after the command, off by default",
+    ),
+    (
+        "strip_metadata",
+        "Remove EXIF, GPS and XMP data from images before uploading, false by default",
+    ),
+    (
+        "sanitize_names",
+        "Replace spaces, control characters and characters URLs choke on in uploaded names, true by default",
+    ),
+    (
+        "randomize_names",
+        "Upload every file under a random name that keeps the extension, false by default",
+    ),
+    (
+        "blocked_extensions",
+        "Extensions such as [\"exe\", \"pem\"] that are refused without --force",
+    ),
+    (
+        "allowed_extensions",
+        "When set, only files with these extensions are uploaded without --force",
+    ),
+    (
+        "url_rewrite",
+        "{ \"from\": origin, \"to\": CDN domain } swapping the start of returned URLs",
+    ),
+    (
+        "rate_limit",
+        "Most uploads to the host in a window, such as \"10/minute\", later ones wait their turn",
+    ),
+    (
+        "max_size",
+        "Largest file the host takes, such as \"95MB\", bigger ones are refused before uploading",
+    ),
+    (
+        "pipelines",
+        "Named captures and the flags to upload them with, run with uppy pipeline <name>",
+    ),
+    (
+        "history",
+        "Record uploads in .config\\uppy\\history.db, true by default",
+    ),
+    (
+        "audit_log",
+        "Append every upload attempt to .config\\uppy\\audit.jsonl, rotated at 10 MB",
+    ),
+    (
+        "publish_status",
+        "List every upload in uppy status, not only those of uppy watch and the queue",
+    ),
+    (
+        "minisign_key",
+        "The minisign secret key --sign signs with, .minisign\\minisign.key in your profile by default",
+    ),
+    (
+        "archive_format",
+        "What --archive packs files into, auto, zip, store or tar.zst",
+    ),
+    (
+        "zstd_level",
+        "Compression level of tar.zst archives, 3 by default",
+    ),
+    (
+        "zip_level",
+        "Deflate level of zip archives from 0 to 9, 6 by default",
+    ),
+    (
+        "archive_threads",
+        "How many threads compress archives, 1 by default",
+    ),
+    (
+        "watch_debounce_ms",
+        "Milliseconds a file uppy watch finds has to stay the same before it is uploaded, 2000 by default",
+    ),
+    (
+        "watch_ignore",
+        "Globs of files uppy watch leaves alone, *.part, *.crdownload, *.download, *.partial and *.tmp by default",
+    ),
+    (
+        "thumbnails",
+        "Cache thumbnails of uploaded images and videos, true by default",
+    ),
+    (
+        "native_host_dirs",
+        "Folders a browser extension may upload files from through uppy native-host, none by default",
+    ),
+];
+
+/// Headers sent with every upload and where their values come from
+const HEADERS: [(&str, &str); 3] = [
+    ("Authorization", "The token from the configuration"),
+    (
+        "Format",
+        "Always RANDOM, x-zipline-format: random for Zipline 4",
+    ),
+    ("Embed", "Always true, left out for Zipline 4"),
+];
+
+const EXIT_CODES: [(&str, &str); 8] = [
+    ("0", "Success"),
+    ("1", "Upload or command failed"),
+    ("2", "Invalid arguments"),
+    ("3", "Configuration missing or invalid"),
+    ("4", "Authentication rejected by the host"),
+    ("5", "Network error"),
+    ("6", "Some files in the batch failed"),
+    ("130", "Stopped with Ctrl+C, or Esc in a picker"),
+];
+
+fn roff_escape(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+fn roff_section(out: &mut impl Write, title: &str, entries: &[(&str, &str)]) -> io::Result<()> {
+    writeln!(out, ".SH {}", title)?;
+    for (name, description) in entries {
+        writeln!(
+            out,
+            ".TP\n\\fB{}\\fR\n{}",
+            roff_escape(name),
+            roff_escape(description)
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the man page to stdout, the subcommands are listed but documented in `--help-all`
+pub fn manpage() -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    clap_mangen::Man::new(Args::command()).render(&mut out)?;
+    roff_section(&mut out, "CONFIGURATION", &CONFIG_KEYS)?;
+    roff_section(&mut out, "HEADERS", &HEADERS)?;
+    roff_section(&mut out, "EXIT STATUS", &EXIT_CODES)?;
+    writeln!(
+        out,
+        ".SH FILES\n%USERPROFILE%\\e.config\\euppy\\econfig.json"
+    )
+}
+
+fn text_section(title: &str, entries: &[(&str, &str)]) {
+    println!("\n{}:", title);
+    let width = entries
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    for (name, description) in entries {
+        println!("  {:width$}  {}", name, description, width = width);
+    }
+}
+
+fn print_help(command: &mut clap::Command) {
+    println!("{}", command.render_long_help());
+    for subcommand in command.get_subcommands_mut() {
+        println!("\n{}", "-".repeat(80));
+        print_help(subcommand);
+    }
+}
+
+/// Prints the long help of every command followed by the configuration keys and headers
+pub fn help_all() {
+    let mut command = Args::command();
+    command.build();
+    print_help(&mut command);
+
+    println!("\n{}", "-".repeat(80));
+    text_section("Configuration keys", &CONFIG_KEYS);
+    text_section("Headers", &HEADERS);
+    text_section("Exit codes", &EXIT_CODES);
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use tracing::debug;
+
+use crate::temp::TempCopy;
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, reason.to_string())
+}
+
+fn u16_be(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Orientation tag of an EXIF block, the only bit of EXIF worth keeping as photos show up sideways without it
+fn exif_orientation(exif: &[u8]) -> Option<u16> {
+    let tiff = exif.strip_prefix(b"Exif\0\0")?;
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |at: usize| {
+        let bytes: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    };
+    let read_u32 = |at: usize| {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    };
+
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| read_u16(entry) == Some(0x0112))
+        .and_then(|entry| read_u16(entry + 8))
+}
+
+/// A minimal APP1 segment holding nothing but the orientation
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+    exif.extend_from_slice(&orientation.to_be_bytes());
+    exif.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(&exif);
+    segment
+}
+
+/// Drops EXIF and XMP (APP1), IPTC (APP13) and comments, the image data is copied untouched
+fn strip_jpeg(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = bytes[..2].to_vec();
+    let mut at = 2;
+    loop {
+        if bytes.get(at) != Some(&0xff) {
+            return Err(invalid("expected a JPEG marker"));
+        }
+        let marker = *bytes.get(at + 1).ok_or_else(|| invalid("truncated JPEG"))?;
+        match marker {
+            // Padding before a marker
+            0xff => at += 1,
+            // Start of scan, everything after it is image data
+            0xda => {
+                out.extend_from_slice(&bytes[at..]);
+                return Ok(out);
+            }
+            0x01 | 0xd0..=0xd8 => {
+                out.extend_from_slice(&bytes[at..at + 2]);
+                at += 2;
+            }
+            _ => {
+                let len = u16_be(bytes, at + 2).ok_or_else(|| invalid("truncated JPEG"))? as usize;
+                if len < 2 {
+                    return Err(invalid("JPEG segment is too short"));
+                }
+                let end = at + 2 + len;
+                let segment = bytes
+                    .get(at..end)
+                    .ok_or_else(|| invalid("truncated JPEG"))?;
+                match marker {
+                    0xe1 => {
+                        if let Some(orientation) = exif_orientation(&segment[4..]) {
+                            out.extend_from_slice(&orientation_segment(orientation));
+                        }
+                    }
+                    0xed | 0xfe => (),
+                    _ => out.extend_from_slice(segment),
+                }
+                at = end;
+            }
+        }
+    }
+}
+
+/// Drops the EXIF, text and timestamp chunks
+fn strip_png(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = bytes[..8].to_vec();
+    let mut at = 8;
+    while at < bytes.len() {
+        let len = u32_be(bytes, at).ok_or_else(|| invalid("truncated PNG"))? as usize;
+        let end = at + 12 + len;
+        let chunk = bytes.get(at..end).ok_or_else(|| invalid("truncated PNG"))?;
+        match &chunk[4..8] {
+            b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" => (),
+            _ => out.extend_from_slice(chunk),
+        }
+        at = end;
+    }
+    Ok(out)
+}
+
+/// Drops the EXIF and XMP chunks and clears their flags in the extended header
+fn strip_webp(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = bytes[..12].to_vec();
+    let mut at = 12;
+    while at < bytes.len() {
+        let len = u32_le(bytes, at + 4).ok_or_else(|| invalid("truncated WebP"))? as usize;
+        // Chunks are padded to an even length
+        let end = (at + 8 + len + len % 2).min(bytes.len());
+        let chunk = bytes
+            .get(at..end)
+            .ok_or_else(|| invalid("truncated WebP"))?;
+        match &chunk[..4] {
+            b"EXIF" | b"XMP " => (),
+            b"VP8X" if chunk.len() > 8 => {
+                let mut chunk = chunk.to_vec();
+                chunk[8] &= !(0x08 | 0x04);
+                out.extend_from_slice(&chunk);
+            }
+            _ => out.extend_from_slice(chunk),
+        }
+        at = end;
+    }
+
+    let riff_len = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    Ok(out)
+}
+
+/// Writes a copy of `file` without EXIF, GPS or XMP data to the temp directory
+///
+/// Returns `None` for files that aren't JPEG, PNG or WebP images, which are uploaded as they are
+pub fn strip(file: &Path) -> io::Result<Option<TempCopy>> {
+    let bytes = fs::read(file)?;
+    let stripped = if bytes.starts_with(&[0xff, 0xd8]) {
+        strip_jpeg(&bytes)?
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        strip_png(&bytes)?
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        strip_webp(&bytes)?
+    } else {
+        debug!("{} isn't an image with metadata to strip", file.display());
+        return Ok(None);
+    };
+    debug!(
+        "Stripped {} bytes of metadata from {}",
+        bytes.len().saturating_sub(stripped.len()),
+        file.display()
+    );
+
+    let name = file.file_name().ok_or_else(|| invalid("no file name"))?;
+    TempCopy::write(name, &stripped).map(Some)
+}
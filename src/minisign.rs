@@ -0,0 +1,243 @@
+//! Signing uploads with a minisign key, for `--sign`, and checking the signatures of updates
+//!
+//! The signatures are the prehashed `ED` kind over the BLAKE2b-512 of the file, which is read as
+//! it is hashed rather than all at once, the kind `minisign -S` makes by default
+
+use base64::prelude::*;
+use blake2::{Blake2b512, Digest};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use crate::error::Error;
+use crate::i18n::t;
+use uppy::clock;
+
+/// Algorithm, KDF and checksum ids, the KDF salt and limits, then the key number, secret key and
+/// checksum that the KDF encrypts
+const SECRET_KEY_LEN: usize = 2 + 2 + 2 + 32 + 8 + 8 + 8 + 64 + 32;
+/// The key number, secret key and checksum, XORed with the output of scrypt
+const ENCRYPTED_LEN: usize = 8 + 64 + 32;
+
+/// The algorithm id, key number and Ed25519 public key
+const PUBLIC_KEY_LEN: usize = 2 + 8 + 32;
+/// The algorithm id, key number and Ed25519 signature
+const SIGNATURE_LEN: usize = 2 + 8 + 64;
+
+/// Where minisign keeps its key when not told otherwise
+pub fn default_key_path() -> PathBuf {
+    match env::var_os("MINISIGN_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir).join("minisign.key"),
+        None => PathBuf::from(env::var_os("USERPROFILE").unwrap_or_default())
+            .join(".minisign")
+            .join("minisign.key"),
+    }
+}
+
+/// The scrypt parameters libsodium derives from the limits stored in the key
+fn scrypt_params(opslimit: u64, memlimit: u64) -> Option<scrypt::Params> {
+    let opslimit = opslimit.max(32768);
+    let r = 8;
+    let log_n = |max_n: u64| {
+        (1..63)
+            .find(|log_n| 1u64 << log_n > max_n / 2)
+            .unwrap_or(63)
+    };
+    let (log_n, p) = match opslimit < memlimit / 32 {
+        true => (log_n(opslimit / (r * 4)), 1),
+        false => {
+            let log_n = log_n(memlimit / (r * 128));
+            (log_n, ((opslimit / 4) >> log_n).min(0x3fff_ffff) / r)
+        }
+    };
+    // The length is only for password hashes, the output is as long as the buffer it goes in
+    scrypt::Params::new(
+        log_n as u8,
+        r as u32,
+        p as u32,
+        scrypt::Params::RECOMMENDED_LEN,
+    )
+    .ok()
+}
+
+/// A minisign secret key, decrypted
+pub struct SigningKey {
+    keynum: [u8; 8],
+    pair: Ed25519KeyPair,
+}
+
+impl SigningKey {
+    /// Reads the key at `path`, asking for its password when it has one
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let invalid = || Error::InvalidSigningKey(path.to_path_buf());
+        let text = fs::read_to_string(path).map_err(|source| Error::SigningKey {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        // An untrusted comment, then the key
+        let encoded = text
+            .lines()
+            .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+            .ok_or_else(invalid)?;
+        let key = BASE64_STANDARD
+            .decode(encoded.trim())
+            .map_err(|_| invalid())?;
+        if key.len() != SECRET_KEY_LEN || &key[..2] != b"Ed" {
+            return Err(invalid());
+        }
+        let (kdf, salt) = (&key[2..4], &key[6..38]);
+        let opslimit = u64::from_le_bytes(key[38..46].try_into().unwrap_or_default());
+        let memlimit = u64::from_le_bytes(key[46..54].try_into().unwrap_or_default());
+        let mut secret = key[54..].to_vec();
+
+        match kdf {
+            // Saved without a password with `minisign -G -W`
+            [0, 0] => (),
+            b"Sc" => {
+                let password = rpassword::prompt_password(format!(
+                    "{} ",
+                    t!("minisign-password", path = path.display().to_string())
+                ))
+                .map_err(Error::Password)?;
+                let params = scrypt_params(opslimit, memlimit).ok_or_else(invalid)?;
+                let mut stream = [0u8; ENCRYPTED_LEN];
+                scrypt::scrypt(password.as_bytes(), salt, &params, &mut stream)
+                    .map_err(|_| invalid())?;
+                for (byte, mask) in secret.iter_mut().zip(stream) {
+                    *byte ^= mask;
+                }
+            }
+            _ => return Err(invalid()),
+        }
+
+        // The checksum is BLAKE2b, but an Ed25519 secret key ends in its public key, which
+        // only comes out right with the right password
+        let (keynum, secret) = secret.split_at(8);
+        let pair = Ed25519KeyPair::from_seed_unchecked(&secret[..32]).map_err(|_| invalid())?;
+        if pair.public_key().as_ref() != &secret[32..64] {
+            return Err(Error::WrongSigningPassword);
+        }
+        Ok(SigningKey {
+            keynum: keynum.try_into().map_err(|_| invalid())?,
+            pair,
+        })
+    }
+
+    /// Signs `file`, returning the contents of its `.minisig` with `name` in the trusted comment
+    pub fn sign(&self, file: &Path, name: &str) -> io::Result<String> {
+        let mut blake2 = Blake2b512::new();
+        io::copy(&mut File::open(file)?, &mut blake2)?;
+        let signature = self.pair.sign(&blake2.finalize());
+
+        let mut line = b"ED".to_vec();
+        line.extend_from_slice(&self.keynum);
+        line.extend_from_slice(signature.as_ref());
+
+        let timestamp = clock::now().as_secs();
+        // Tabs and line breaks would end the comment early
+        let name: String = name.chars().filter(|c| !c.is_control()).collect();
+        let comment = format!("timestamp:{}\tfile:{}", timestamp, name);
+        // The global signature covers the comment so it can't be swapped out
+        let mut global = signature.as_ref().to_vec();
+        global.extend_from_slice(comment.as_bytes());
+
+        Ok(format!(
+            "untrusted comment: signature from uppy\n{}\ntrusted comment: {}\n{}\n",
+            BASE64_STANDARD.encode(line),
+            comment,
+            BASE64_STANDARD.encode(self.pair.sign(&global))
+        ))
+    }
+}
+
+/// A minisign public key, such as the one releases are signed with
+pub struct PublicKey {
+    keynum: [u8; 8],
+    key: [u8; 32],
+}
+
+impl PublicKey {
+    /// The base64 line `minisign -G` prints, with or without the untrusted comment above it
+    pub fn parse(text: &str) -> Option<Self> {
+        let encoded = text
+            .lines()
+            .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))?;
+        let key = BASE64_STANDARD.decode(encoded.trim()).ok()?;
+        if key.len() != PUBLIC_KEY_LEN || &key[..2] != b"Ed" {
+            return None;
+        }
+        Some(PublicKey {
+            keynum: key[2..10].try_into().ok()?,
+            key: key[10..].try_into().ok()?,
+        })
+    }
+
+    /// Whether `minisig`, the contents of a `.minisig`, is a signature of `data` made with this
+    /// key, prehashed or legacy, and its trusted comment wasn't tampered with
+    pub fn verify(&self, data: &[u8], minisig: &str) -> bool {
+        let mut lines = minisig.lines().skip(1);
+        let (Some(signature), Some(trusted), Some(global)) =
+            (lines.next(), lines.next(), lines.next())
+        else {
+            return false;
+        };
+        let Some(comment) = trusted.strip_prefix("trusted comment: ") else {
+            return false;
+        };
+        let (Ok(signature), Ok(global)) = (
+            BASE64_STANDARD.decode(signature.trim()),
+            BASE64_STANDARD.decode(global.trim()),
+        ) else {
+            return false;
+        };
+        if signature.len() != SIGNATURE_LEN || signature[2..10] != self.keynum {
+            return false;
+        }
+        let prehashed;
+        let signed: &[u8] = match &signature[..2] {
+            b"ED" => {
+                prehashed = Blake2b512::digest(data);
+                &prehashed
+            }
+            b"Ed" => data,
+            _ => return false,
+        };
+        let key = UnparsedPublicKey::new(&ED25519, &self.key);
+        let mut commented = signature[10..].to_vec();
+        commented.extend_from_slice(comment.as_bytes());
+        key.verify(signed, &signature[10..]).is_ok() && key.verify(&commented, &global).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn signatures_verify_against_the_public_key() {
+        let signing = SigningKey {
+            keynum: *b"uppytest",
+            pair: Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap(),
+        };
+        let mut public = b"Ed".to_vec();
+        public.extend_from_slice(&signing.keynum);
+        public.extend_from_slice(signing.pair.public_key().as_ref());
+        let key = PublicKey::parse(&format!(
+            "untrusted comment: minisign public key\n{}\n",
+            BASE64_STANDARD.encode(public)
+        ))
+        .unwrap();
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"uppy.exe").unwrap();
+        let minisig = signing.sign(file.path(), "uppy.exe").unwrap();
+
+        assert!(key.verify(b"uppy.exe", &minisig));
+        assert!(!key.verify(b"something else", &minisig));
+        let swapped = minisig.replace("file:uppy.exe", "file:other.exe");
+        assert!(!key.verify(b"uppy.exe", &swapped));
+    }
+}
@@ -0,0 +1,101 @@
+use rand::distributions::{Alphanumeric, DistString};
+use std::path::Path;
+
+/// Longer names get cut down, some hosts and filesystems refuse anything past 255 bytes
+const MAX_LEN: usize = 128;
+/// Length of the name `--randomize-name` makes up, without the extension
+const RANDOM_LEN: usize = 12;
+
+fn split(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => {
+            (stem, Some(extension))
+        }
+        _ => (name, None),
+    }
+}
+
+/// Characters that break URLs or confuse hosts, such as `#`, `?` and `%`, zero width spaces or
+/// direction overriding characters, and anything that isn't printable
+///
+/// Zero width joiners stay, emoji like 👨‍👩‍👧 and some scripts are written with them
+fn problematic(c: char) -> bool {
+    c.is_control()
+        || c.is_whitespace()
+        || matches!(
+            c,
+            '#' | '?' | '%' | '&' | '+' | '/' | '\\' | '"' | '\'' | '<' | '>' | '|' | '*' | ':'
+        )
+        || matches!(c, '\u{200b}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' | '\u{feff}')
+}
+
+/// Characters that belong to the one before them, combining accents, joiners, variation
+/// selectors and skin tones
+fn combines(c: char) -> bool {
+    matches!(
+        c,
+        '\u{300}'..='\u{36f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{200c}'..='\u{200d}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{fe20}'..='\u{fe2f}'
+            | '\u{1f3fb}'..='\u{1f3ff}'
+            | '\u{e0100}'..='\u{e01ef}'
+    )
+}
+
+/// Replaces problematic characters with `_` and cuts overly long names down, keeping the extension
+pub fn sanitize(name: &str) -> String {
+    let mut clean = String::with_capacity(name.len());
+    for c in name.chars() {
+        match problematic(c) {
+            true if clean.ends_with('_') => (),
+            true => clean.push('_'),
+            false => clean.push(c),
+        }
+    }
+
+    let (stem, extension) = split(&clean);
+    let stem = stem.trim_matches(|c| c == '_' || c == '.');
+    let stem = if stem.is_empty() { "file" } else { stem };
+    let extension = extension
+        .map(|extension| format!(".{}", extension))
+        .unwrap_or_default();
+
+    let room = MAX_LEN.saturating_sub(extension.len()).max(1);
+    let mut end = stem.len().min(room);
+    // Never between a character and the accents or modifiers that belong to it
+    while !stem.is_char_boundary(end)
+        || (end > 0 && stem[end..].chars().next().is_some_and(combines))
+    {
+        end -= 1;
+    }
+    format!("{}{}", &stem[..end], extension)
+}
+
+/// A random name with the extension of `name`, so the host still knows what kind of file it is
+pub fn randomize(name: &str) -> String {
+    let random = Alphanumeric.sample_string(&mut rand::thread_rng(), RANDOM_LEN);
+    let extension: String = split(name)
+        .1
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    match extension.is_empty() {
+        true => random,
+        false => format!("{}.{}", random, extension.to_lowercase()),
+    }
+}
+
+/// The name `path` is uploaded as
+pub fn upload_name(path: &Path, sanitize_names: bool, randomize_name: bool) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    match (randomize_name, sanitize_names) {
+        (true, _) => Some(randomize(&name)),
+        (false, true) => Some(sanitize(&name)),
+        (false, false) => None,
+    }
+}
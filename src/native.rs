@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::winpath;
+use uppy::process;
+
+/// Browsers refuse messages from the host past 1 MB, anything asking for more isn't from them
+const MAX_MESSAGE: u32 = 1024 * 1024;
+
+/// What the extension sends, a file on disk or a URL such as a right-clicked image
+#[derive(Deserialize)]
+struct Request {
+    /// Echoed back so the extension can tell answers apart
+    id: Option<Value>,
+    url: Option<String>,
+    path: Option<PathBuf>,
+    profile: Option<String>,
+}
+
+#[derive(Default, Serialize)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deletion_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The `--json` line of the upload, see `FileReport`
+#[derive(Deserialize)]
+struct Uploaded {
+    url: Option<String>,
+    deletion_url: Option<String>,
+    error: Option<String>,
+}
+
+/// A message is its length as a native endian u32 followed by that much JSON, `None` once the browser hangs up
+fn read_message(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(_) => (),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_ne_bytes(len);
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("a message of {} bytes is too long", len),
+        ));
+    }
+    let mut message = vec![0; len as usize];
+    input.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_message(output: &mut impl Write, response: &Response) -> io::Result<()> {
+    let json = serde_json::to_vec(response)?;
+    output.write_all(&(json.len() as u32).to_ne_bytes())?;
+    output.write_all(&json)?;
+    output.flush()
+}
+
+/// `path` resolved when it is in one of the folders of `native_host_dirs`, anything else on disk
+/// is off limits to a page that got hold of the extension
+fn allowed(path: &Path, dirs: &[PathBuf]) -> Result<PathBuf, String> {
+    let path = path
+        .canonicalize()
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    let inside = dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| path.starts_with(dir));
+    match inside {
+        true => Ok(winpath::plain(&path)),
+        false => Err(format!(
+            "{} isn't in a folder of native_host_dirs",
+            winpath::plain(&path).display()
+        )),
+    }
+}
+
+/// Uploads through another uppy so stdout stays free for messages
+fn upload(request: &Request, dirs: &[PathBuf]) -> Result<Uploaded, String> {
+    let exe = env::current_exe().map_err(|err| err.to_string())?;
+    let mut command = Command::new(exe);
+    if let Some(profile) = &request.profile {
+        command.arg(format!("--profile={}", profile));
+    }
+    command.args(["--json", "--no-clipboard"]);
+    match (&request.url, &request.path) {
+        // After `--` neither of them can pass for a flag
+        (Some(url), _) => command.args(["fetch", "--", url]),
+        (None, Some(path)) => command.args(["--keep", "--"]).arg(allowed(path, dirs)?),
+        (None, None) => return Err("the message has neither a url nor a path".to_string()),
+    };
+
+    let output = process::hidden(&mut command)
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| err.to_string())?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| serde_json::from_str(line).ok())
+        .ok_or_else(|| format!("the upload failed with {}", output.status))
+}
+
+fn answer(message: &[u8], dirs: &[PathBuf]) -> Response {
+    let request: Request = match serde_json::from_slice(message) {
+        Ok(request) => request,
+        Err(err) => {
+            return Response {
+                error: Some(format!("Invalid message: {}", err)),
+                ..Response::default()
+            }
+        }
+    };
+    let mut response = match upload(&request, dirs) {
+        Ok(uploaded) => Response {
+            url: uploaded.url,
+            deletion_url: uploaded.deletion_url,
+            error: uploaded.error,
+            ..Response::default()
+        },
+        Err(err) => Response {
+            error: Some(err),
+            ..Response::default()
+        },
+    };
+    response.id = request.id;
+    response
+}
+
+/// Answers upload requests from a browser extension over the native messaging protocol until
+/// the browser closes the connection
+///
+/// Files on disk are only uploaded from `dirs`, URLs from anywhere
+pub fn run(dirs: &[PathBuf]) -> Result<Exit, Error> {
+    let (mut input, mut output) = (io::stdin().lock(), io::stdout().lock());
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Ok(Exit::Success),
+            Err(err) => {
+                warn!("Failed to read a message from the browser: {}", err);
+                return Ok(Exit::Failure);
+            }
+        };
+        debug!(
+            "Message from the browser: {}",
+            String::from_utf8_lossy(&message)
+        );
+        write_message(&mut output, &answer(&message, dirs)).map_err(Error::Stdout)?;
+    }
+}
@@ -0,0 +1,77 @@
+use serde_json::json;
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+
+use crate::{FileReport, FileStatus};
+use uppy::process;
+
+/// Discord wants `content` while Slack and most other webhooks read `text`
+fn payload(webhook: &str, message: &str) -> serde_json::Value {
+    if webhook.contains("discord.com/api/webhooks")
+        || webhook.contains("discordapp.com/api/webhooks")
+    {
+        json!({ "content": message })
+    } else {
+        json!({ "text": message })
+    }
+}
+
+/// Posts the uploaded files of a batch to a Discord or Slack webhook as a single message,
+/// without `names` only the URLs are posted
+pub fn notify(webhook: &str, reports: &[FileReport], names: bool) {
+    let lines: Vec<String> = reports
+        .iter()
+        .filter(|report| report.status == FileStatus::Uploaded)
+        .filter_map(|report| {
+            let url = report.url.as_deref()?;
+            match names {
+                true => Some(format!(
+                    "{}: {}",
+                    report.path.file_name()?.to_string_lossy(),
+                    url
+                )),
+                false => Some(url.to_string()),
+            }
+        })
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+
+    let client = reqwest::blocking::Client::new();
+    match client
+        .post(webhook)
+        .json(&payload(webhook, &lines.join("\n")))
+        .send()
+    {
+        Ok(res) if res.status().is_success() => debug!("Notified webhook"),
+        Ok(res) => warn!("The webhook responded with {}", res.status()),
+        Err(err) => warn!("Failed to notify the webhook: {}", err),
+    }
+}
+
+/// Balloon from the notification area, PowerShell ships with every Windows so nothing extra is needed
+const BALLOON: &str = "Add-Type -AssemblyName System.Windows.Forms; \
+    $icon = New-Object System.Windows.Forms.NotifyIcon; \
+    $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+    $icon.Visible = $true; \
+    $icon.ShowBalloonTip(10000, 'uppy', $env:UPPY_MESSAGE, 'Info'); \
+    Start-Sleep -Seconds 10; \
+    $icon.Dispose()";
+
+/// Shows a desktop notification without waiting for it to go away
+pub fn desktop(message: &str) {
+    let shown = process::hidden(
+        Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-WindowStyle", "Hidden"])
+            .args(["-Command", BALLOON])
+            // Passed through the environment so the message needs no quoting
+            .env("UPPY_MESSAGE", message),
+    )
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn();
+    if let Err(err) = shown {
+        warn!("Failed to show a notification: {}", err);
+    }
+}
@@ -0,0 +1,161 @@
+//! OAuth2 access tokens for hosts behind OAuth, refreshed whenever they run out
+
+use reqwest::blocking;
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::client::UploadError;
+use crate::clock;
+use crate::config::CONFIG_PATH;
+
+/// Access tokens are refreshed this many seconds before they run out, so one never expires mid upload
+const EXPIRY_MARGIN: u64 = 60;
+
+/// Where access tokens come from, the `oauth` key of the configuration
+///
+/// The refresh token has to be gotten once through the provider's own consent flow
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OAuthConfig {
+    /// Token endpoint of the provider, e.g. `https://oauth2.googleapis.com/token`
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub refresh_token: String,
+}
+
+/// What the token endpoint answered, kept in `oauth.json` between runs
+#[derive(Clone, Serialize, Deserialize)]
+struct Stored {
+    access_token: String,
+    /// Seconds since the epoch
+    expires_at: u64,
+    /// Providers that rotate refresh tokens hand out a new one with every access token
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+type Store = HashMap<String, Stored>;
+
+fn read_store() -> Store {
+    fs::read_to_string(CONFIG_PATH.join("oauth.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// The access token for one host, shared by every request of a client
+pub(crate) struct OAuth {
+    config: OAuthConfig,
+    /// Tokens are stored per host and client so profiles don't overwrite each other's
+    key: String,
+    stored: Mutex<Option<Stored>>,
+}
+
+impl OAuth {
+    pub(crate) fn new(config: &OAuthConfig, host: &str) -> Self {
+        let key = format!("{} {}", host, config.client_id);
+        OAuth {
+            config: config.clone(),
+            stored: Mutex::new(read_store().remove(&key)),
+            key,
+        }
+    }
+
+    fn bearer(stored: &Stored) -> Result<HeaderValue, UploadError> {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", stored.access_token))
+            .map_err(|err| UploadError::OAuth(err.to_string()))?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+
+    /// The `Authorization` header, refreshing the access token first when it ran out
+    pub(crate) fn header(&self, http: &blocking::Client) -> Result<HeaderValue, UploadError> {
+        let mut stored = self
+            .stored
+            .lock()
+            .map_err(|_| UploadError::OAuth("the token store is poisoned".to_string()))?;
+        match &*stored {
+            Some(valid) if valid.expires_at > clock::now().as_secs() + EXPIRY_MARGIN => {
+                Self::bearer(valid)
+            }
+            _ => {
+                let refreshed = self.refresh_with(http, stored.as_ref())?;
+                let header = Self::bearer(&refreshed);
+                *stored = Some(refreshed);
+                header
+            }
+        }
+    }
+
+    /// Throws the access token away, for when the host refused it before it should have run out
+    pub(crate) fn expire(&self) {
+        if let Ok(mut stored) = self.stored.lock() {
+            if let Some(stored) = stored.as_mut() {
+                stored.expires_at = 0;
+            }
+        }
+    }
+
+    fn refresh_with(
+        &self,
+        http: &blocking::Client,
+        stored: Option<&Stored>,
+    ) -> Result<Stored, UploadError> {
+        debug!(
+            "Refreshing the OAuth access token through {}",
+            self.config.token_url
+        );
+        let refresh_token = stored
+            .and_then(|stored| stored.refresh_token.clone())
+            .unwrap_or_else(|| self.config.refresh_token.clone());
+        let mut form = vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", refresh_token.clone()),
+            ("client_id", self.config.client_id.clone()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.clone()));
+        }
+
+        let res = http
+            .post(&self.config.token_url)
+            .form(&form)
+            .send()
+            .and_then(|res| res.error_for_status())
+            .map_err(|err| UploadError::OAuth(err.to_string()))?;
+        let token: TokenResponse = res
+            .json()
+            .map_err(|err| UploadError::OAuth(err.to_string()))?;
+
+        let refreshed = Stored {
+            access_token: token.access_token,
+            // Providers that don't say are assumed to hand out tokens good for an hour
+            expires_at: clock::now().as_secs() + token.expires_in.unwrap_or(3600),
+            refresh_token: token.refresh_token.or(Some(refresh_token)),
+        };
+        self.save(&refreshed);
+        Ok(refreshed)
+    }
+
+    fn save(&self, stored: &Stored) {
+        let mut store = read_store();
+        store.insert(self.key.clone(), stored.clone());
+        let saved = serde_json::to_string_pretty(&store)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(CONFIG_PATH.join("oauth.json"), json));
+        if let Err(err) = saved {
+            warn!("Failed to save the OAuth tokens: {}", err);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use crate::winpath;
+use uppy::process;
+
+/// Runs the OCR engine Windows ships with on the image in `UPPY_OCR_FILE`, printing a line of text per line found
+const WINDOWS_OCR: &str = "[Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+    Add-Type -AssemblyName System.Runtime.WindowsRuntime; \
+    $asTask = [System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object { \
+        $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and \
+        $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1' } | Select-Object -First 1; \
+    function Await($operation, $type) { \
+        $task = $asTask.MakeGenericMethod($type).Invoke($null, @($operation)); \
+        $task.Wait(-1) | Out-Null; $task.Result }; \
+    [Windows.Storage.StorageFile, Windows.Storage, ContentType = WindowsRuntime] | Out-Null; \
+    [Windows.Media.Ocr.OcrEngine, Windows.Foundation, ContentType = WindowsRuntime] | Out-Null; \
+    [Windows.Graphics.Imaging.BitmapDecoder, Windows.Graphics, ContentType = WindowsRuntime] | Out-Null; \
+    $file = Await ([Windows.Storage.StorageFile]::GetFileFromPathAsync($env:UPPY_OCR_FILE)) ([Windows.Storage.StorageFile]); \
+    $stream = Await ($file.OpenAsync([Windows.Storage.FileAccessMode]::Read)) ([Windows.Storage.Streams.IRandomAccessStream]); \
+    $decoder = Await ([Windows.Graphics.Imaging.BitmapDecoder]::CreateAsync($stream)) ([Windows.Graphics.Imaging.BitmapDecoder]); \
+    $bitmap = Await ($decoder.GetSoftwareBitmapAsync()) ([Windows.Graphics.Imaging.SoftwareBitmap]); \
+    $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromUserProfileLanguages(); \
+    $result = Await ($engine.RecognizeAsync($bitmap)) ([Windows.Media.Ocr.OcrResult]); \
+    $result.Lines | ForEach-Object { $_.Text }";
+
+fn output(command: &mut Command) -> Result<String, String> {
+    let output = process::hidden(command)
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn windows(image: &Path) -> Result<String, String> {
+    // An absolute path since WinRT resolves it on its own
+    let image = image.canonicalize().map_err(|err| err.to_string())?;
+    output(
+        Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", WINDOWS_OCR])
+            .env("UPPY_OCR_FILE", winpath::plain(&image)),
+    )
+}
+
+fn tesseract(image: &Path) -> Result<String, String> {
+    output(
+        Command::new("tesseract")
+            .arg(image)
+            .args(["stdout", "--psm", "3"]),
+    )
+}
+
+/// The text in `image`, found by Windows OCR or by tesseract when Windows can't read it
+pub fn recognize(image: &Path) -> Result<String, String> {
+    match windows(image) {
+        Ok(text) if !text.is_empty() => Ok(text),
+        Ok(_) => tesseract(image).or(Ok(String::new())),
+        Err(err) => {
+            debug!("Windows OCR failed, trying tesseract: {}", err);
+            tesseract(image)
+                .map_err(|tesseract| format!("Windows OCR {} and tesseract {}", err, tesseract))
+        }
+    }
+}
@@ -0,0 +1,551 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, Write};
+use std::iter;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::{AsRawHandle, FromRawHandle};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+use std::{env, ptr, thread};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::{history, notify, status, sys, temp};
+use uppy::config::CONFIG_PATH;
+use uppy::process;
+
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+const LONGEST_RETRY: Duration = Duration::from_secs(10 * 60);
+/// The exit code of a command line clap refuses
+const USAGE_EXIT: i32 = 2;
+/// A flusher touches its lock before every round, one this old belongs to a flusher that died
+const STALE_LOCK: Duration = Duration::from_secs(30 * 60);
+/// How often a paused flusher touches its lock, so it isn't taken for one that died
+const PAUSED_TOUCH: Duration = Duration::from_secs(5 * 60);
+
+const PIPE_ACCESS_DUPLEX: u32 = 0x0003;
+const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x0008;
+const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+const ERROR_PIPE_CONNECTED: i32 = 535;
+
+/// Whether uploads in the background are held back, `None` until it was looked up
+///
+/// Uppys in the background look it up once when they start, after that `uppy queue pause` and
+/// `resume` tell them over their pipe, see [`listen`]
+static PAUSED: Mutex<Option<bool>> = Mutex::new(None);
+/// Wakes up whatever waits for [`resume`]
+static RESUMED: Condvar = Condvar::new();
+
+/// Files that couldn't reach the host, uploaded again with the flags they were first given
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Entry {
+    /// The original command line without the files
+    args: Vec<String>,
+    files: Vec<PathBuf>,
+    queued_at: i64,
+}
+
+fn queue_path() -> PathBuf {
+    CONFIG_PATH.join("queue.jsonl")
+}
+
+fn lock_path() -> PathBuf {
+    CONFIG_PATH.join("queue.lock")
+}
+
+/// Where the files of a queue entry that would be gone by the time it is flushed are kept, like
+/// what was fetched or pasted, one directory per file so it keeps its name
+fn copies_dir() -> PathBuf {
+    CONFIG_PATH.join("queued")
+}
+
+/// There while background uploads are paused, for the uppys that start meanwhile
+fn pause_path() -> PathBuf {
+    CONFIG_PATH.join("paused")
+}
+
+/// The pipe `uppy queue pause` and `resume` reach the uppy with process id `pid` through
+fn pipe_name(pid: u32) -> String {
+    format!(r"\\.\pipe\uppy-{}", pid)
+}
+
+/// The queue, which no other uppy changes until this is dropped
+struct Queue {
+    file: File,
+}
+
+impl Queue {
+    fn open() -> io::Result<Queue> {
+        fs::create_dir_all(&*CONFIG_PATH)?;
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(queue_path())?;
+        file.lock()?;
+        Ok(Queue { file })
+    }
+
+    fn entries(&mut self) -> io::Result<Vec<Entry>> {
+        self.file.rewind()?;
+        let mut queue = String::new();
+        self.file.read_to_string(&mut queue)?;
+        Ok(queue
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    fn write(&mut self, entries: &[Entry]) -> io::Result<()> {
+        let queue: String = entries
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| line + "\n")
+            .collect();
+        self.file.rewind()?;
+        self.file.set_len(0)?;
+        self.file.write_all(queue.as_bytes())
+    }
+
+    fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let line = serde_json::to_string(entry)?;
+        self.file.seek(io::SeekFrom::End(0))?;
+        writeln!(self.file, "{}", line)
+    }
+
+    /// Takes `entry` out of the queue, unless it was cleared meanwhile
+    fn remove(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        if let Some(i) = entries.iter().position(|queued| queued == entry) {
+            entries.remove(i);
+            self.write(&entries)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_entries() -> Vec<Entry> {
+    match Queue::open().and_then(|mut queue| queue.entries()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            debug!("Failed to read the offline queue: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// A copy of `file` that the queue owns, for a temp file that is removed when this run ends
+fn keep(file: &Path) -> io::Result<PathBuf> {
+    let dir = copies_dir().join(format!("{:016x}", rand::random::<u64>()));
+    fs::create_dir_all(&dir)?;
+    let kept = dir.join(file.file_name().unwrap_or_default());
+    fs::copy(file, &kept)?;
+    Ok(kept)
+}
+
+/// Removes the copies of files that aren't among `entries` anymore, once they were uploaded or
+/// gave up on
+fn prune(entries: &[Entry]) {
+    let Ok(dirs) = fs::read_dir(copies_dir()) else {
+        return;
+    };
+    for dir in dirs.flatten() {
+        let dir = dir.path();
+        let queued = entries
+            .iter()
+            .flat_map(|entry| &entry.files)
+            .any(|file| file.starts_with(&dir));
+        if queued {
+            continue;
+        }
+        if let Err(err) = fs::remove_dir_all(&dir) {
+            warn!("Failed to remove {}: {}", dir.display(), err);
+        }
+    }
+}
+
+/// Queues `files` to be uploaded with `flags`, see [`crate::cli::flags`], once the host can be reached again
+pub fn queue(files: Vec<PathBuf>, flags: Vec<String>) {
+    let files: io::Result<Vec<PathBuf>> = files
+        .into_iter()
+        .map(|file| match temp::is_copy(&file) {
+            true => keep(&file),
+            false => Ok(file),
+        })
+        .collect();
+    let files = match files {
+        Ok(files) => files,
+        Err(err) => return warn!("Failed to queue the upload: {}", err),
+    };
+    let entry = Entry {
+        args: flags,
+        files,
+        queued_at: history::now(),
+    };
+    match Queue::open().and_then(|mut queue| queue.append(&entry)) {
+        Ok(_) => {
+            println!("{}", t!("queued", count = entry.files.len()));
+            spawn_flusher();
+        }
+        Err(err) => warn!("Failed to queue the upload: {}", err),
+    }
+}
+
+/// Whether `uppy queue pause` holds back uploads in the background
+pub fn paused() -> bool {
+    let Ok(mut paused) = PAUSED.lock() else {
+        return false;
+    };
+    *paused.get_or_insert_with(|| pause_path().exists())
+}
+
+fn set_paused(to: bool) {
+    if let Ok(mut paused) = PAUSED.lock() {
+        *paused = Some(to);
+        RESUMED.notify_all();
+    }
+}
+
+/// Tells every running uppy that listens, like `uppy watch` and the flusher, `message`
+fn tell(message: &str) {
+    for pid in status::pids() {
+        let told = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(pipe_name(pid))
+            .and_then(|mut pipe| {
+                writeln!(pipe, "{}", message)?;
+                let mut answer = String::new();
+                BufReader::new(pipe).read_line(&mut answer)?;
+                Ok(answer)
+            });
+        match told {
+            Ok(_) => debug!("Told process {} to {}", pid, message),
+            // Uploads that publish their status but don't run in the background don't listen
+            Err(err) => debug!("Failed to tell process {} to {}: {}", pid, message, err),
+        }
+    }
+}
+
+/// Holds back the flusher and `uppy watch` until [`resume`], whichever process is running them
+pub fn pause() -> Result<Exit, Error> {
+    fs::write(pause_path(), "").map_err(Error::Queue)?;
+    tell("pause");
+    println!("{}", t!("queue-paused"));
+    Ok(Exit::Success)
+}
+
+pub fn resume() -> Result<Exit, Error> {
+    match fs::remove_file(pause_path()) {
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(Error::Queue(err)),
+        _ => (),
+    }
+    tell("resume");
+    println!("{}", t!("queue-resumed"));
+    Ok(Exit::Success)
+}
+
+/// A new instance of the pipe of this uppy, for the next one telling it something
+fn pipe() -> io::Result<isize> {
+    let name: Vec<u16> = OsString::from(pipe_name(std::process::id()))
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect();
+    // Safety: the name is nul terminated and outlives the call
+    let pipe = unsafe {
+        sys::CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            512,
+            512,
+            0,
+            ptr::null(),
+        )
+    };
+    match pipe {
+        -1 => Err(io::Error::last_os_error()),
+        pipe => Ok(pipe),
+    }
+}
+
+/// Answers `uppy queue pause` and `resume` from now on, pausing and resuming this uppy
+pub fn listen() {
+    let mut next = match pipe() {
+        Ok(pipe) => pipe,
+        Err(err) => return warn!("Failed to listen for uppy queue pause: {}", err),
+    };
+    paused();
+    thread::spawn(move || loop {
+        // Safety: `next` is a pipe handle this thread owns, waited on without overlapped I/O
+        let connected = unsafe { sys::ConnectNamedPipe(next, ptr::null_mut()) } != 0
+            || io::Error::last_os_error().raw_os_error() == Some(ERROR_PIPE_CONNECTED);
+        // Safety: the handle isn't used anywhere else, the file closes it
+        let client = unsafe { File::from_raw_handle(next as _) };
+        // The next one is ready before this one is answered, so nobody finds no pipe at all
+        next = match pipe() {
+            Ok(pipe) => pipe,
+            Err(err) => return warn!("Failed to keep listening for uppy queue pause: {}", err),
+        };
+        if !connected {
+            continue;
+        }
+        let mut message = String::new();
+        let mut reader = BufReader::new(&client);
+        if reader.read_line(&mut message).is_err() {
+            continue;
+        }
+        match message.trim() {
+            "pause" => set_paused(true),
+            "resume" => set_paused(false),
+            message => debug!("Told something unknown over the pipe: {}", message),
+        }
+        let _ = writeln!(&client, "ok");
+        // Safety: the client reads the answer before it goes away with the handle
+        unsafe {
+            sys::FlushFileBuffers(client.as_raw_handle() as isize);
+        }
+    });
+}
+
+/// Drops every queued file without uploading it
+pub fn clear() -> Result<Exit, Error> {
+    let mut queue = Queue::open().map_err(Error::Queue)?;
+    let count: usize = queue
+        .entries()
+        .map_err(Error::Queue)?
+        .iter()
+        .map(|entry| entry.files.len())
+        .sum();
+    queue.write(&[]).map_err(Error::Queue)?;
+    prune(&[]);
+    println!("{}", t!("queue-cleared", count = count));
+    Ok(Exit::Success)
+}
+
+/// Waits for [`resume`], keeping the lock of the flusher fresh meanwhile
+fn wait_while_paused() -> Result<(), Error> {
+    let Ok(mut paused) = PAUSED.lock() else {
+        return Ok(());
+    };
+    if *paused.get_or_insert_with(|| pause_path().exists()) {
+        debug!("Background uploads are paused, waiting");
+    }
+    while paused.unwrap_or_default() {
+        Lock::touch().map_err(Error::Queue)?;
+        paused = match RESUMED.wait_timeout(paused, PAUSED_TOUCH) {
+            Ok((paused, _)) => paused,
+            Err(_) => return Ok(()),
+        };
+    }
+    Ok(())
+}
+
+/// How many files are waiting in the queue
+pub fn queued() -> usize {
+    read_entries().iter().map(|entry| entry.files.len()).sum()
+}
+
+/// Whether anything is waiting in the queue
+pub fn pending() -> bool {
+    fs::metadata(queue_path()).is_ok_and(|meta| meta.len() > 0)
+}
+
+fn is_locked() -> bool {
+    fs::metadata(lock_path())
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|modified| modified.elapsed().unwrap_or_default() < STALE_LOCK)
+}
+
+/// Starts `uppy queue flush --wait` in the background unless one is already running
+pub fn spawn_flusher() {
+    if is_locked() {
+        return;
+    }
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => return warn!("Failed to start flushing the queue: {}", err),
+    };
+    let spawned = process::hidden(Command::new(exe).args(["queue", "flush", "--wait"]))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        Ok(child) => debug!("Flushing the queue in process {}", child.id()),
+        Err(err) => warn!("Failed to start flushing the queue: {}", err),
+    }
+}
+
+/// Held by the one process flushing the queue, removed again once dropped
+struct Lock;
+
+impl Lock {
+    fn acquire() -> Option<Lock> {
+        let created = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(lock_path());
+        match created {
+            Ok(_) => Some(Lock),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists && !is_locked() => {
+                debug!("Taking over the lock of a flusher that died");
+                Lock::touch().ok().map(|_| Lock)
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn touch() -> io::Result<()> {
+        fs::write(lock_path(), std::process::id().to_string())
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(lock_path());
+    }
+}
+
+#[derive(Deserialize)]
+struct Uploaded {
+    path: PathBuf,
+    url: Option<String>,
+}
+
+/// The queued flags without those that pick the output, the upload is run with `--json` instead
+fn replayed(args: &[String]) -> Vec<OsString> {
+    let mut replayed = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" | "--quiet" | "-q" => (),
+            // Queued before the flags were saved with their values attached
+            "--print" => {
+                args.next();
+            }
+            arg if arg.starts_with("--print=") => (),
+            arg => replayed.push(OsString::from(arg)),
+        }
+    }
+    replayed.push("--json".into());
+    replayed
+}
+
+/// Runs uppy again on the queued files, failures that can't reach the host queue them again
+fn upload(entry: &Entry) -> Result<Exit, Error> {
+    let exe = env::current_exe().map_err(Error::Queue)?;
+    let args = replayed(&entry.args);
+    let output = process::hidden(Command::new(exe).args(args).args(&entry.files))
+        .env(status::PUBLISH_ENV, "1")
+        .stderr(Stdio::null())
+        .output()
+        .map_err(Error::Queue)?;
+
+    let uploads: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Uploaded>(line).ok())
+        .filter_map(|upload| {
+            let name = upload.path.file_name()?.to_string_lossy().into_owned();
+            Some(format!("{}: {}", name, upload.url?))
+        })
+        .collect();
+    if !uploads.is_empty() {
+        notify::desktop(&format!("{}\n{}", t!("queue-uploaded"), uploads.join("\n")));
+    }
+    Ok(match output.status.code() {
+        Some(USAGE_EXIT) => return Err(Error::QueuedArgs),
+        Some(code) if code == Exit::Success as i32 => Exit::Success,
+        Some(code) if code == Exit::Network as i32 => Exit::Network,
+        Some(code) if code == Exit::PartialFailure as i32 => Exit::PartialFailure,
+        _ => Exit::Failure,
+    })
+}
+
+/// Uploads everything in the queue, with `wait` retrying with a growing delay until it is empty
+///
+/// With `wait` it is running in the background, where `uppy queue pause` holds it back
+pub fn flush(wait: bool) -> Result<Exit, Error> {
+    let Some(_lock) = Lock::acquire() else {
+        println!("{}", t!("queue-flushing"));
+        return Ok(Exit::Success);
+    };
+    let _status = wait.then(status::announce);
+    if wait {
+        listen();
+    }
+
+    let mut delay = FIRST_RETRY;
+    loop {
+        if wait {
+            wait_while_paused()?;
+        }
+        Lock::touch().map_err(Error::Queue)?;
+        let entries = read_entries();
+        if entries.is_empty() {
+            println!("{}", t!("queue-empty"));
+            return Ok(Exit::Success);
+        }
+
+        // Each entry stays queued until its upload is done, anything still offline is queued
+        // again by the upload itself
+        for entry in entries {
+            if wait && paused() {
+                break;
+            }
+            println!("{}", t!("queue-uploading", count = entry.files.len()));
+            match upload(&entry) {
+                Ok(exit) => {
+                    let mut queue = Queue::open().map_err(Error::Queue)?;
+                    queue.remove(&entry).map_err(Error::Queue)?;
+                    if exit == Exit::Network {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    // Kept for the next round rather than lost, behind the others so they
+                    // aren't held up by it
+                    warn!("{}", err);
+                    let mut queue = Queue::open().map_err(Error::Queue)?;
+                    queue.remove(&entry).map_err(Error::Queue)?;
+                    queue.append(&entry).map_err(Error::Queue)?;
+                    break;
+                }
+            }
+        }
+
+        let mut queue = Queue::open().map_err(Error::Queue)?;
+        prune(&queue.entries().map_err(Error::Queue)?);
+        drop(queue);
+        if !pending() {
+            return Ok(Exit::Success);
+        }
+        if !wait {
+            return Ok(Exit::Network);
+        }
+        debug!("Host still unreachable, retrying in {:?}", delay);
+        thread::sleep(delay);
+        delay = (delay * 2).min(LONGEST_RETRY);
+    }
+}
+
+/// Lists the queued files and when they were queued
+pub fn list() -> Exit {
+    let entries = read_entries();
+    if entries.is_empty() {
+        println!("{}", t!("queue-empty"));
+    }
+    for entry in entries {
+        let queued = history::date(entry.queued_at);
+        for file in &entry.files {
+            println!("{}  {}", queued, file.display());
+        }
+    }
+    Exit::Success
+}
@@ -0,0 +1,107 @@
+//! Zipline's partial uploads, which send a big file in chunks the host puts back together
+//!
+//! How far an upload got is kept in `.config\uppy\partial`, so one cut short by a dropped
+//! connection carries on from the last chunk the host confirmed, whether it is retried right away
+//! or uploaded again by a later run
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+use crate::clock;
+use crate::config::CONFIG_PATH;
+
+/// Chunks older than this may have been cleaned up by the host, the upload starts over then
+const MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// An upload in chunks, and how much of the file the host has
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Partial {
+    /// Tells the host which chunks belong together
+    pub(crate) identifier: String,
+    size: u64,
+    /// Modified time of the file in seconds since the Unix epoch, a changed file starts over
+    modified: u64,
+    chunk_size: u64,
+    started_at: u64,
+    /// Bytes from the start of the file the host confirmed
+    pub(crate) confirmed: u64,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    clock::since_epoch(time).as_secs()
+}
+
+impl Partial {
+    /// The upload of `file` to `host` that was cut short, or a new one when there is none that
+    /// can be carried on
+    pub(crate) fn resume_or_start(
+        host: &str,
+        file: &Path,
+        meta: &Metadata,
+        chunk_size: u64,
+    ) -> Self {
+        let absolute = std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf());
+        let key = Sha256::digest(format!("{}\n{}", host, absolute.display()));
+        let path = CONFIG_PATH.join("partial").join(format!("{:x}.json", key));
+        let modified = meta.modified().map_or(0, seconds);
+        let now = clock::now().as_secs();
+
+        let saved = fs::read(&path)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Partial>(&json).ok())
+            .filter(|saved| {
+                saved.size == meta.len()
+                    && saved.modified == modified
+                    && saved.chunk_size == chunk_size
+                    && now.saturating_sub(saved.started_at) < MAX_AGE_SECS
+            });
+        match saved {
+            Some(saved) => {
+                debug!(
+                    "Carrying on with {}, the host has {} of {} bytes",
+                    file.display(),
+                    saved.confirmed,
+                    saved.size
+                );
+                Partial { path, ..saved }
+            }
+            None => Partial {
+                identifier: format!("{:032x}", rand::random::<u128>()),
+                size: meta.len(),
+                modified,
+                chunk_size,
+                started_at: now,
+                confirmed: 0,
+                path,
+            },
+        }
+    }
+
+    /// Records that the host has everything up to `offset`
+    pub(crate) fn confirm(&mut self, offset: u64) {
+        self.confirmed = offset;
+        let saved = fs::create_dir_all(CONFIG_PATH.join("partial"))
+            .and_then(|_| serde_json::to_vec(self).map_err(io::Error::from))
+            .and_then(|json| fs::write(&self.path, json));
+        if let Err(err) = saved {
+            warn!("Failed to save how far the upload got: {}", err);
+        }
+    }
+
+    /// Forgets the upload once the host put the file together
+    pub(crate) fn finish(self) {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                warn!("Failed to remove {}: {}", self.path.display(), err)
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Picking the files to upload in the console, by typing part of their name when uppy is run
+//! without any or by checking them off in a list with `uppy pick`
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::error::Error;
+use crate::format::is_image;
+use crate::i18n::t;
+use crate::template::human_size;
+use crate::term::{self, Key, Lines, Raw};
+use crate::walk::{self, Filter};
+
+/// How many of the files that match are shown at once
+const ROWS: usize = 10;
+/// Lines the preview of an image takes up, each showing two rows of its pixels
+const PREVIEW_ROWS: u32 = 8;
+/// Images bigger than this aren't decoded for a preview, it would hold up moving through the list
+const PREVIEW_MAX_SIZE: u64 = 50 * 1024 * 1024;
+/// Where a match counts for more, like the start of a name or a word in it
+const SEPARATORS: [char; 6] = ['/', '\\', '_', '-', '.', ' '];
+
+/// How well `query` matches `text`, `None` when its characters don't all appear in it in order
+///
+/// Characters that follow each other or start a word weigh more, shorter paths win ties
+pub fn score(query: &[char], text: &str) -> Option<i64> {
+    let mut wanted = query.iter().peekable();
+    let mut score = 0;
+    let mut previous = None;
+    let mut streak = false;
+    for char in text.chars() {
+        let Some(&&want) = wanted.peek() else {
+            break;
+        };
+        if char.to_lowercase().eq(want.to_lowercase()) {
+            score += 1;
+            if streak {
+                score += 4;
+            }
+            if previous.is_none_or(|previous| SEPARATORS.contains(&previous)) {
+                score += 6;
+            }
+            wanted.next();
+            streak = true;
+        } else {
+            streak = false;
+        }
+        previous = Some(char);
+    }
+    wanted
+        .peek()
+        .is_none()
+        .then(|| score * 1000 - text.len() as i64)
+}
+
+/// The end of `text` when all of it doesn't fit in `width` columns, the name matters most
+pub fn tail(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    match count > width {
+        true => format!(
+            "…{}",
+            text.chars().skip(count - width + 1).collect::<String>()
+        ),
+        false => text.to_string(),
+    }
+}
+
+fn toggle(picked: &mut BTreeSet<usize>, i: usize) {
+    if !picked.remove(&i) {
+        picked.insert(i);
+    }
+}
+
+/// The files in `dir` and their paths relative to it, how they are shown
+pub fn list(dir: &Path, filter: &Filter) -> Result<(Vec<PathBuf>, Vec<String>), Error> {
+    let files = walk::expand(vec![dir.to_path_buf()], filter)?;
+    if files.is_empty() {
+        return Err(Error::NothingToPick(dir.to_path_buf()));
+    }
+    let shown = files
+        .iter()
+        .map(|file| {
+            let relative = file.strip_prefix(dir).unwrap_or(file);
+            relative.display().to_string()
+        })
+        .collect();
+    Ok((files, shown))
+}
+
+/// Lets the files in `dir` be picked, `None` when that was cancelled
+///
+/// Typing narrows the list down, Tab picks several and Enter takes the picked files or the
+/// highlighted one
+pub fn pick(dir: &Path, filter: &Filter) -> Result<Option<Vec<PathBuf>>, Error> {
+    let (files, shown) = list(dir, filter)?;
+
+    let raw = Raw::enter().map_err(Error::Terminal)?;
+    let mut lines = Lines::default();
+    let mut keys = VecDeque::new();
+    let mut query = String::new();
+    let mut picked = BTreeSet::new();
+    let (mut cursor, mut top) = (0, 0);
+    loop {
+        let wanted: Vec<char> = query.chars().collect();
+        let mut matches: Vec<(i64, usize)> = shown
+            .iter()
+            .enumerate()
+            .filter_map(|(i, shown)| Some((score(&wanted, shown)?, i)))
+            .collect();
+        matches.sort_by_key(|&(score, i)| (Reverse(score), i));
+        cursor = cursor.min(matches.len().saturating_sub(1));
+        top = top.min(cursor).max((cursor + 1).saturating_sub(ROWS));
+
+        // Keys that came in together are all dealt with before drawing again
+        if keys.is_empty() {
+            let width = term::width().saturating_sub(4);
+            let mut drawn = vec![format!("> {}\x1b[7m \x1b[27m", query)];
+            for (row, &(_, i)) in matches.iter().enumerate().skip(top).take(ROWS) {
+                drawn.push(format!(
+                    "{}{} {}",
+                    if row == cursor { '>' } else { ' ' },
+                    if picked.contains(&i) { '*' } else { ' ' },
+                    tail(&shown[i], width)
+                ));
+            }
+            drawn.push(format!(
+                "\x1b[2m{}\x1b[22m",
+                t!(
+                    "picker-help",
+                    matched = matches.len(),
+                    total = files.len(),
+                    picked = picked.len()
+                )
+            ));
+            lines.draw(&drawn).map_err(Error::Terminal)?;
+            keys.extend(raw.keys().map_err(Error::Terminal)?);
+        }
+        let Some(key) = keys.pop_front() else {
+            continue;
+        };
+
+        match key {
+            Key::Char(char) => {
+                query.push(char);
+                cursor = 0;
+            }
+            Key::Backspace => {
+                query.pop();
+                cursor = 0;
+            }
+            Key::Up => cursor = cursor.saturating_sub(1),
+            Key::Down => cursor += 1,
+            Key::PageUp => cursor = cursor.saturating_sub(ROWS),
+            Key::PageDown => cursor += ROWS,
+            Key::Tab => {
+                if let Some(&(_, i)) = matches.get(cursor) {
+                    toggle(&mut picked, i);
+                    cursor += 1;
+                }
+            }
+            Key::Enter => {
+                let chosen: Vec<usize> = match picked.is_empty() {
+                    true => matches.get(cursor).map(|&(_, i)| i).into_iter().collect(),
+                    false => picked.iter().copied().collect(),
+                };
+                if chosen.is_empty() {
+                    continue;
+                }
+                lines.clear().map_err(Error::Terminal)?;
+                return Ok(Some(chosen.into_iter().map(|i| files[i].clone()).collect()));
+            }
+            Key::Escape | Key::Interrupt => {
+                lines.clear().map_err(Error::Terminal)?;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// `file` drawn in colored half blocks, each line two rows of pixels, `None` when it isn't an
+/// image that can be decoded
+fn preview(file: &Path) -> Option<Vec<String>> {
+    let name = file.file_name()?.to_string_lossy();
+    let small = fs::metadata(file).is_ok_and(|meta| meta.len() <= PREVIEW_MAX_SIZE);
+    if !is_image(&name) || !small {
+        return None;
+    }
+    let image = image::open(file)
+        .map_err(|err| debug!("No preview of {}: {}", file.display(), err))
+        .ok()?;
+    let image = image
+        .thumbnail(PREVIEW_ROWS * 4, PREVIEW_ROWS * 2)
+        .to_rgb8();
+    let lines = (0..image.height().div_ceil(2))
+        .map(|row| {
+            let mut line: String = (0..image.width())
+                .map(|x| {
+                    let [r, g, b] = image.get_pixel(x, row * 2).0;
+                    // The last row of an image with an odd height only has a top half
+                    match image.get_pixel_checked(x, row * 2 + 1) {
+                        Some(below) => {
+                            let [br, bg, bb] = below.0;
+                            format!("\x1b[38;2;{r};{g};{b}m\x1b[48;2;{br};{bg};{bb}m▀")
+                        }
+                        None => format!("\x1b[49m\x1b[38;2;{r};{g};{b}m▀"),
+                    }
+                })
+                .collect();
+            line.push_str("\x1b[0m");
+            line
+        })
+        .collect();
+    Some(lines)
+}
+
+/// Lets some of the files in `dir` be checked off, `None` when that was cancelled
+///
+/// Space checks the highlighted file, `a` all of them, and images are previewed below the list
+pub fn check(dir: &Path, filter: &Filter) -> Result<Option<Vec<PathBuf>>, Error> {
+    let (files, shown) = list(dir, filter)?;
+    let sizes: Vec<String> = files
+        .iter()
+        .map(|file| {
+            fs::metadata(file).map_or_else(|_| String::new(), |meta| human_size(meta.len()))
+        })
+        .collect();
+
+    let raw = Raw::enter().map_err(Error::Terminal)?;
+    let mut lines = Lines::default();
+    let mut previews: HashMap<usize, Option<Vec<String>>> = HashMap::new();
+    let mut checked = BTreeSet::new();
+    let (mut cursor, mut top) = (0, 0);
+    loop {
+        cursor = cursor.min(files.len() - 1);
+        top = top.min(cursor).max((cursor + 1).saturating_sub(ROWS));
+
+        let width = term::width().saturating_sub(6);
+        let mut drawn = vec![format!(
+            "\x1b[2m{}\x1b[22m",
+            t!("pick-help", checked = checked.len(), total = files.len())
+        )];
+        for (row, name) in shown.iter().enumerate().skip(top).take(ROWS) {
+            let name = tail(name, width.saturating_sub(sizes[row].len() + 4));
+            drawn.push(format!(
+                "{}[{}] {}  \x1b[2m{}\x1b[22m",
+                if row == cursor { '>' } else { ' ' },
+                if checked.contains(&row) { 'x' } else { ' ' },
+                name,
+                sizes[row]
+            ));
+        }
+        let preview = previews
+            .entry(cursor)
+            .or_insert_with(|| preview(&files[cursor]));
+        drawn.extend(preview.iter().flatten().cloned());
+        lines.draw(&drawn).map_err(Error::Terminal)?;
+
+        for key in raw.keys().map_err(Error::Terminal)? {
+            match key {
+                Key::Up => cursor = cursor.saturating_sub(1),
+                Key::Down => cursor += 1,
+                Key::PageUp => cursor = cursor.saturating_sub(ROWS),
+                Key::PageDown => cursor += ROWS,
+                Key::Char(' ') | Key::Tab => toggle(&mut checked, cursor),
+                Key::Char('a') => match checked.len() == files.len() {
+                    true => checked.clear(),
+                    false => checked.extend(0..files.len()),
+                },
+                Key::Enter if !checked.is_empty() => {
+                    lines.clear().map_err(Error::Terminal)?;
+                    return Ok(Some(checked.iter().map(|&i| files[i].clone()).collect()));
+                }
+                Key::Escape | Key::Interrupt => {
+                    lines.clear().map_err(Error::Terminal)?;
+                    return Ok(None);
+                }
+                _ => (),
+            }
+            cursor = cursor.min(files.len() - 1);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Refusing hosts whose certificate doesn't carry the public key pinned in `pinned_sha256`
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, warn};
+use webpki::EndEntityCert;
+
+use crate::config::ConfigError;
+
+/// `sha256//<base64>` like curl's `--pinnedpubkey`, bare base64 or hex
+fn parse_pin(pin: &str) -> Result<[u8; 32], ConfigError> {
+    let invalid = || ConfigError::InvalidPin(pin.to_string());
+    let pin = pin.trim();
+    let pin = pin.strip_prefix("sha256//").unwrap_or(pin);
+    let hex: String = pin.chars().filter(|&c| c != ':').collect();
+    let bytes = match hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => (0..32)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?,
+        false => STANDARD.decode(pin).map_err(|_| invalid())?,
+    };
+    bytes.try_into().map_err(|_| invalid())
+}
+
+/// The SHA-256 of the SubjectPublicKeyInfo of a certificate, which is what gets pinned
+fn pin_of(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let cert = EndEntityCert::try_from(cert).ok()?;
+    Some(Sha256::digest(cert.subject_public_key_info().as_ref()).into())
+}
+
+/// Checks the certificate chain like any other connection, then the public key of the host
+#[derive(Debug)]
+struct PinnedVerifier {
+    verifier: Arc<WebPkiServerVerifier>,
+    /// Only the host is pinned, OAuth token endpoints and downloads from elsewhere aren't
+    host: String,
+    pin: [u8; 32],
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        if !server_name.to_str().eq_ignore_ascii_case(&self.host) {
+            return Ok(verified);
+        }
+        match pin_of(end_entity) {
+            Some(pin) if pin == self.pin => {
+                debug!("The certificate of {} matches pinned_sha256", self.host);
+                Ok(verified)
+            }
+            pin => {
+                warn!(
+                    "The certificate of {} has the key sha256//{}, not the pinned one",
+                    self.host,
+                    pin.map(|pin| STANDARD.encode(pin)).unwrap_or_default()
+                );
+                Err(rustls::Error::General(
+                    "the certificate doesn't match pinned_sha256".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.verifier.supported_verify_schemes()
+    }
+}
+
+/// A TLS configuration that trusts the certificates Windows trusts, as long as the one of `host`
+/// has the public key of `pin`
+pub(crate) fn tls_config(host: &str, pin: &str) -> Result<rustls::ClientConfig, ConfigError> {
+    let pin = parse_pin(pin)?;
+    let host = reqwest::Url::parse(host)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .ok_or_else(|| ConfigError::Tls(format!("{} has no host name to pin", host)))?;
+    let provider = Arc::new(ring::default_provider());
+
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for err in &native.errors {
+        warn!("Failed to load a root certificate: {}", err);
+    }
+    roots.add_parsable_certificates(native.certs);
+    let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|err| ConfigError::Tls(err.to_string()))?;
+
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|err| ConfigError::Tls(err.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+            verifier,
+            host,
+            pin,
+        }))
+        .with_no_client_auth();
+    // reqwest leaves ALPN to preconfigured TLS, without it there is no HTTP/2
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIN: [u8; 32] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        0xff, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+        0xee, 0xff,
+    ];
+
+    #[test]
+    fn parse_pin_reads_base64_and_hex() {
+        let base64 = STANDARD.encode(PIN);
+        let hex: String = PIN.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let colons = PIN
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":");
+
+        assert_eq!(parse_pin(&format!("sha256//{}", base64)).unwrap(), PIN);
+        assert_eq!(parse_pin(&base64).unwrap(), PIN);
+        assert_eq!(parse_pin(&format!(" {} ", hex)).unwrap(), PIN);
+        assert_eq!(parse_pin(&colons).unwrap(), PIN);
+    }
+
+    #[test]
+    fn parse_pin_refuses_anything_but_32_bytes() {
+        assert!(parse_pin("").is_err());
+        assert!(parse_pin("not a pin").is_err());
+        assert!(parse_pin(&STANDARD.encode([0u8; 20])).is_err());
+        assert!(parse_pin(&"g".repeat(64)).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Where a pipeline gets what it uploads
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capture {
+    /// Opens the Windows snipping overlay and waits for the region that is picked
+    Region,
+    /// Whatever files or image are on the clipboard, like `uppy clip`
+    Clipboard,
+}
+
+/// A named capture and the flags its upload gets, run with `uppy pipeline <name>`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub capture: Capture,
+    /// Flags as they'd be typed before the files, e.g. `["--optimize", "--copy-format", "markdown", "--toast"]`
+    #[serde(default)]
+    pub args: Vec<String>,
+}
@@ -0,0 +1,14 @@
+//! Starting other programs without a console window flashing up for each of them, uppy is often
+//! run from a shortcut or the Explorer menu where there is no console to share
+
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// `command` without a window of its own and with nothing to read from stdin
+pub fn hidden(command: &mut Command) -> &mut Command {
+    command
+        .creation_flags(CREATE_NO_WINDOW)
+        .stdin(Stdio::null())
+}
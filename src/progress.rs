@@ -0,0 +1,93 @@
+//! Reporting how far an upload got while its body is being sent, and cutting it short
+
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::checksum::Hashes;
+
+/// How far the upload of a single file got, see [`Client::with_progress`](crate::Client::with_progress)
+#[derive(Clone, Debug)]
+pub struct Progress {
+    /// The file being sent
+    pub path: Arc<Path>,
+    /// Bytes of the file sent so far
+    pub sent: u64,
+    /// Size of the file
+    pub total: u64,
+    /// Time since the file started uploading
+    pub elapsed: Duration,
+}
+
+pub(crate) type ProgressFn = dyn Fn(&Progress) + Send + Sync;
+
+/// Hashes and reports progress on the file while reqwest streams it into the request body
+pub(crate) struct UploadBody<R> {
+    inner: R,
+    path: Arc<Path>,
+    hashes: Arc<Mutex<Hashes>>,
+    progress: Option<Arc<ProgressFn>>,
+    cancel: Option<Arc<AtomicBool>>,
+    sent: u64,
+    total: u64,
+    started: Instant,
+}
+
+impl<R> UploadBody<R> {
+    pub(crate) fn new(
+        inner: R,
+        path: &Path,
+        total: u64,
+        hashes: Arc<Mutex<Hashes>>,
+        progress: Option<Arc<ProgressFn>>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Self {
+        UploadBody {
+            inner,
+            path: Arc::from(path),
+            hashes,
+            progress,
+            cancel,
+            sent: 0,
+            total,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl<R> UploadBody<R> {
+    /// Reports progress from `offset` on, for a chunk that far into the file
+    pub(crate) fn starting_at(mut self, offset: u64) -> Self {
+        self.sent = offset;
+        self
+    }
+}
+
+impl<R: Read> Read for UploadBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Failing the read is the only way to stop reqwest halfway through the body
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(io::Error::other("the upload was cancelled"));
+        }
+        let read = self.inner.read(buf)?;
+        if let Ok(mut hashes) = self.hashes.lock() {
+            hashes.update(&buf[..read]);
+        }
+        self.sent += read as u64;
+        if let Some(progress) = &self.progress {
+            progress(&Progress {
+                path: Arc::clone(&self.path),
+                sent: self.sent,
+                total: self.total,
+                elapsed: self.started.elapsed(),
+            });
+        }
+        Ok(read)
+    }
+}
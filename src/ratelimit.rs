@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::i18n::t;
+use crate::style;
+use crate::template::human_duration;
+use uppy::clock;
+use uppy::config::CONFIG_PATH;
+
+/// Held while the uploads of this process are counted, other processes are kept out by locking
+/// the file
+static COUNTING: Mutex<()> = Mutex::new(());
+
+/// At most `uploads` uploads to a host in any `per` long window, written like `10/minute`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RateLimit {
+    uploads: usize,
+    per: Duration,
+    unit: String,
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(limit: &str) -> Result<Self, String> {
+        let (uploads, unit) = limit
+            .split_once('/')
+            .ok_or_else(|| format!("\"{}\" should look like 10/minute", limit))?;
+        let uploads = match uploads.trim().parse() {
+            Ok(0) | Err(_) => {
+                return Err(format!(
+                    "\"{}\" should start with a number of uploads",
+                    limit
+                ))
+            }
+            Ok(uploads) => uploads,
+        };
+        let unit = unit.trim().to_lowercase();
+        let per = match unit.as_str() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(60 * 60),
+            "d" | "day" => Duration::from_secs(24 * 60 * 60),
+            _ => {
+                return Err(format!(
+                    "unknown unit in \"{}\", use second, minute, hour or day",
+                    limit
+                ))
+            }
+        };
+        Ok(RateLimit { uploads, per, unit })
+    }
+}
+
+impl TryFrom<String> for RateLimit {
+    type Error = String;
+
+    fn try_from(limit: String) -> Result<Self, String> {
+        limit.parse()
+    }
+}
+
+impl From<RateLimit> for String {
+    fn from(limit: RateLimit) -> Self {
+        limit.to_string()
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.uploads, self.unit)
+    }
+}
+
+/// When the recent uploads to each host started, in milliseconds since the epoch
+type Uploads = HashMap<String, Vec<u64>>;
+
+fn uploads_path() -> PathBuf {
+    CONFIG_PATH.join("ratelimit.json")
+}
+
+/// `ratelimit.json`, which no other uppy counts in until it is closed again
+fn open_uploads() -> io::Result<File> {
+    fs::create_dir_all(&*CONFIG_PATH)?;
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(uploads_path())?;
+    file.lock()?;
+    Ok(file)
+}
+
+fn read_uploads(mut file: &File) -> Uploads {
+    let mut json = String::new();
+    match file.read_to_string(&mut json) {
+        Ok(_) => serde_json::from_str(&json).unwrap_or_default(),
+        Err(_) => Uploads::default(),
+    }
+}
+
+fn write_uploads(mut file: &File, uploads: &Uploads) {
+    let written = serde_json::to_string(uploads)
+        .map_err(io::Error::from)
+        .and_then(|json| {
+            file.rewind()?;
+            file.set_len(0)?;
+            file.write_all(json.as_bytes())
+        });
+    if let Err(err) = written {
+        warn!(
+            "Failed to save the uploads counted for the rate limit: {}",
+            err
+        );
+    }
+}
+
+impl RateLimit {
+    /// Blocks until another upload to `host` fits in the limit and counts it, saying so
+    /// when `announce` is set
+    ///
+    /// The uploads are counted in `ratelimit.json` so separate runs of uppy share the limit
+    pub fn wait(&self, host: &str, announce: bool) {
+        let window = self.per.as_millis() as u64;
+        loop {
+            let wait = {
+                let _counting = COUNTING.lock();
+                // Counting without the file would let every run go past the limit on its own
+                let file = match open_uploads() {
+                    Ok(file) => file,
+                    Err(err) => {
+                        return warn!(
+                            "Failed to open the uploads counted for the rate limit: {}",
+                            err
+                        )
+                    }
+                };
+                let mut uploads = read_uploads(&file);
+                let now = clock::now().as_millis() as u64;
+                let started = uploads.entry(host.to_string()).or_default();
+                started.retain(|&at| now.saturating_sub(at) < window);
+                if started.len() < self.uploads {
+                    started.push(now);
+                    write_uploads(&file, &uploads);
+                    return;
+                }
+                let oldest = started.iter().min().copied().unwrap_or(now);
+                Duration::from_millis((oldest + window).saturating_sub(now).max(1))
+            };
+
+            debug!(
+                "Rate limit of {} reached for {}, waiting {:?}",
+                self, host, wait
+            );
+            if announce {
+                println!(
+                    "{}",
+                    style::dim(&t!(
+                        "rate-limited",
+                        limit = self.to_string(),
+                        wait = human_duration(wait)
+                    ))
+                );
+            }
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_uploads_per_unit() {
+        let limit: RateLimit = "10/minute".parse().unwrap();
+        assert_eq!(limit.uploads, 10);
+        assert_eq!(limit.per, Duration::from_secs(60));
+
+        let limit: RateLimit = " 3 / H ".parse().unwrap();
+        assert_eq!(limit.uploads, 3);
+        assert_eq!(limit.per, Duration::from_secs(60 * 60));
+
+        let limit: RateLimit = "100/d".parse().unwrap();
+        assert_eq!(limit.per, Duration::from_secs(24 * 60 * 60));
+    }
+
+    #[test]
+    fn refuses_nonsense() {
+        assert!("10".parse::<RateLimit>().is_err());
+        assert!("0/minute".parse::<RateLimit>().is_err());
+        assert!("ten/minute".parse::<RateLimit>().is_err());
+        assert!("10/fortnight".parse::<RateLimit>().is_err());
+    }
+}
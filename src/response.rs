@@ -0,0 +1,242 @@
+//! Finding the URLs in what a host answered an upload with
+
+use regex_automata::meta::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::client::UploadError;
+use crate::config::{ClientConfig, ConfigError};
+
+/// How much of an answer that has no URL goes in the error
+const SHOWN_BODY: usize = 500;
+
+/// The answers Zipline and the ShareX style hosts copying it are known to give
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Answer {
+    /// `{"files": ["<url>"]}`, or `{"files": [{"url": "<url>"}]}` from Zipline 4
+    Files {
+        files: Vec<FileUrl>,
+        #[serde(default, alias = "deletionUrl", alias = "delete_url")]
+        deletion_url: Option<String>,
+    },
+    /// `{"url": "<url>"}`
+    Single {
+        url: String,
+        #[serde(default, alias = "deletionUrl", alias = "delete_url")]
+        deletion_url: Option<String>,
+    },
+    /// `"<url>"`
+    Bare(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FileUrl {
+    Url(String),
+    Object { url: String },
+}
+
+/// `body` for an error, cut short when it is long
+fn shown(body: &str) -> String {
+    let body = body.trim();
+    match body.char_indices().nth(SHOWN_BODY) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+fn is_url(text: &str) -> bool {
+    (text.starts_with("https://") || text.starts_with("http://"))
+        && !text.contains(char::is_whitespace)
+}
+
+/// The URL and deletion URL in an answer of one of the shapes of [`Answer`], or a plain text URL
+fn zipline_urls(body: &str) -> Result<(String, Option<String>), UploadError> {
+    let (mut urls, deletion_url) = match serde_json::from_str(body) {
+        Ok(Answer::Files {
+            files,
+            deletion_url,
+        }) => {
+            let urls = files
+                .into_iter()
+                .map(|file| match file {
+                    FileUrl::Url(url) | FileUrl::Object { url } => url,
+                })
+                .collect();
+            (urls, deletion_url)
+        }
+        Ok(Answer::Single { url, deletion_url }) => (vec![url], deletion_url),
+        // A string that isn't a URL is more likely an error message
+        Ok(Answer::Bare(url)) if is_url(&url) => (vec![url], None),
+        Err(_) if is_url(body.trim()) => (vec![body.trim().to_string()], None),
+        _ => {
+            return Err(UploadError::InvalidResponse(format!(
+                "no URL in the answer: {}",
+                shown(body)
+            )))
+        }
+    };
+    if urls.len() != 1 {
+        return Err(UploadError::InvalidResponse(format!(
+            "expected a single URL but got {} in the answer: {}",
+            urls.len(),
+            shown(body)
+        )));
+    }
+    Ok((urls.pop().unwrap_or_default(), deletion_url))
+}
+
+/// `$.data.links[0]` as the JSON pointer `/data/links/0`, pointers are left as they are
+fn pointer(path: &str) -> String {
+    let Some(path) = path.strip_prefix('$') else {
+        return path.to_string();
+    };
+    path.replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .filter(|key| !key.is_empty())
+        .map(|key| format!("/{}", key))
+        .collect()
+}
+
+fn lookup<'a>(json: &'a Value, path: &str) -> Result<&'a Value, UploadError> {
+    json.pointer(path)
+        .ok_or_else(|| UploadError::InvalidResponse(format!("nothing at {} in the answer", path)))
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// How the URLs are pulled out of an answer, set up from the `response_*` keys of the configuration
+pub(crate) struct Extract {
+    url_path: Option<String>,
+    url_regex: Option<Regex>,
+    deletion_url_path: Option<String>,
+    lookup_url_path: Option<String>,
+}
+
+impl Extract {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self, ConfigError> {
+        let url_regex = match &config.response_url_regex {
+            Some(regex) => {
+                Some(Regex::new(regex).map_err(|err| ConfigError::InvalidRegex(err.to_string()))?)
+            }
+            None => None,
+        };
+        Ok(Extract {
+            url_path: config.response_url_path.as_deref().map(pointer),
+            url_regex,
+            deletion_url_path: config.response_deletion_url_path.as_deref().map(pointer),
+            lookup_url_path: config.lookup_url_path.as_deref().map(pointer),
+        })
+    }
+
+    fn is_zipline(&self) -> bool {
+        self.url_path.is_none() && self.url_regex.is_none() && self.deletion_url_path.is_none()
+    }
+
+    /// The URL and deletion URL in `body`
+    pub(crate) fn urls(&self, body: &str) -> Result<(String, Option<String>), UploadError> {
+        if self.is_zipline() {
+            return zipline_urls(body);
+        }
+
+        // Plain text answers are fine as long as no path has to be looked up in them
+        let json: Option<Value> = serde_json::from_str(body).ok();
+        let json = || {
+            json.as_ref()
+                .ok_or_else(|| UploadError::InvalidResponse("the answer isn't JSON".to_string()))
+        };
+        let mut url = match &self.url_path {
+            Some(path) => text(lookup(json()?, path)?),
+            None => body.trim().to_string(),
+        };
+        if let Some(regex) = &self.url_regex {
+            let mut groups = regex.create_captures();
+            regex.captures(&url, &mut groups);
+            // The first group when there is one, otherwise everything that matched
+            let found = groups
+                .get_group(1)
+                .or_else(|| groups.get_match().map(|found| found.span()))
+                .ok_or_else(|| {
+                    UploadError::InvalidResponse(
+                        "response_url_regex doesn't match the answer".to_string(),
+                    )
+                })?;
+            url = url[found.range()].to_string();
+        }
+        let deletion_url = match &self.deletion_url_path {
+            Some(path) => Some(text(lookup(json()?, path)?)),
+            None => None,
+        };
+        Ok((url, deletion_url))
+    }
+
+    /// The URL in the answer of `lookup_url`, `None` when the host doesn't have the file
+    pub(crate) fn found(&self, body: &str) -> Result<Option<String>, UploadError> {
+        let Some(path) = &self.lookup_url_path else {
+            return Ok(Some(body.trim().to_string()).filter(|url| !url.is_empty()));
+        };
+        let json: Value = serde_json::from_str(body)
+            .map_err(|_| UploadError::InvalidResponse("the answer isn't JSON".to_string()))?;
+        // Missing or null is how most APIs say they found nothing
+        Ok(json
+            .pointer(path)
+            .filter(|url| !url.is_null())
+            .map(text)
+            .filter(|url| !url.is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(body: &str) -> (String, Option<String>) {
+        zipline_urls(body).expect("an upload answer")
+    }
+
+    #[test]
+    fn zipline_urls_reads_every_shape_of_answer() {
+        let url = "https://files.example.com/u/abc.png".to_string();
+        let deletion = Some("https://files.example.com/d/abc".to_string());
+
+        assert_eq!(
+            urls(r#"{"files":["https://files.example.com/u/abc.png"]}"#),
+            (url.clone(), None)
+        );
+        assert_eq!(
+            urls(
+                r#"{"files":[{"url":"https://files.example.com/u/abc.png"}],"deletion_url":"https://files.example.com/d/abc"}"#
+            ),
+            (url.clone(), deletion.clone())
+        );
+        assert_eq!(
+            urls(
+                r#"{"url":"https://files.example.com/u/abc.png","deletion_url":"https://files.example.com/d/abc"}"#
+            ),
+            (url.clone(), deletion)
+        );
+        assert_eq!(
+            urls(r#""https://files.example.com/u/abc.png""#),
+            (url.clone(), None)
+        );
+        assert_eq!(urls("https://files.example.com/u/abc.png\n"), (url, None));
+    }
+
+    #[test]
+    fn zipline_urls_refuses_answers_without_a_single_url() {
+        assert!(zipline_urls(r#"{"files":[]}"#).is_err());
+        assert!(
+            zipline_urls(r#"{"files":["https://a.example/1","https://a.example/2"]}"#).is_err()
+        );
+        assert!(zipline_urls(r#""file too large""#).is_err());
+        assert!(zipline_urls("<html>Bad Gateway</html>").is_err());
+        assert!(zipline_urls("").is_err());
+    }
+}
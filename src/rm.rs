@@ -0,0 +1,52 @@
+use std::env;
+use tracing::warn;
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::history;
+use crate::i18n::t;
+use uppy::Client;
+
+/// Deletes an upload from its host, given either its URL or the local path it was uploaded from
+pub fn run(target: &str, client: &Client) -> Result<Exit, Error> {
+    let url = delete(target, client)?;
+    println!("{}", t!("deleted-upload", url = url.as_str()));
+    Ok(Exit::Success)
+}
+
+/// [`run`] without printing anything, handing back the URL that was deleted
+pub fn delete(target: &str, client: &Client) -> Result<String, Error> {
+    // Local paths are stored absolute
+    let path = env::current_dir().map(|dir| dir.join(target).to_string_lossy().into_owned());
+
+    let conn = history::open()?;
+    let found = match (history::find(&conn, target)?, &path) {
+        (None, Ok(path)) => history::find(&conn, path)?,
+        (found, _) => found,
+    };
+    let Some((id, upload)) = found else {
+        return Err(Error::NotInHistory(target.to_string()));
+    };
+
+    let deleted = match &upload.deletion_url {
+        Some(deletion_url) => client.delete_with_url(deletion_url),
+        None if upload.host != client.host() => {
+            return Err(Error::WrongHost {
+                url: upload.url,
+                host: upload.host,
+            });
+        }
+        None => client.delete(&upload.url),
+    };
+    if let Err(source) = deleted {
+        return Err(Error::Delete {
+            url: upload.url,
+            source,
+        });
+    }
+
+    if let Err(err) = history::mark_deleted(&conn, id) {
+        warn!("Failed to update the upload history: {}", err);
+    }
+    Ok(upload.url)
+}
@@ -0,0 +1,90 @@
+//! Scanning files for malware before they are uploaded, set up with `malware_scan`
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use crate::{hooks, template, winpath};
+use uppy::process;
+
+/// What `MpCmdRun -Scan` exits with when it found a threat
+const THREAT_FOUND: i32 = 2;
+
+fn defender() -> PathBuf {
+    PathBuf::from(env::var_os("ProgramFiles").unwrap_or_else(|| r"C:\Program Files".into()))
+        .join("Windows Defender")
+        .join("MpCmdRun.exe")
+}
+
+/// Scans `file` with Microsoft Defender, or runs `scanner` with `{path}` and `{name}` filled in
+/// where anything but a zero exit code means the file is flagged
+///
+/// Returns why the file can't be uploaded. Scanners that fail to run count as flagging it, a
+/// folder that is uploaded unattended shouldn't get through because Defender was being updated
+pub fn scan(scanner: &str, file: &Path) -> Result<(), String> {
+    let path = winpath::plain(file);
+    let mut command = match scanner {
+        "defender" => {
+            let mut command = Command::new(defender());
+            // Leaves the file where it is, quarantining is up to the real time protection
+            command
+                .args(["-Scan", "-ScanType", "3", "-DisableRemediation", "-File"])
+                .arg(&path);
+            command
+        }
+        scanner => {
+            let mut values = template::Values::new();
+            values.insert("path", path.display().to_string());
+            values.insert(
+                "name",
+                file.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            );
+            hooks::command(scanner, &values)
+                .ok_or_else(|| "The malware scanner could not be parsed".to_string())?
+        }
+    };
+    debug!(?command, "Scanning for malware");
+
+    let output = process::hidden(&mut command)
+        .output()
+        .map_err(|err| format!("Failed to run the malware scanner: {}", err))?;
+    let code = output.status.code();
+    if output.status.success() {
+        return Ok(());
+    }
+    let said = String::from_utf8_lossy(&output.stdout);
+    let mut said = said.lines().map(str::trim).filter(|line| !line.is_empty());
+    Err(match (scanner, code) {
+        ("defender", Some(THREAT_FOUND)) => {
+            // Defender lists what it found on lines like `Threat  : EICAR-Test-File`
+            let threats: Vec<&str> = said
+                .filter_map(|line| line.strip_prefix("Threat"))
+                .map(|threat| threat.trim_start_matches([' ', ':']))
+                .collect();
+            format!(
+                "Defender found {} in {}, not uploading",
+                match threats.is_empty() {
+                    true => "a threat".to_string(),
+                    false => threats.join(", "),
+                },
+                file.display()
+            )
+        }
+        ("defender", _) => format!(
+            "Defender failed to scan {} ({}), not uploading",
+            file.display(),
+            output.status
+        ),
+        _ => format!(
+            "The malware scanner flagged {} ({}){}, not uploading",
+            file.display(),
+            output.status,
+            said.next_back()
+                .map(|line| format!(": {}", line))
+                .unwrap_or_default()
+        ),
+    })
+}
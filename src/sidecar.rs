@@ -0,0 +1,56 @@
+//! Small files uploaded next to a file to describe it, for `--manifest` and `--sign`
+
+use serde::Serialize;
+use std::ffi::OsStr;
+
+use uppy::{Client, UploadError, UploadRequest};
+
+use crate::temp::TempCopy;
+use crate::{name, FileReport};
+
+/// What `--manifest` uploads next to a file, so whoever downloads it can check they got the real thing
+#[derive(Serialize)]
+struct Manifest<'a> {
+    name: &'a str,
+    /// Without the fragment, an `--encrypt` key has no business in here
+    url: &'a str,
+    /// Of the bytes that were uploaded, which are what gets downloaded
+    sha256: &'a str,
+    size: Option<u64>,
+    /// In RFC 3339
+    uploaded_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<&'a str>,
+}
+
+/// The manifest of an uploaded file, as JSON
+pub fn manifest(
+    report: &FileReport,
+    name: &str,
+    note: Option<&str>,
+) -> serde_json::Result<Vec<u8>> {
+    let url = report.url.as_deref().unwrap_or_default();
+    serde_json::to_vec_pretty(&Manifest {
+        name,
+        url: url.split('#').next().unwrap_or(url),
+        sha256: report.sha256.as_deref().unwrap_or_default(),
+        size: report.size,
+        uploaded_at: chrono::Local::now().to_rfc3339(),
+        note,
+    })
+}
+
+/// Uploads `contents` as a file called `name`, returning its URL
+pub fn upload(
+    client: &Client,
+    name: &str,
+    contents: &[u8],
+    sanitize_names: bool,
+) -> Result<String, UploadError> {
+    let copy = TempCopy::write(OsStr::new(name), contents).map_err(UploadError::IOError)?;
+    let mut request = UploadRequest::new(&copy.path);
+    if let Some(name) = name::upload_name(&copy.path, sanitize_names, false) {
+        request = request.with_file_name(name);
+    }
+    Ok(client.upload(&request)?.url)
+}
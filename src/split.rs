@@ -0,0 +1,246 @@
+use reqwest::blocking;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::download::download;
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::temp::TempCopy;
+use crate::{hash, template};
+
+/// Parses sizes like `95MB`, `500KB` or `2GB`, counted in powers of 1024 like Windows does
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let upper = size.trim().to_uppercase();
+    let split = upper.len() - upper.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (amount, unit) = upper.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("\"{}\" doesn't start with a number", size))?;
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown unit in \"{}\", use KB, MB or GB", size)),
+    };
+    match amount.checked_mul(multiplier) {
+        Some(0) => Err("the size can't be zero".to_string()),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("\"{}\" is too large", size)),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Part {
+    pub url: String,
+    pub size: u64,
+    /// BLAKE3 in hex
+    pub hash: String,
+}
+
+/// Uploaded next to the parts, everything `uppy join` needs to put the file back together
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub size: u64,
+    /// BLAKE3 in hex
+    pub hash: String,
+    pub parts: Vec<Part>,
+}
+
+/// A file cut into numbered parts in the temp directory, removed again once dropped
+pub struct Split {
+    pub original: PathBuf,
+    pub parts: Vec<PathBuf>,
+    dir: TempCopy,
+}
+
+/// Cuts `file` into parts of at most `limit` bytes named `<name>.001`, `<name>.002` and so on
+pub fn split(file: &Path, limit: u64) -> io::Result<Split> {
+    let name = file
+        .file_name()
+        .unwrap_or(OsStr::new("file"))
+        .to_string_lossy();
+    let dir = TempCopy::new(OsStr::new(&format!("{}.manifest.json", name)))?;
+    let mut source = File::open(file)?;
+    let mut parts = Vec::new();
+
+    loop {
+        let part = dir
+            .path
+            .with_file_name(format!("{}.{:03}", name, parts.len() + 1));
+        let mut out = File::create(&part)?;
+        let written = io::copy(&mut (&mut source).take(limit), &mut out)?;
+        if written == 0 {
+            drop(out);
+            fs::remove_file(&part)?;
+            break;
+        }
+        parts.push(part);
+    }
+    debug!("Split {} into {} parts", file.display(), parts.len());
+
+    Ok(Split {
+        original: file.to_path_buf(),
+        parts,
+        dir,
+    })
+}
+
+impl Split {
+    /// Whether `path` is one of the parts or the manifest
+    pub fn contains(&self, path: &Path) -> bool {
+        self.parts.iter().any(|part| part == path) || self.dir.path == path
+    }
+
+    /// Writes the manifest for the parts, uploaded in order as `urls`, returning its path
+    pub fn write_manifest(&self, urls: Vec<String>) -> io::Result<PathBuf> {
+        let parts = self
+            .parts
+            .iter()
+            .zip(urls)
+            .map(|(part, url)| {
+                Ok(Part {
+                    url,
+                    size: fs::metadata(part)?.len(),
+                    hash: hash::hash_file(part)?,
+                })
+            })
+            .collect::<io::Result<Vec<Part>>>()?;
+
+        let manifest = Manifest {
+            name: self
+                .original
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: fs::metadata(&self.original)?.len(),
+            hash: hash::hash_file(&self.original)?,
+            parts,
+        };
+        fs::write(&self.dir.path, serde_json::to_string_pretty(&manifest)?)?;
+        Ok(self.dir.path.clone())
+    }
+}
+
+/// Downloads the parts of `manifest` one after the other into `file` at `path`, checking each of
+/// them and then the whole file against their hashes
+fn join_parts(
+    http: &blocking::Client,
+    manifest: &Manifest,
+    mut file: &File,
+    path: &Path,
+) -> Result<(), Error> {
+    let write_error = |source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    };
+    for (i, part) in manifest.parts.iter().enumerate() {
+        println!(
+            "{}",
+            t!(
+                "downloading-part",
+                part = i + 1,
+                parts = manifest.parts.len()
+            )
+        );
+        let bytes = download(http, &part.url)?;
+        if hash::hash_bytes(&bytes) != part.hash {
+            return Err(Error::Corrupted(part.url.clone()));
+        }
+        file.write_all(&bytes).map_err(write_error)?;
+    }
+    file.sync_all().map_err(write_error)?;
+
+    if hash::hash_file(path).map_err(write_error)? != manifest.hash {
+        return Err(Error::Corrupted(manifest.name.clone()));
+    }
+    Ok(())
+}
+
+/// Downloads the parts listed in a manifest, given as a URL or local path, and joins them back into the file
+///
+/// The file is written to `out` or under its original name in the current directory, never
+/// replacing a file that is already there
+pub fn join(manifest: &str, out: Option<&Path>) -> Result<Exit, Error> {
+    let http = blocking::Client::new();
+    let json = match manifest.starts_with("http://") || manifest.starts_with("https://") {
+        true => download(&http, manifest)?,
+        false => fs::read(manifest).map_err(Error::Manifest)?,
+    };
+    let manifest: Manifest =
+        serde_json::from_slice(&json).map_err(|err| Error::Manifest(err.into()))?;
+
+    // Only the last component so a crafted name can't write outside the directory
+    let name = Path::new(&manifest.name)
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("joined"));
+    let path = out.map(Path::to_path_buf).unwrap_or(name);
+    let write_error = |source| Error::Write {
+        path: path.clone(),
+        source,
+    };
+    // Saves downloading the parts for nothing, the file is still never put over one that
+    // turns up meanwhile
+    if out.is_none() && path.exists() {
+        return Err(write_error(io::ErrorKind::AlreadyExists.into()));
+    }
+
+    // Put together next to the target and only moved to it once the whole file checks out, a
+    // failed join removes what it wrote
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let joining = tempfile::Builder::new()
+        .prefix(".uppy-join-")
+        .tempfile_in(dir)
+        .map_err(write_error)?;
+    join_parts(&http, &manifest, joining.as_file(), joining.path())?;
+    let persisted = match out {
+        Some(_) => joining.persist(&path),
+        None => joining.persist_noclobber(&path),
+    };
+    persisted.map_err(|err| write_error(err.error))?;
+
+    println!(
+        "{}",
+        t!(
+            "joined",
+            path = path.display().to_string(),
+            size = template::human_size(manifest.size)
+        )
+    );
+    Ok(Exit::Success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_reads_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("4k"), Ok(4 * 1024));
+        assert_eq!(parse_size(" 95 MB "), Ok(95 * 1024 * 1024));
+        assert_eq!(parse_size("2GB"), Ok(2 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn parse_size_refuses_nonsense() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("0MB").is_err());
+        assert!(parse_size("10TB").is_err());
+        assert!(parse_size("-1MB").is_err());
+        assert!(parse_size("99999999999999999999GB").is_err());
+        assert!(parse_size("18446744073709551615GB").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! `uppy status`, what every running uppy is uploading, what is queued and what finished lately
+//!
+//! `uppy watch` and the queue in the background keep a file named after their process id in
+//! `.config\uppy\status`, rewritten as their uploads move on and removed once they are done.
+//! Other runs only do so with `publish_status`, or when the queue started them
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, process};
+use tracing::{debug, warn};
+use uppy::config::CONFIG_PATH;
+use uppy::Progress;
+
+use crate::batch::{self, Status};
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::template::human_size;
+use crate::{history, offline, style, sys};
+
+/// How often the file is rewritten while uploads move on, a finished upload is written right away
+const REWRITE: Duration = Duration::from_secs(1);
+/// A transfer that hasn't moved for this long was given up on without finishing
+const STALE: Duration = Duration::from_secs(60);
+/// How many of the latest uploads are shown
+const RECENT: usize = 5;
+/// Set for the uploads the queue runs, so they publish their progress too
+pub const PUBLISH_ENV: &str = "UPPY_PUBLISH_STATUS";
+
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+const STILL_ACTIVE: u32 = 259;
+
+/// What one running uppy publishes about itself
+#[derive(Serialize, Deserialize)]
+struct Running {
+    pid: u32,
+    /// The command line it was started with
+    command: String,
+    started_at: i64,
+    transfers: Vec<Transfer>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Transfer {
+    path: PathBuf,
+    sent: u64,
+    total: u64,
+}
+
+struct Published {
+    started_at: i64,
+    transfers: HashMap<Arc<Path>, (u64, u64, Instant)>,
+    written: Option<Instant>,
+}
+
+static PUBLISHED: Mutex<Option<Published>> = Mutex::new(None);
+
+fn status_dir() -> PathBuf {
+    CONFIG_PATH.join("status")
+}
+
+fn status_path(pid: u32) -> PathBuf {
+    status_dir().join(format!("{}.json", pid))
+}
+
+/// The command line of this run, without the password of `--zip-password`
+fn command() -> String {
+    let args: Vec<String> = env::args()
+        .skip(1)
+        .map(|arg| match arg.starts_with("--zip-password") {
+            true => "--zip-password=…".to_string(),
+            false => arg,
+        })
+        .collect();
+    format!("uppy {}", args.join(" "))
+}
+
+fn write(published: &mut Published) {
+    let running = Running {
+        pid: process::id(),
+        command: command(),
+        started_at: published.started_at,
+        transfers: published
+            .transfers
+            .iter()
+            .map(|(path, &(sent, total, _))| Transfer {
+                path: path.to_path_buf(),
+                sent,
+                total,
+            })
+            .collect(),
+    };
+    let written = fs::create_dir_all(status_dir())
+        .and_then(|_| serde_json::to_vec(&running).map_err(io::Error::from))
+        .and_then(|json| fs::write(status_path(running.pid), json));
+    if let Err(err) = written {
+        warn!("Failed to publish the status: {}", err);
+    }
+    published.written = Some(Instant::now());
+}
+
+/// Keeps this uppy listed in `uppy status` until dropped
+pub struct Announced {
+    /// Whether this one started the listing, an uppy announced by `uppy watch` stays listed
+    /// until the watch is done
+    first: bool,
+}
+
+impl Drop for Announced {
+    fn drop(&mut self) {
+        if !self.first {
+            return;
+        }
+        if let Ok(mut guard) = PUBLISHED.lock() {
+            guard.take();
+        }
+        match fs::remove_file(status_path(process::id())) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                warn!("Failed to remove the status: {}", err)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Lists this uppy in `uppy status` even while it isn't uploading anything, like `uppy watch`
+pub fn announce() -> Announced {
+    let Ok(mut guard) = PUBLISHED.lock() else {
+        return Announced { first: false };
+    };
+    if guard.is_some() {
+        return Announced { first: false };
+    }
+    write(guard.insert(Published {
+        started_at: history::now(),
+        transfers: HashMap::new(),
+        written: None,
+    }));
+    Announced { first: true }
+}
+
+/// Whether uploads of this run are listed without `uppy watch` or the queue announcing it
+pub fn wanted(enabled: bool) -> bool {
+    enabled || env::var_os(PUBLISH_ENV).is_some()
+}
+
+/// Publishes how far an upload got, for [`uppy::Client::with_progress`], once announced
+pub fn progress(progress: &Progress) {
+    let Ok(mut guard) = PUBLISHED.lock() else {
+        return;
+    };
+    let Some(published) = guard.as_mut() else {
+        return;
+    };
+    let finished = progress.sent >= progress.total;
+    match finished {
+        true => {
+            published.transfers.remove(&progress.path);
+        }
+        false => {
+            published.transfers.insert(
+                Arc::clone(&progress.path),
+                (progress.sent, progress.total, Instant::now()),
+            );
+        }
+    }
+    published
+        .transfers
+        .retain(|_, (_, _, updated)| updated.elapsed() < STALE);
+    let due = published
+        .written
+        .is_none_or(|written| written.elapsed() >= REWRITE);
+    if finished || due {
+        write(published);
+    }
+}
+
+/// Whether process `pid` is still running
+fn alive(pid: u32) -> bool {
+    // Safety: the handle is only used here and closed again
+    unsafe {
+        let process = sys::OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process == 0 {
+            return false;
+        }
+        let mut code = 0;
+        let queried = sys::GetExitCodeProcess(process, &mut code);
+        sys::CloseHandle(process);
+        queried != 0 && code == STILL_ACTIVE
+    }
+}
+
+/// The uppys that are running, cleaning up after those that died without removing their file
+fn running() -> Vec<Running> {
+    let Ok(dir) = fs::read_dir(status_dir()) else {
+        return Vec::new();
+    };
+    let mut running: Vec<Running> = dir
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let published = fs::read(&path)
+                .ok()
+                .and_then(|json| serde_json::from_slice::<Running>(&json).ok());
+            match published {
+                Some(published) if published.pid == process::id() => None,
+                Some(published) if alive(published.pid) => Some(published),
+                _ => {
+                    debug!("Removing the status of a process that is gone");
+                    let _ = fs::remove_file(path);
+                    None
+                }
+            }
+        })
+        .collect();
+    running.sort_by_key(|running| running.started_at);
+    running
+}
+
+/// The process ids of the uppys that are running, other than this one
+pub fn pids() -> Vec<u32> {
+    running().iter().map(|running| running.pid).collect()
+}
+
+fn percent(sent: u64, total: u64) -> u64 {
+    match total {
+        0 => 100,
+        total => sent * 100 / total,
+    }
+}
+
+/// Prints every running uppy and its uploads, the queue and how the last uploads went
+pub fn show() -> Result<Exit, Error> {
+    let running = running();
+    if running.is_empty() {
+        println!("{}", t!("status-idle"));
+    }
+    for running in &running {
+        println!(
+            "{}",
+            t!(
+                "status-running",
+                pid = running.pid,
+                since = history::date(running.started_at),
+                command = running.command.clone()
+            )
+        );
+        for transfer in &running.transfers {
+            let name = transfer.path.file_name().unwrap_or_default();
+            println!(
+                "  {}  {}%  {} / {}",
+                name.to_string_lossy(),
+                percent(transfer.sent, transfer.total),
+                human_size(transfer.sent),
+                human_size(transfer.total)
+            );
+        }
+    }
+
+    if offline::paused() {
+        println!("{}", t!("status-paused"));
+    }
+    let queued = offline::queued();
+    if queued > 0 {
+        println!("{}", t!("status-queued", count = queued));
+    }
+
+    match batch::last() {
+        Ok(Some(batch)) => {
+            let count = |statuses: &[Status]| batch.with_status(statuses).len();
+            println!(
+                "{}",
+                t!(
+                    "status-batch",
+                    since = history::date(batch.started_at),
+                    done = count(&[Status::Done]),
+                    failed = count(&[Status::Failed]),
+                    pending = count(&[Status::Pending, Status::InProgress])
+                )
+            );
+        }
+        Ok(None) => (),
+        Err(err) => warn!("Failed to read the last batch: {}", err),
+    }
+
+    let filter = history::Filter {
+        limit: Some(RECENT),
+        ..Default::default()
+    };
+    let uploads = history::search(&history::open()?, &filter)?;
+    if !uploads.is_empty() {
+        println!("{}", style::dim(&t!("status-recent")));
+    }
+    for upload in uploads {
+        println!("  {}  {}  {}", upload.date(), upload.url, upload.name());
+    }
+    Ok(Exit::Success)
+}
@@ -0,0 +1,47 @@
+use clap::ColorChoice;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STDOUT_COLOR: AtomicBool = AtomicBool::new(false);
+
+const GREEN: &str = "\x1b[32m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+fn enabled(choice: ColorChoice, terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal && !anstyle_query::no_color(),
+    }
+}
+
+/// Decides whether stdout and stderr get colored, returning the choice for stderr
+pub fn init(choice: ColorChoice) -> bool {
+    let stdout = enabled(choice, std::io::stdout().is_terminal());
+    let stderr = enabled(choice, std::io::stderr().is_terminal());
+
+    // Older consoles need escape sequences switched on before they render them
+    if (stdout || stderr) && anstyle_query::windows::enable_ansi_colors() == Some(false) {
+        return false;
+    }
+
+    STDOUT_COLOR.store(stdout, Ordering::Relaxed);
+    stderr
+}
+
+fn paint(color: &str, text: &str) -> String {
+    if STDOUT_COLOR.load(Ordering::Relaxed) {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn success(text: &str) -> String {
+    paint(GREEN, text)
+}
+
+pub fn dim(text: &str) -> String {
+    paint(DIM, text)
+}
@@ -0,0 +1,232 @@
+//! `uppy sync`, uploading what changed in a directory and keeping a map of where each file went
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use uppy::{clock, Client};
+
+use crate::error::Error;
+use crate::{hash, history, winpath, FileReport, FileStatus};
+
+/// Where the map goes in the synced directory, hidden so it isn't synced along with the files
+pub const MAP_NAME: &str = ".uppy-sync.json";
+
+/// Every file of a synced directory and its URL, by its path relative to the directory
+#[derive(Serialize, Deserialize, Default)]
+pub struct SyncMap {
+    pub host: String,
+    /// When the directory was last synced, in RFC 3339
+    pub synced_at: String,
+    pub files: BTreeMap<String, Synced>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Synced {
+    pub url: String,
+    pub size: u64,
+    /// Last modified time in seconds since the Unix epoch, files that kept it and their size
+    /// aren't hashed again
+    pub modified: u64,
+    /// BLAKE3 of the file as it was uploaded
+    pub hash: String,
+}
+
+/// The map at `path`, empty when the directory wasn't synced before
+pub fn read(path: &Path) -> Result<SyncMap, Error> {
+    let failed = |source: io::Error| Error::SyncMap {
+        path: path.to_path_buf(),
+        source,
+    };
+    match fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json).map_err(|err| failed(err.into())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(SyncMap::default()),
+        Err(err) => Err(failed(err)),
+    }
+}
+
+pub fn write(path: &Path, map: &SyncMap) -> Result<(), Error> {
+    let write = || fs::write(path, serde_json::to_vec_pretty(map)?);
+    write().map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// `file` relative to `dir` with forward slashes, how the map lists it
+fn key(dir: &Path, file: &Path) -> Option<String> {
+    let file = winpath::extended(file);
+    let relative = file.strip_prefix(winpath::extended(dir)).ok()?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    Some(parts.join("/"))
+}
+
+fn modified(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .map_or(0, |modified| clock::since_epoch(modified).as_secs())
+}
+
+/// Whether `file` is still what was uploaded, only hashing it when its modified time changed
+fn unchanged(file: &Path, synced: &Synced) -> bool {
+    let Ok(meta) = fs::metadata(file) else {
+        return false;
+    };
+    meta.len() == synced.size
+        && (modified(&meta) == synced.modified
+            || hash::hash_file(file).is_ok_and(|hash| hash == synced.hash))
+}
+
+/// The earlier upload of `file` to `host` in the history, so files uploaded before the directory
+/// was first synced are mapped rather than uploaded again
+fn uploaded(conn: &Connection, file: &Path, host: &str) -> Option<Synced> {
+    let meta = fs::metadata(file).ok()?;
+    let lookup = || -> rusqlite::Result<Option<Synced>> {
+        // Hashing is only worth it when a file as large was uploaded
+        if !history::has_size(conn, meta.len(), host)? {
+            return Ok(None);
+        }
+        let Ok(hash) = hash::hash_file(file) else {
+            return Ok(None);
+        };
+        let upload = history::find_by_hash(conn, &hash, host)?;
+        Ok(upload.map(|upload| Synced {
+            url: upload.url,
+            size: meta.len(),
+            modified: modified(&meta),
+            hash,
+        }))
+    };
+    lookup()
+        .map_err(|err| {
+            warn!(
+                "Failed to look {} up in the history: {}",
+                file.display(),
+                err
+            )
+        })
+        .ok()
+        .flatten()
+}
+
+/// Whether the host no longer has what `url` points to, in which case the history stops handing
+/// it out
+fn gone(client: &Client, history: Option<&Connection>, url: &str) -> bool {
+    match client.exists(url) {
+        Ok(true) => false,
+        Ok(false) => {
+            debug!("{} is gone from the host, uploading it again", url);
+            // The history would otherwise hand out the same URL again
+            if let Some(conn) = history {
+                match history::find(conn, url) {
+                    Ok(Some((id, _))) => {
+                        if let Err(err) = history::mark_deleted(conn, id) {
+                            warn!("Failed to update the upload history: {}", err);
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => warn!("Failed to look {} up in the history: {}", url, err),
+                }
+            }
+            true
+        }
+        Err(err) => {
+            warn!("Failed to check whether {} is still there: {}", url, err);
+            false
+        }
+    }
+}
+
+/// The files of `dir` that are new or changed since the last sync to `host`
+///
+/// Files the map doesn't have yet are looked up in `history` and mapped to their earlier upload
+/// when there is one. With `verify` the URLs of the other files are checked as well, and those
+/// the host no longer has are uploaded again
+pub fn changed(
+    map: &mut SyncMap,
+    dir: &Path,
+    files: &[PathBuf],
+    host: &str,
+    history: Option<&Connection>,
+    verify: Option<&Client>,
+) -> Vec<PathBuf> {
+    if map.host != host {
+        map.files.clear();
+        map.host = host.to_string();
+    }
+    let mut changed = Vec::new();
+    for file in files {
+        let Some(key) = key(dir, file) else {
+            changed.push(file.clone());
+            continue;
+        };
+        match map.files.get(&key) {
+            Some(synced) if !unchanged(file, synced) => {
+                changed.push(file.clone());
+                continue;
+            }
+            Some(_) => (),
+            None => match history.and_then(|conn| uploaded(conn, file, host)) {
+                Some(synced) => {
+                    debug!("{} was already uploaded as {}", file.display(), synced.url);
+                    map.files.insert(key.clone(), synced);
+                }
+                None => {
+                    changed.push(file.clone());
+                    continue;
+                }
+            },
+        }
+        let url = &map.files[&key].url;
+        if verify.is_some_and(|client| gone(client, history, url)) {
+            changed.push(file.clone());
+        }
+    }
+    changed
+}
+
+/// Adds what was uploaded to the map and drops the files that are no longer in `dir`
+pub fn update(
+    map: &mut SyncMap,
+    dir: &Path,
+    files: &[PathBuf],
+    reports: &[FileReport],
+    host: &str,
+) {
+    if map.host != host {
+        map.files.clear();
+    }
+    let present: HashSet<String> = files.iter().filter_map(|file| key(dir, file)).collect();
+    map.files.retain(|key, _| present.contains(key));
+
+    for report in reports {
+        let (FileStatus::Uploaded, Some(url)) = (report.status, &report.url) else {
+            continue;
+        };
+        let Some(key) = key(dir, &report.path) else {
+            continue;
+        };
+        // Hashed again since the hash in the report may be of a converted copy
+        let synced = fs::metadata(&report.path).and_then(|meta| {
+            Ok(Synced {
+                url: url.clone(),
+                size: meta.len(),
+                modified: modified(&meta),
+                hash: hash::hash_file(&report.path)?,
+            })
+        });
+        match synced {
+            Ok(synced) => {
+                map.files.insert(key, synced);
+            }
+            Err(err) => warn!("Failed to map {}: {}", report.path.display(), err),
+        }
+    }
+    map.host = host.to_string();
+    map.synced_at = chrono::Local::now().to_rfc3339();
+}
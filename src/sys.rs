@@ -0,0 +1,62 @@
+//! The few calls into Windows that no dependency covers, declared once for every module using them
+
+use std::ffi::c_void;
+
+pub const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+pub const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+
+#[repr(C)]
+#[derive(Default)]
+pub struct Coord {
+    pub x: i16,
+    pub y: i16,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct SmallRect {
+    pub left: i16,
+    pub top: i16,
+    pub right: i16,
+    pub bottom: i16,
+}
+
+/// `CONSOLE_SCREEN_BUFFER_INFO`
+#[repr(C)]
+#[derive(Default)]
+pub struct ScreenBufferInfo {
+    pub size: Coord,
+    pub cursor: Coord,
+    pub attributes: u16,
+    pub window: SmallRect,
+    pub maximum_window_size: Coord,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn GetUserDefaultLocaleName(name: *mut u16, len: i32) -> i32;
+    pub fn SetConsoleCtrlHandler(
+        handler: Option<unsafe extern "system" fn(u32) -> i32>,
+        add: i32,
+    ) -> i32;
+    pub fn OpenProcess(access: u32, inherit: i32, pid: u32) -> isize;
+    pub fn GetExitCodeProcess(process: isize, code: *mut u32) -> i32;
+    pub fn CloseHandle(handle: isize) -> i32;
+    pub fn GetStdHandle(handle: u32) -> isize;
+    pub fn SetStdHandle(handle: u32, value: isize) -> i32;
+    pub fn GetConsoleMode(console: isize, mode: *mut u32) -> i32;
+    pub fn SetConsoleMode(console: isize, mode: u32) -> i32;
+    pub fn GetConsoleScreenBufferInfo(console: isize, info: *mut ScreenBufferInfo) -> i32;
+    pub fn CreateNamedPipeW(
+        name: *const u16,
+        open_mode: u32,
+        pipe_mode: u32,
+        max_instances: u32,
+        out_buffer_size: u32,
+        in_buffer_size: u32,
+        default_timeout: u32,
+        security_attributes: *const c_void,
+    ) -> isize;
+    pub fn ConnectNamedPipe(pipe: isize, overlapped: *mut c_void) -> i32;
+    pub fn FlushFileBuffers(file: isize) -> i32;
+}
@@ -0,0 +1,50 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+use tracing::warn;
+
+const PREFIX: &str = "uppy-";
+
+/// A file written to its own directory under the temp directory, removed again once dropped
+///
+/// Each copy gets a directory of its own so it can keep the name the host will see
+pub struct TempCopy {
+    dir: PathBuf,
+    pub path: PathBuf,
+}
+
+impl TempCopy {
+    /// Makes the directory for a file called `name`, leaving the file itself for the caller to write
+    pub fn new(name: &OsStr) -> io::Result<Self> {
+        let dir = env::temp_dir().join(format!("{}{:016x}", PREFIX, rand::random::<u64>()));
+        fs::create_dir(&dir)?;
+        Ok(TempCopy {
+            path: dir.join(name),
+            dir,
+        })
+    }
+
+    pub fn write(name: &OsStr, contents: &[u8]) -> io::Result<Self> {
+        let copy = TempCopy::new(name)?;
+        fs::write(&copy.path, contents)?;
+        Ok(copy)
+    }
+}
+
+impl Drop for TempCopy {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            warn!("Failed to remove {}: {}", self.dir.display(), err);
+        }
+    }
+}
+
+/// Whether `path` is a [`TempCopy`], which is gone once the uppy that made it exits
+pub fn is_copy(path: &Path) -> bool {
+    path.parent().is_some_and(|dir| {
+        dir.parent() == Some(env::temp_dir().as_path())
+            && dir
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(PREFIX))
+    })
+}
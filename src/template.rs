@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub type Values = HashMap<&'static str, String>;
+
+/// Replaces every `{placeholder}` in the template with its value, unknown placeholders are left untouched
+///
+/// The template is read once from left to right, so a value that contains `{url}` or the like
+/// is put in as it is rather than filled in as well
+pub fn render(template: &str, values: &Values) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find(['{', '}'])
+            .filter(|&end| after[end..].starts_with('}'))
+            .and_then(|end| Some((values.get(&after[..end])?, end)));
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    template.contains(&format!("{{{}}}", placeholder))
+}
+
+/// Zipline serves the file itself under /r/ while /u/ is the embed page
+pub fn raw_url(url: &str) -> String {
+    url.replacen("/u/", "/r/", 1)
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+pub fn human_duration(elapsed: Duration) -> String {
+    format!("{:.2}s", elapsed.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_fills_in_placeholders() {
+        let values = Values::from([
+            ("url", "https://files.example.com/u/abc.png".to_string()),
+            ("name", "abc.png".to_string()),
+        ]);
+
+        assert_eq!(
+            render("[{name}]({url}) {name}", &values),
+            "[abc.png](https://files.example.com/u/abc.png) abc.png"
+        );
+        assert_eq!(render("{unknown} {url", &values), "{unknown} {url");
+        assert_eq!(render("", &values), "");
+        assert_eq!(
+            render("{{url}}", &values),
+            "{https://files.example.com/u/abc.png}"
+        );
+    }
+
+    #[test]
+    fn render_leaves_placeholders_in_values_alone() {
+        let values = Values::from([
+            ("name", "{url}.png".to_string()),
+            ("url", "https://files.example.com/u/{name}".to_string()),
+        ]);
+
+        assert_eq!(
+            render("{name} {url}", &values),
+            "{url}.png https://files.example.com/u/{name}"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Reading single keys from the console and redrawing a few lines of stderr in place, for the
+//! pickers
+
+use std::io::{self, IsTerminal, Read, Write};
+
+use crate::sys::{self, ScreenBufferInfo};
+
+const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+const ENABLE_LINE_INPUT: u32 = 0x0002;
+const ENABLE_ECHO_INPUT: u32 = 0x0004;
+const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+/// What is drawn when the width of the console can't be found out
+const FALLBACK_WIDTH: usize = 80;
+
+/// Whether keys can be read and lines drawn, stdout may still be piped somewhere
+pub fn available() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Columns of the console stderr is drawn to
+pub fn width() -> usize {
+    let mut info = ScreenBufferInfo::default();
+    // Safety: the info is written by Windows and only read when that worked
+    let found = unsafe {
+        sys::GetConsoleScreenBufferInfo(sys::GetStdHandle(sys::STD_ERROR_HANDLE), &mut info)
+    };
+    match found {
+        0 => FALLBACK_WIDTH,
+        _ => (info.window.right - info.window.left + 1).max(1) as usize,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    /// Ctrl+C, which doesn't end uppy while keys are read one by one
+    Interrupt,
+}
+
+/// Keys arrive one by one without being echoed until this is dropped
+pub struct Raw {
+    input: isize,
+    mode: u32,
+}
+
+impl Raw {
+    pub fn enter() -> io::Result<Raw> {
+        let mut mode = 0;
+        // Safety: plain calls on the console handle of this process
+        unsafe {
+            let input = sys::GetStdHandle(sys::STD_INPUT_HANDLE);
+            if sys::GetConsoleMode(input, &mut mode) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let raw = (mode & !(ENABLE_PROCESSED_INPUT | ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT))
+                | ENABLE_VIRTUAL_TERMINAL_INPUT;
+            if sys::SetConsoleMode(input, raw) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            eprint!("\x1b[?25l");
+            Ok(Raw { input, mode })
+        }
+    }
+
+    /// The keys pressed since the last call, waiting for at least one
+    pub fn keys(&self) -> io::Result<Vec<Key>> {
+        let mut buf = [0; 256];
+        let read = match io::stdin().read(&mut buf)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => read,
+        };
+        let text = String::from_utf8_lossy(&buf[..read]);
+        let mut chars = text.chars();
+        let mut keys = Vec::new();
+        while let Some(char) = chars.next() {
+            keys.push(match char {
+                '\r' | '\n' => Key::Enter,
+                '\t' => Key::Tab,
+                '\x08' | '\x7f' => Key::Backspace,
+                '\x03' => Key::Interrupt,
+                // Arrows and the like come as escape sequences, a lone escape is the key itself
+                '\x1b' if matches!(chars.clone().next(), Some('[' | 'O')) => {
+                    chars.next();
+                    let mut params = String::new();
+                    let end = loop {
+                        match chars.next() {
+                            Some(char) if char.is_ascii_digit() || char == ';' => params.push(char),
+                            end => break end,
+                        }
+                    };
+                    match (params.as_str(), end) {
+                        (_, Some('A')) => Key::Up,
+                        (_, Some('B')) => Key::Down,
+                        ("5", Some('~')) => Key::PageUp,
+                        ("6", Some('~')) => Key::PageDown,
+                        _ => continue,
+                    }
+                }
+                '\x1b' => Key::Escape,
+                char if char.is_control() => continue,
+                char => Key::Char(char),
+            });
+        }
+        Ok(keys)
+    }
+}
+
+impl Drop for Raw {
+    fn drop(&mut self) {
+        eprint!("\x1b[?25h");
+        // Safety: puts back the mode read in `enter`
+        unsafe {
+            sys::SetConsoleMode(self.input, self.mode);
+        }
+    }
+}
+
+/// Lines on stderr that are drawn over on every redraw instead of scrolling
+#[derive(Default)]
+pub struct Lines {
+    drawn: usize,
+}
+
+impl Lines {
+    fn rewind(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.drawn > 1 {
+            write!(out, "\x1b[{}A", self.drawn - 1)?;
+        }
+        write!(out, "\r\x1b[J")
+    }
+
+    /// Replaces what was drawn with `lines`, each cut to the width of the console
+    pub fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        let width = width().saturating_sub(1);
+        let mut out = io::stderr().lock();
+        self.rewind(&mut out)?;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                write!(out, "\r\n")?;
+            }
+            write!(out, "{}", fit(line, width))?;
+        }
+        self.drawn = lines.len();
+        out.flush()
+    }
+
+    /// Removes what was drawn
+    pub fn clear(&mut self) -> io::Result<()> {
+        let mut out = io::stderr().lock();
+        self.rewind(&mut out)?;
+        self.drawn = 0;
+        out.flush()
+    }
+}
+
+/// `line` cut to `width` columns, keeping escape sequences out of the count
+fn fit(line: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut columns = 0;
+    let mut escape = false;
+    for char in line.chars() {
+        match char {
+            '\x1b' => escape = true,
+            char if escape && char.is_ascii_alphabetic() => escape = false,
+            _ if escape => (),
+            _ if columns == width => continue,
+            _ => columns += 1,
+        }
+        fitted.push(char);
+    }
+    fitted
+}
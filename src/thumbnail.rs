@@ -0,0 +1,91 @@
+use base64::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use crate::format::is_image;
+use uppy::config::CONFIG_PATH;
+use uppy::process;
+
+const SIZE: u32 = 256;
+const VIDEO_EXTENSIONS: [&str; 6] = ["mp4", "mkv", "webm", "mov", "avi", "m4v"];
+
+fn cache_dir() -> PathBuf {
+    CONFIG_PATH.join("thumbnails")
+}
+
+/// Where the thumbnail of the file with this hash is cached, whether it exists or not
+pub fn path(hash: &str) -> PathBuf {
+    cache_dir().join(format!("{}.jpg", hash))
+}
+
+fn is_video(file: &Path) -> bool {
+    match file.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+fn from_image(file: &Path, thumbnail: &Path) -> Result<(), String> {
+    let image = image::open(file).map_err(|err| err.to_string())?;
+    image
+        .thumbnail(SIZE, SIZE)
+        .to_rgb8()
+        .save(thumbnail)
+        .map_err(|err| err.to_string())
+}
+
+/// Grabs a frame with ffmpeg, videos are simply skipped when it isn't installed
+fn from_video(file: &Path, thumbnail: &Path) -> Result<(), String> {
+    let status = process::hidden(
+        Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-y", "-ss", "1", "-i"])
+            .arg(file)
+            .args(["-frames:v", "1", "-vf"])
+            .arg(format!("scale={}:-2", SIZE))
+            .arg(thumbnail),
+    )
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .map_err(|err| format!("ffmpeg is unavailable: {}", err))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("ffmpeg exited with {}", status)),
+    }
+}
+
+/// Caches a thumbnail of an image or video under the configuration directory
+pub fn generate(file: &Path, hash: &str) {
+    let thumbnail = path(hash);
+    if thumbnail.exists() {
+        return;
+    }
+
+    let generate = if is_image(&file.to_string_lossy()) {
+        from_image
+    } else if is_video(file) {
+        from_video
+    } else {
+        return;
+    };
+
+    let generated = fs::create_dir_all(cache_dir())
+        .map_err(|err| err.to_string())
+        .and_then(|_| generate(file, &thumbnail));
+    match generated {
+        Ok(_) => debug!("Cached thumbnail {}", thumbnail.display()),
+        Err(err) => debug!("No thumbnail for {}: {}", file.display(), err),
+    }
+}
+
+/// The cached thumbnail as a data URI for embedding in HTML
+pub fn data_uri(hash: &str) -> Option<String> {
+    let bytes = fs::read(path(hash)).ok()?;
+    Some(format!(
+        "data:image/jpeg;base64,{}",
+        BASE64_STANDARD.encode(bytes)
+    ))
+}
@@ -0,0 +1,147 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uppy::Progress;
+
+use crate::template::human_size;
+
+/// How often the line is redrawn, faster than this only makes it flicker
+const REDRAW: Duration = Duration::from_millis(100);
+/// How long the current speed is measured over before it is smoothed into the shown one
+const SAMPLE: Duration = Duration::from_millis(250);
+
+#[derive(Default)]
+struct State {
+    batch_started: Option<Instant>,
+    /// Bytes sent for files that are done
+    finished: u64,
+    /// Sizes of the files that haven't started yet
+    pending: u64,
+    files: usize,
+    name: String,
+    sent: u64,
+    total: u64,
+    /// Smoothed bytes per second over the last few samples
+    speed: f64,
+    sample: Option<(Duration, u64)>,
+    drawn: Option<Instant>,
+    width: usize,
+}
+
+/// Live speed and ETA of the file being uploaded and of the whole batch, drawn on stderr
+#[derive(Default)]
+pub struct Meter {
+    state: Mutex<State>,
+}
+
+/// `42s`, `3m05s` or `1h20m`
+fn eta(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "?".to_string();
+    }
+    let seconds = seconds.ceil() as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+fn percent(done: u64, total: u64) -> u64 {
+    match total {
+        0 => 100,
+        total => done * 100 / total,
+    }
+}
+
+impl State {
+    fn line(&self, elapsed: Duration) -> String {
+        let average = self.sent as f64 / elapsed.as_secs_f64().max(0.001);
+        let left = self.total.saturating_sub(self.sent) as f64;
+        let mut line = format!(
+            "{} {:>3}%  {}/s (avg {}/s)  ETA {}",
+            self.name,
+            percent(self.sent, self.total),
+            human_size(self.speed as u64),
+            human_size(average as u64),
+            eta(left / self.speed.max(average)),
+        );
+
+        if let (true, Some(started)) = (self.files > 1, self.batch_started) {
+            let done = self.finished + self.sent;
+            let total = done + left as u64 + self.pending;
+            let speed = done as f64 / started.elapsed().as_secs_f64().max(0.001);
+            line += &format!(
+                "  |  batch {:>3}%  ETA {}",
+                percent(done, total),
+                eta((total - done) as f64 / speed)
+            );
+        }
+        line
+    }
+}
+
+impl Meter {
+    /// Starts a batch of `files` adding up to `size` bytes
+    pub fn start_batch(&self, files: usize, size: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = State {
+                batch_started: Some(Instant::now()),
+                pending: size,
+                files,
+                ..State::default()
+            };
+        }
+    }
+
+    pub fn start_file(&self, name: &str, size: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            state.pending = state.pending.saturating_sub(size);
+            state.name = name.to_string();
+            state.sent = 0;
+            state.total = size;
+            state.speed = 0.0;
+            state.sample = None;
+            state.drawn = None;
+        }
+    }
+
+    pub fn update(&self, progress: &Progress) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.sent = progress.sent;
+        // Converted or encrypted copies aren't the size the file started out as
+        state.total = progress.total;
+
+        let (at, sent) = state.sample.unwrap_or_default();
+        let since = progress.elapsed.saturating_sub(at);
+        if since >= SAMPLE {
+            let current = (progress.sent - sent) as f64 / since.as_secs_f64();
+            state.speed = match state.sample {
+                Some(_) => state.speed * 0.7 + current * 0.3,
+                None => current,
+            };
+            state.sample = Some((progress.elapsed, progress.sent));
+        }
+
+        let done = progress.sent == progress.total;
+        if !done && state.drawn.is_some_and(|drawn| drawn.elapsed() < REDRAW) {
+            return;
+        }
+        let line = state.line(progress.elapsed);
+        state.width = state.width.max(line.chars().count());
+        eprint!("\r{:width$}", line, width = state.width);
+        state.drawn = Some(Instant::now());
+    }
+
+    /// Clears the line so whatever is printed about the file starts on an empty one
+    pub fn finish_file(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.finished += state.sent;
+            if state.drawn.is_some() {
+                eprint!("\r{:width$}\r", "", width = state.width);
+            }
+            state.width = 0;
+        }
+    }
+}
@@ -0,0 +1,646 @@
+//! `uppy tui`, a full-screen front end for picking files, following their uploads and going
+//! through the history
+//!
+//! Files are uploaded one after the other on a thread of their own, through the same steps as
+//! any other run, while the screen is redrawn with the progress that comes in
+
+use clipboard_win::{formats, set_clipboard};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{List, ListItem, ListState, Paragraph, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use rusqlite::Connection;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::windows::io::AsRawHandle;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+use uppy::Client;
+
+use crate::cli::{Args, Print};
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::history::{self, Upload};
+use crate::i18n::t;
+use crate::template::human_size;
+use crate::{cancel, picker, rm, style, sys};
+use crate::{Configuration, Context, FileReport, FileStatus};
+
+/// How long to wait for a key before redrawing with the progress that came in meanwhile
+const TICK: Duration = Duration::from_millis(100);
+/// Rows Page Up and Page Down move by
+const PAGE: isize = 10;
+/// Uploads the history view goes through, the newest first
+const HISTORY_LIMIT: usize = 500;
+/// Columns of the progress bars
+const BAR: u64 = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+enum View {
+    Files,
+    Uploads,
+    History,
+}
+
+const VIEWS: [View; 3] = [View::Files, View::Uploads, View::History];
+
+enum State {
+    Queued,
+    Uploading,
+    Uploaded(String),
+    Failed(String),
+}
+
+/// A file that was sent off to be uploaded
+struct Job {
+    path: PathBuf,
+    sent: u64,
+    total: u64,
+    state: State,
+    failure: Option<Exit>,
+}
+
+/// What the upload thread tells the screen
+enum Update {
+    Started(usize),
+    Progress(u64, u64),
+    Finished(usize, Box<FileReport>),
+}
+
+/// Sends stderr to `NUL` until dropped, warnings and hooks would write over the screen
+struct Muted {
+    stderr: isize,
+    _nul: File,
+}
+
+impl Muted {
+    fn start() -> io::Result<Muted> {
+        let nul = OpenOptions::new().write(true).open("NUL")?;
+        // Safety: `NUL` stays open for as long as it is stderr, it is only closed after drop put
+        // the console back
+        unsafe {
+            let stderr = sys::GetStdHandle(sys::STD_ERROR_HANDLE);
+            if sys::SetStdHandle(sys::STD_ERROR_HANDLE, nul.as_raw_handle() as isize) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Muted { stderr, _nul: nul })
+        }
+    }
+}
+
+impl Drop for Muted {
+    fn drop(&mut self) {
+        // Safety: puts back the handle read in `start`
+        unsafe {
+            sys::SetStdHandle(sys::STD_ERROR_HANDLE, self.stderr);
+        }
+    }
+}
+
+/// Moves the highlight of `list` by `by` rows, staying within its `len` rows
+fn step(list: &mut ListState, len: usize, by: isize) {
+    let at = list.selected().unwrap_or(0) as isize + by;
+    list.select((len > 0).then(|| at.clamp(0, len as isize - 1) as usize));
+}
+
+/// A line of text typed into a view, the key when it wasn't one for it
+fn typed(line: &mut String, key: KeyEvent) -> Option<KeyEvent> {
+    match key.code {
+        KeyCode::Char(char) if !key.modifiers.contains(KeyModifiers::CONTROL) => line.push(char),
+        KeyCode::Backspace => {
+            line.pop();
+        }
+        _ => return Some(key),
+    }
+    None
+}
+
+fn bar(sent: u64, total: u64) -> String {
+    let filled = match total {
+        0 => BAR,
+        total => (sent.min(total) * BAR / total).min(BAR),
+    };
+    format!(
+        "{}{}",
+        "━".repeat(filled as usize),
+        "─".repeat((BAR - filled) as usize)
+    )
+}
+
+struct Tui<'a> {
+    view: View,
+    no_clipboard: bool,
+    client: &'a Client,
+    conn: Connection,
+    /// Sends files to the upload thread, taken once the screen closes so the thread ends
+    jobs: Option<Sender<(usize, PathBuf)>>,
+
+    files: Vec<PathBuf>,
+    shown: Vec<String>,
+    sizes: Vec<String>,
+    query: String,
+    /// The files that match the query, best first
+    matches: Vec<usize>,
+    checked: BTreeSet<usize>,
+    file_list: ListState,
+
+    queue: Vec<Job>,
+    /// The job being uploaded, which the progress is about
+    current: Option<usize>,
+    job_list: ListState,
+
+    search: String,
+    uploads: Vec<Upload>,
+    upload_list: ListState,
+    /// The upload Delete was pressed on once, pressing it again deletes it from the host
+    confirm: Option<usize>,
+
+    /// What the last action did, shown at the bottom
+    message: String,
+}
+
+impl Tui<'_> {
+    fn filter_files(&mut self) {
+        let wanted: Vec<char> = self.query.chars().collect();
+        let mut matches: Vec<(i64, usize)> = self
+            .shown
+            .iter()
+            .enumerate()
+            .filter_map(|(i, shown)| Some((picker::score(&wanted, shown)?, i)))
+            .collect();
+        matches.sort_by_key(|&(score, i)| (Reverse(score), i));
+        self.matches = matches.into_iter().map(|(_, i)| i).collect();
+        self.file_list
+            .select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn search_history(&mut self) {
+        let filter = history::Filter {
+            limit: Some(HISTORY_LIMIT),
+            grep: Some(self.search.clone()),
+            ..Default::default()
+        };
+        match history::search(&self.conn, &filter) {
+            Ok(uploads) => self.uploads = uploads,
+            Err(err) => self.message = err.to_string(),
+        }
+        self.confirm = None;
+        let selected = self.upload_list.selected().unwrap_or(0);
+        self.upload_list
+            .select((!self.uploads.is_empty()).then(|| selected.min(self.uploads.len() - 1)));
+    }
+
+    fn copy(&mut self, url: String) {
+        self.message = match set_clipboard(formats::Unicode, &url) {
+            Ok(_) => t!("tui-copied", url = url),
+            Err(err) => err.to_string(),
+        };
+    }
+
+    /// Sends the checked files, or the highlighted one when none are, off to be uploaded
+    fn upload_checked(&mut self) {
+        let picked: Vec<usize> = match self.checked.is_empty() {
+            true => self
+                .file_list
+                .selected()
+                .and_then(|row| self.matches.get(row).copied())
+                .into_iter()
+                .collect(),
+            false => std::mem::take(&mut self.checked).into_iter().collect(),
+        };
+        if picked.is_empty() {
+            return;
+        }
+        for &i in &picked {
+            let path = self.files[i].clone();
+            if let Some(jobs) = &self.jobs {
+                let _ = jobs.send((self.queue.len(), path.clone()));
+            }
+            self.queue.push(Job {
+                total: fs::metadata(&path).map_or(0, |meta| meta.len()),
+                path,
+                sent: 0,
+                state: State::Queued,
+                failure: None,
+            });
+        }
+        self.message = t!("tui-queued", count = picked.len());
+        self.view = View::Uploads;
+        if self.job_list.selected().is_none() {
+            self.job_list.select(Some(0));
+        }
+    }
+
+    fn delete(&mut self) {
+        let Some(row) = self.upload_list.selected() else {
+            return;
+        };
+        if self.confirm != Some(row) {
+            self.confirm = Some(row);
+            self.message = t!("tui-confirm-delete", url = self.uploads[row].url.as_str());
+            return;
+        }
+        self.message = match rm::delete(&self.uploads[row].url, self.client) {
+            Ok(url) => t!("deleted-upload", url = url.as_str()),
+            Err(err) => err.to_string(),
+        };
+        self.search_history();
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Started(i) => {
+                self.queue[i].state = State::Uploading;
+                self.current = Some(i);
+            }
+            Update::Progress(sent, total) => {
+                if let Some(job) = self.current.and_then(|i| self.queue.get_mut(i)) {
+                    (job.sent, job.total) = (sent, total);
+                }
+            }
+            Update::Finished(i, report) => {
+                self.current = None;
+                let job = &mut self.queue[i];
+                job.failure = report.failure;
+                job.state = match (report.status, report.url) {
+                    (FileStatus::Uploaded, Some(url)) => {
+                        job.sent = job.total;
+                        State::Uploaded(url)
+                    }
+                    _ => State::Failed(report.error.unwrap_or_default()),
+                };
+                if let (State::Uploaded(url), false) = (&job.state, self.no_clipboard) {
+                    let url = url.clone();
+                    self.copy(url);
+                }
+                self.search_history();
+            }
+        }
+    }
+
+    /// Handles `key`, false once the screen should go away
+    fn key(&mut self, key: KeyEvent) -> bool {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if control => return false,
+            KeyCode::Right | KeyCode::Left => {
+                let at = VIEWS
+                    .iter()
+                    .position(|&view| view == self.view)
+                    .unwrap_or(0);
+                let by = if key.code == KeyCode::Right { 1 } else { 2 };
+                self.view = VIEWS[(at + by) % VIEWS.len()];
+                return true;
+            }
+            KeyCode::Esc => {
+                let query = match self.view {
+                    View::Files => &mut self.query,
+                    View::History => &mut self.search,
+                    View::Uploads => return false,
+                };
+                if query.is_empty() {
+                    return false;
+                }
+                query.clear();
+                match self.view {
+                    View::Files => self.filter_files(),
+                    _ => self.search_history(),
+                }
+                return true;
+            }
+            _ => (),
+        }
+
+        match self.view {
+            View::Files => {
+                let Some(key) = typed(&mut self.query, key) else {
+                    self.filter_files();
+                    return true;
+                };
+                let len = self.matches.len();
+                match key.code {
+                    KeyCode::Up => step(&mut self.file_list, len, -1),
+                    KeyCode::Down => step(&mut self.file_list, len, 1),
+                    KeyCode::PageUp => step(&mut self.file_list, len, -PAGE),
+                    KeyCode::PageDown => step(&mut self.file_list, len, PAGE),
+                    KeyCode::Tab => {
+                        let row = self.file_list.selected();
+                        if let Some(&i) = row.and_then(|row| self.matches.get(row)) {
+                            if !self.checked.remove(&i) {
+                                self.checked.insert(i);
+                            }
+                            step(&mut self.file_list, len, 1);
+                        }
+                    }
+                    KeyCode::Enter => self.upload_checked(),
+                    _ => (),
+                }
+            }
+            View::Uploads => {
+                let len = self.queue.len();
+                match key.code {
+                    KeyCode::Up => step(&mut self.job_list, len, -1),
+                    KeyCode::Down => step(&mut self.job_list, len, 1),
+                    KeyCode::PageUp => step(&mut self.job_list, len, -PAGE),
+                    KeyCode::PageDown => step(&mut self.job_list, len, PAGE),
+                    KeyCode::Enter => {
+                        let job = self.job_list.selected().and_then(|i| self.queue.get(i));
+                        if let Some(Job {
+                            state: State::Uploaded(url),
+                            ..
+                        }) = job
+                        {
+                            let url = url.clone();
+                            self.copy(url);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            View::History => {
+                let Some(key) = typed(&mut self.search, key) else {
+                    self.search_history();
+                    return true;
+                };
+                let len = self.uploads.len();
+                match key.code {
+                    KeyCode::Up => step(&mut self.upload_list, len, -1),
+                    KeyCode::Down => step(&mut self.upload_list, len, 1),
+                    KeyCode::PageUp => step(&mut self.upload_list, len, -PAGE),
+                    KeyCode::PageDown => step(&mut self.upload_list, len, PAGE),
+                    KeyCode::Enter => {
+                        let upload = self.upload_list.selected().map(|i| &self.uploads[i]);
+                        if let Some(url) = upload.map(|upload| upload.url.clone()) {
+                            self.copy(url);
+                        }
+                    }
+                    KeyCode::Delete => self.delete(),
+                    _ => (),
+                }
+                if key.code != KeyCode::Delete {
+                    self.confirm = None;
+                }
+            }
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs, body, help, message] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let done = self
+            .queue
+            .iter()
+            .filter(|job| matches!(job.state, State::Uploaded(_) | State::Failed(_)))
+            .count();
+        let titles = [
+            t!("tui-files", checked = self.checked.len()),
+            t!("tui-uploads", done = done, count = self.queue.len()),
+            t!("tui-history"),
+        ];
+        let selected = VIEWS.iter().position(|&view| view == self.view);
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(selected)
+                .highlight_style(Style::new().bold().reversed()),
+            tabs,
+        );
+
+        let highlight = Style::new().reversed();
+        match self.view {
+            View::Files => {
+                let [query, list] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(body);
+                frame.render_widget(Paragraph::new(format!("> {}█", self.query)), query);
+                let width = (list.width as usize).saturating_sub(20);
+                let items: Vec<ListItem> = self
+                    .matches
+                    .iter()
+                    .map(|&i| {
+                        let check = if self.checked.contains(&i) {
+                            "[x]"
+                        } else {
+                            "[ ]"
+                        };
+                        ListItem::new(Line::from(vec![
+                            Span::raw(format!(
+                                "{} {}  ",
+                                check,
+                                picker::tail(&self.shown[i], width)
+                            )),
+                            self.sizes[i].as_str().dim(),
+                        ]))
+                    })
+                    .collect();
+                frame.render_stateful_widget(
+                    List::new(items).highlight_style(highlight),
+                    list,
+                    &mut self.file_list,
+                );
+            }
+            View::Uploads => {
+                let items: Vec<ListItem> = self
+                    .queue
+                    .iter()
+                    .map(|job| {
+                        let name = job.path.file_name().unwrap_or_default().to_string_lossy();
+                        let name = Span::raw(format!("{}  ", name));
+                        ListItem::new(Line::from(match &job.state {
+                            State::Queued => vec![name, t!("tui-waiting").dim()],
+                            State::Uploading => vec![
+                                name,
+                                bar(job.sent, job.total).cyan(),
+                                Span::raw(format!(
+                                    " {} / {}",
+                                    human_size(job.sent),
+                                    human_size(job.total)
+                                )),
+                            ],
+                            State::Uploaded(url) => vec![name, url.as_str().green()],
+                            State::Failed(error) => vec![name, error.as_str().red()],
+                        }))
+                    })
+                    .collect();
+                frame.render_stateful_widget(
+                    List::new(items).highlight_style(highlight),
+                    body,
+                    &mut self.job_list,
+                );
+            }
+            View::History => {
+                let [search, list] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(body);
+                frame.render_widget(Paragraph::new(format!("> {}█", self.search)), search);
+                let items: Vec<ListItem> = self
+                    .uploads
+                    .iter()
+                    .map(|upload| {
+                        ListItem::new(Line::from(vec![
+                            upload.date().dim(),
+                            Span::raw(format!("  {}  ", upload.name())),
+                            upload.url.as_str().green(),
+                        ]))
+                    })
+                    .collect();
+                frame.render_stateful_widget(
+                    List::new(items).highlight_style(highlight),
+                    list,
+                    &mut self.upload_list,
+                );
+            }
+        }
+
+        let keys = match self.view {
+            View::Files => t!("tui-files-help"),
+            View::Uploads => t!("tui-uploads-help"),
+            View::History => t!("tui-history-help"),
+        };
+        frame.render_widget(Paragraph::new(keys.dim()), help);
+        frame.render_widget(Paragraph::new(self.message.as_str()), message);
+    }
+
+    /// Draws until the screen is closed, showing what `updates` brings in
+    fn show(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        updates: &Receiver<Update>,
+    ) -> io::Result<()> {
+        loop {
+            while let Ok(update) = updates.try_recv() {
+                self.apply(update);
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(TICK)? {
+                continue;
+            }
+            // Windows reports keys being let go of too
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Press && !self.key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Uploads the files sent to it one by one, until the screen that sends them goes away
+fn upload(
+    jobs: Receiver<(usize, PathBuf)>,
+    updates: Sender<Update>,
+    config: &Configuration,
+    ctx: &Context,
+) {
+    for (i, file) in jobs {
+        if cancel::requested() {
+            break;
+        }
+        let _ = updates.send(Update::Started(i));
+        let report = crate::process_file(&file, config, ctx);
+        crate::remember(&report, config, ctx);
+        crate::post_upload(&report, config, ctx);
+        let _ = updates.send(Update::Finished(i, Box::new(report)));
+    }
+}
+
+/// Shows the files in `dir`, the uploads of this run and the history on a screen of their own
+/// until Esc or Ctrl+C, then prints the URLs of what was uploaded
+pub fn run(args: &Args, config: &Configuration, dir: &Path) -> Result<Exit, Error> {
+    let (files, shown) = match picker::list(dir, &crate::filter(args)) {
+        Ok(listed) => listed,
+        Err(Error::NothingToPick(_)) => Default::default(),
+        Err(err) => return Err(err),
+    };
+    let sizes = files
+        .iter()
+        .map(|file| {
+            fs::metadata(file).map_or_else(|_| String::new(), |meta| human_size(meta.len()))
+        })
+        .collect();
+
+    let (updates, updated) = mpsc::channel();
+    let progress = updates.clone();
+    let client = crate::client(args, config)?
+        .with_cancel(cancel::flag())
+        .with_progress(move |progress_of| {
+            let _ = progress.send(Update::Progress(progress_of.sent, progress_of.total));
+        });
+    // Nothing is printed while the screen is up, the reports are shown on it
+    let ctx = crate::context(args, config, Print::Url, client, None)?;
+    let (jobs, queued) = mpsc::channel();
+    let mut tui = Tui {
+        view: View::Files,
+        no_clipboard: args.no_clipboard,
+        client: &ctx.client,
+        conn: history::open()?,
+        jobs: Some(jobs),
+        files,
+        shown,
+        sizes,
+        query: String::new(),
+        matches: Vec::new(),
+        checked: BTreeSet::new(),
+        file_list: ListState::default(),
+        queue: Vec::new(),
+        current: None,
+        job_list: ListState::default(),
+        search: String::new(),
+        uploads: Vec::new(),
+        upload_list: ListState::default(),
+        confirm: None,
+        message: String::new(),
+    };
+    tui.filter_files();
+    tui.search_history();
+
+    let mut terminal = ratatui::try_init().map_err(Error::Terminal)?;
+    let muted = Muted::start()
+        .map_err(|err| warn!("Failed to keep stderr off the screen: {}", err))
+        .ok();
+    let shown = thread::scope(|scope| {
+        scope.spawn(|| upload(queued, updates, config, &ctx));
+        let shown = tui.show(&mut terminal, &updated);
+        // Whatever is still uploading is aborted, the upload thread ends with the screen
+        if tui.current.is_some()
+            || tui
+                .queue
+                .iter()
+                .any(|job| matches!(job.state, State::Queued))
+        {
+            cancel::flag().store(true, Ordering::Relaxed);
+        }
+        tui.jobs = None;
+        shown
+    });
+    drop(muted);
+    ratatui::restore();
+    shown.map_err(Error::Terminal)?;
+
+    while let Ok(update) = updated.try_recv() {
+        tui.apply(update);
+    }
+    for job in &tui.queue {
+        if let State::Uploaded(url) = &job.state {
+            println!("{}", t!("uploaded", url = style::success(url)));
+        }
+    }
+    let failures: Vec<Option<Exit>> = tui.queue.iter().map(|job| job.failure).collect();
+    Ok(match cancel::requested() {
+        true => Exit::Cancelled,
+        false => Exit::for_batch(&failures),
+    })
+}
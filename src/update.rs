@@ -0,0 +1,210 @@
+use reqwest::blocking;
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use std::{env, fs, thread};
+use tracing::debug;
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::history;
+use crate::i18n::t;
+use crate::minisign::PublicKey;
+use uppy::config::CONFIG_PATH;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/sigi3012/uppy/releases/latest";
+/// The minisign public key release binaries are signed with, set by the release build
+///
+/// A checksum from the release could be swapped out along with the binary, the key can't
+const RELEASE_KEY: Option<&str> = option_env!("UPPY_RELEASE_KEY");
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// `v1.2.3` into `[1, 2, 3]`, anything after the numbers like `-rc1` is ignored
+fn version(tag: &str) -> Vec<u64> {
+    tag.trim_start_matches('v')
+        .split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+/// Release binaries are named after the platform, e.g. `uppy-x86_64-windows.exe`
+fn is_for_this_platform(name: &str) -> bool {
+    name.contains(env::consts::ARCH)
+        && name.contains(env::consts::OS)
+        && name.ends_with(env::consts::EXE_SUFFIX)
+}
+
+fn download(http: &blocking::Client, url: &str) -> Result<Vec<u8>, Error> {
+    debug!("Downloading {}", url);
+    let res = http
+        .get(url)
+        .header(USER_AGENT, "uppy")
+        .send()
+        .and_then(|res| res.error_for_status())
+        .map_err(Error::UpdateCheck)?;
+    Ok(res.bytes().map_err(Error::UpdateCheck)?.to_vec())
+}
+
+fn latest_release(http: &blocking::Client) -> Result<Release, Error> {
+    http.get(LATEST_RELEASE)
+        .header(USER_AGENT, "uppy")
+        .header(ACCEPT, "application/vnd.github+json")
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.json())
+        .map_err(Error::UpdateCheck)
+}
+
+fn is_newer(tag: &str) -> bool {
+    version(tag) > version(env!("CARGO_PKG_VERSION"))
+}
+
+/// When the background check last asked GitHub and what it found
+#[derive(Serialize, Deserialize)]
+struct LastCheck {
+    checked_at: i64,
+    latest: String,
+}
+
+fn last_check_path() -> PathBuf {
+    CONFIG_PATH.join("update_check.json")
+}
+
+fn cached_or_fetch(every_days: u64) -> Option<String> {
+    let last: Option<LastCheck> = fs::read_to_string(last_check_path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok());
+    if let Some(last) =
+        last.filter(|last| history::now() - last.checked_at < every_days as i64 * 24 * 60 * 60)
+    {
+        return Some(last.latest);
+    }
+
+    let http = blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .ok()?;
+    let latest = match latest_release(&http) {
+        Ok(release) => release.tag_name,
+        Err(err) => {
+            debug!("Background update check failed: {}", err);
+            return None;
+        }
+    };
+    let last = LastCheck {
+        checked_at: history::now(),
+        latest,
+    };
+    if let Ok(json) = serde_json::to_string(&last) {
+        let _ = fs::write(last_check_path(), json);
+    }
+    Some(last.latest)
+}
+
+/// Looks for a newer release on another thread, asking GitHub at most once every `every_days` days
+pub fn check_in_background(every_days: u64) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        if let Some(latest) = cached_or_fetch(every_days).filter(|latest| is_newer(latest)) {
+            let _ = sender.send(latest);
+        }
+    });
+    receiver
+}
+
+/// Prints a notice when the background check found a newer release
+///
+/// A check that is still waiting on GitHub gets a moment to finish, after that it is
+/// abandoned and tried again next time
+pub fn notify(receiver: Receiver<String>) {
+    if let Ok(latest) = receiver.recv_timeout(Duration::from_secs(1)) {
+        eprintln!("{}", t!("update-available", version = latest));
+    }
+}
+
+/// Replaces the running executable with the binary of the latest GitHub release
+///
+/// Windows won't overwrite a running executable but it will rename one, so the
+/// old binary is moved aside and removed by the next update
+pub fn run(check: bool) -> Result<Exit, Error> {
+    let http = blocking::Client::new();
+    let release = latest_release(&http)?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    if !is_newer(&release.tag_name) {
+        println!("{}", t!("update-latest", version = current));
+        return Ok(Exit::Success);
+    }
+    println!(
+        "{}",
+        t!(
+            "update-newer",
+            latest = release.tag_name.as_str(),
+            current = current
+        )
+    );
+    if check {
+        return Ok(Exit::Success);
+    }
+
+    let binary = release
+        .assets
+        .iter()
+        .find(|asset| is_for_this_platform(&asset.name))
+        .ok_or_else(|| {
+            Error::NoReleaseAsset(format!(
+                "build for {} {}",
+                env::consts::OS,
+                env::consts::ARCH
+            ))
+        })?;
+    // Refuse to install anything that can't be checked
+    let key = RELEASE_KEY
+        .and_then(PublicKey::parse)
+        .ok_or(Error::NoReleaseKey)?;
+    let signature_name = format!("{}.minisig", binary.name);
+    let signature = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == signature_name)
+        .ok_or(Error::NoReleaseAsset(signature_name))?;
+
+    let minisig =
+        String::from_utf8_lossy(&download(&http, &signature.browser_download_url)?).into_owned();
+    let bytes = download(&http, &binary.browser_download_url)?;
+    if !key.verify(&bytes, &minisig) {
+        return Err(Error::BadSignature);
+    }
+
+    let exe = env::current_exe().map_err(Error::Install)?;
+    let old = exe.with_extension("old");
+    let staged = exe.with_extension("new");
+    // Left behind by the previous update
+    let _ = fs::remove_file(&old);
+    fs::write(&staged, bytes).map_err(Error::Install)?;
+    fs::rename(&exe, &old).map_err(Error::Install)?;
+    if let Err(err) = fs::rename(&staged, &exe) {
+        // Put the old binary back rather than leave nothing to run
+        let _ = fs::rename(&old, &exe);
+        return Err(Error::Install(err));
+    }
+
+    println!("{}", t!("updated", version = release.tag_name.as_str()));
+    Ok(Exit::Success)
+}
@@ -0,0 +1,96 @@
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::winpath;
+
+/// Read from every directory that is walked, with the same syntax as a `.gitignore`
+const IGNORE_FILE: &str = ".uppyignore";
+
+/// What happens to symbolic links and junctions
+#[derive(Clone, Copy, PartialEq)]
+pub enum Symlinks {
+    /// Links given directly are followed, links inside directories are skipped so nothing
+    /// outside the directory gets uploaded
+    Given,
+    /// Links inside directories are followed too, archives get what they point to
+    Follow,
+    /// Links are never followed, not even when given directly
+    Skip,
+}
+
+/// The `--include` and `--exclude` globs, matched like lines of a `.gitignore`, and how links are handled
+pub struct Filter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub symlinks: Symlinks,
+}
+
+impl Filter {
+    fn overrides(&self, root: &Path) -> Result<Override, Error> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.include {
+            builder.add(glob).map_err(Error::Filter)?;
+        }
+        for glob in &self.exclude {
+            builder.add(&format!("!{}", glob)).map_err(Error::Filter)?;
+        }
+        builder.build().map_err(Error::Filter)
+    }
+}
+
+/// Expands directories into every file inside them, leaving out hidden files and whatever
+/// `.uppyignore` files in them list
+///
+/// Files that were given directly are only checked against the `--include` and `--exclude` globs
+pub fn expand(paths: Vec<PathBuf>, filter: &Filter) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = winpath::normalize(&path);
+        let link = fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_symlink());
+        if link && filter.symlinks == Symlinks::Skip {
+            warn!("Skipping {}, it is a link", path.display());
+            continue;
+        }
+        if !path.is_dir() {
+            let overrides = filter.overrides(path.parent().unwrap_or(Path::new("")))?;
+            match overrides.matched(&path, false).is_ignore() {
+                true => debug!("Filtered out {}", path.display()),
+                false => files.push(path),
+            }
+            continue;
+        }
+
+        let walk = WalkBuilder::new(&path)
+            .standard_filters(false)
+            .hidden(true)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .overrides(filter.overrides(&path)?)
+            .follow_links(filter.symlinks == Symlinks::Follow)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+        for entry in walk {
+            match entry {
+                Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_file()) => {
+                    files.push(entry.into_path())
+                }
+                Ok(entry) if entry.path_is_symlink() && filter.symlinks != Symlinks::Follow => {
+                    warn!(
+                        "Skipping the link {}, pass --follow-symlinks to upload what it points to",
+                        entry.path().display()
+                    )
+                }
+                Ok(_) => (),
+                Err(err) => warn!("Skipping part of {}: {}", path.display(), err),
+            }
+        }
+    }
+    // A file given directly can also turn up in a directory that was given, spelled differently
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(winpath::extended(file)));
+    Ok(files)
+}
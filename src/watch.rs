@@ -0,0 +1,125 @@
+//! `uppy watch`, uploading the files that show up in a directory once they are done being written
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::os::windows::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::i18n::t;
+use crate::walk::{self, Filter};
+use crate::{cancel, offline, status, winpath};
+
+const POLL: Duration = Duration::from_millis(500);
+/// How long a file has to keep its size and modified time before it is uploaded
+pub const DEFAULT_DEBOUNCE_MS: u64 = 2000;
+/// What browsers and download managers write to before the file gets its real name
+pub const PARTIAL_FILES: [&str; 5] = ["*.part", "*.crdownload", "*.download", "*.partial", "*.tmp"];
+
+/// A file that showed up, and since when it looked the way it does
+struct Settling {
+    size: u64,
+    modified: Option<SystemTime>,
+    since: Instant,
+}
+
+/// Whether nothing else has `file` open, recorders and browsers keep it open while writing
+fn unlocked(file: &Path) -> bool {
+    // Asking to share nothing fails while any other handle to the file is open
+    OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(file)
+        .is_ok()
+}
+
+/// Uploads every file that shows up in `dir` from now on, until Ctrl+C
+///
+/// A file is only uploaded once it kept its size and modified time for `debounce` and nothing
+/// else has it open anymore
+pub fn watch(
+    dir: &Path,
+    filter: &Filter,
+    debounce: Duration,
+    mut upload: impl FnMut(Vec<PathBuf>) -> Result<Exit, Error>,
+) -> Result<Exit, Error> {
+    if !dir.is_dir() {
+        return Err(Error::NotADirectory(dir.to_path_buf()));
+    }
+    let list = || walk::expand(vec![dir.to_path_buf()], filter);
+    // What was already there is left alone, it isn't new
+    let mut seen: HashSet<PathBuf> = list()?.into_iter().collect();
+    let mut settling: HashMap<PathBuf, Settling> = HashMap::new();
+    println!(
+        "{}",
+        t!(
+            "watching-dir",
+            path = winpath::plain(dir).display().to_string()
+        )
+    );
+
+    let _status = status::announce();
+    offline::listen();
+    cancel::install();
+    while !cancel::requested() {
+        thread::sleep(POLL);
+        // The files that show up meanwhile are uploaded once resumed
+        if offline::paused() {
+            continue;
+        }
+        let files = match list() {
+            Ok(files) => files,
+            Err(err) => {
+                warn!("Failed to list {}: {}", dir.display(), err);
+                continue;
+            }
+        };
+        // A file that was removed and comes back is new again
+        let present: HashSet<&PathBuf> = files.iter().collect();
+        seen.retain(|file| present.contains(file));
+        settling.retain(|file, _| present.contains(file));
+
+        let mut ready = Vec::new();
+        for file in files.iter().filter(|file| !seen.contains(*file)) {
+            let Ok(meta) = fs::metadata(file) else {
+                continue;
+            };
+            let (size, modified) = (meta.len(), meta.modified().ok());
+            match settling.get(file) {
+                Some(settled) if settled.size == size && settled.modified == modified => {
+                    if settled.since.elapsed() >= debounce && unlocked(file) {
+                        ready.push(file.clone());
+                    }
+                }
+                // Still being written, or only just showed up
+                _ => {
+                    settling.insert(
+                        file.clone(),
+                        Settling {
+                            size,
+                            modified,
+                            since: Instant::now(),
+                        },
+                    );
+                }
+            }
+        }
+        if ready.is_empty() {
+            continue;
+        }
+
+        for file in &ready {
+            settling.remove(file);
+            seen.insert(file.clone());
+        }
+        debug!(files = ?ready, "Uploading from {}", dir.display());
+        if let Err(err) = upload(ready) {
+            warn!("{}", err);
+        }
+    }
+    Ok(Exit::Success)
+}
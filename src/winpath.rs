@@ -0,0 +1,77 @@
+//! Paths past `MAX_PATH` and on network shares, which Windows only fully supports behind `\\?\`
+
+use std::ffi::OsString;
+use std::path::{self, Component, Path, PathBuf, Prefix};
+
+/// Longest path most of Windows takes without the `\\?\` prefix, counting the terminating NUL
+const MAX_PATH: usize = 260;
+/// Directories have to leave room for an 8.3 file name in them
+const MAX_DIR_PATH: usize = MAX_PATH - 12;
+
+/// `path` as an absolute extended-length path, `\\?\C:\...` or `\\?\UNC\server\share\...`
+///
+/// `..` and `.` are resolved first since Windows takes everything after `\\?\` literally
+pub fn extended(path: &Path) -> PathBuf {
+    let Ok(absolute) = path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return absolute;
+    };
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(_) => OsString::from(r"\\?\"),
+        Prefix::UNC(..) => OsString::from(r"\\?\UNC"),
+        // Already verbatim, or a device
+        _ => return absolute,
+    };
+    // `\\server\share` loses its leading backslashes behind `\\?\UNC`
+    extended.push(match prefix.kind() {
+        Prefix::UNC(server, share) => {
+            let mut unc = OsString::from(r"\");
+            unc.push(server);
+            unc.push(r"\");
+            unc.push(share);
+            unc
+        }
+        _ => prefix.as_os_str().to_os_string(),
+    });
+    extended.push(components.as_path());
+    PathBuf::from(extended)
+}
+
+/// `path` without the `\\?\` prefix when it fits in `MAX_PATH` without it, for showing to people
+/// and handing to programs that don't understand the prefix
+pub fn plain(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path.to_path_buf();
+    };
+    let mut plain = match prefix.kind() {
+        Prefix::VerbatimDisk(disk) => OsString::from(format!("{}:", disk as char)),
+        Prefix::VerbatimUNC(server, share) => {
+            let mut unc = OsString::from(r"\\");
+            unc.push(server);
+            unc.push(r"\");
+            unc.push(share);
+            unc
+        }
+        _ => return path.to_path_buf(),
+    };
+    plain.push(components.as_path());
+    match plain.len() < MAX_PATH {
+        true => PathBuf::from(plain),
+        false => path.to_path_buf(),
+    }
+}
+
+/// `path`, made extended-length when it is too long to be used without the prefix
+///
+/// Directories get walked, so any that are close to the limit are extended as well, so the files
+/// found in them are too
+pub fn normalize(path: &Path) -> PathBuf {
+    match path::absolute(path) {
+        Ok(absolute) if absolute.as_os_str().len() >= MAX_DIR_PATH => extended(&absolute),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::Write;
+
+use reqwest::StatusCode;
+use uppy::{Client, ClientConfig, UploadError, UploadRequest};
+
+fn client(server: &mockito::Server) -> Client {
+    Client::new(&ClientConfig {
+        host: "https://files.example.com".to_string(),
+        token: "secret".to_string(),
+        // Skips asking the mock server for /api/version
+        zipline_version: Some(4),
+        ..Default::default()
+    })
+    .expect("valid config")
+    .with_host(server.url())
+}
+
+fn screenshot() -> tempfile::NamedTempFile {
+    let mut file = tempfile::Builder::new()
+        .suffix(".png")
+        .tempfile()
+        .expect("temp file");
+    file.write_all(b"not really a png")
+        .expect("write temp file");
+    file
+}
+
+#[test]
+fn upload_returns_the_url_of_the_answer() {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/upload")
+        .match_header("authorization", "secret")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"files":[{"url":"https://files.example.com/u/abc.png"}],"deletion_url":"https://files.example.com/d/abc"}"#)
+        .create();
+    let file = screenshot();
+
+    let response = client(&server)
+        .upload(&UploadRequest::new(file.path()))
+        .expect("upload");
+
+    mock.assert();
+    assert_eq!(response.url, "https://files.example.com/u/abc.png");
+    assert_eq!(
+        response.deletion_url.as_deref(),
+        Some("https://files.example.com/d/abc")
+    );
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[test]
+fn upload_turns_a_server_error_into_an_error() {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/upload")
+        .with_status(500)
+        .with_body("Internal Server Error")
+        .create();
+    let file = screenshot();
+
+    let Err(err) = client(&server).upload(&UploadRequest::new(file.path())) else {
+        panic!("a 500 isn't an upload");
+    };
+
+    mock.assert();
+    assert!(
+        matches!(
+            err,
+            UploadError::HTTPServerError(StatusCode::INTERNAL_SERVER_ERROR)
+        ),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn upload_turns_a_refused_token_into_an_error() {
+    let mut server = mockito::Server::new();
+    server
+        .mock("POST", "/api/upload")
+        .with_status(401)
+        .with_body(r#"{"error":"not logged in"}"#)
+        .create();
+    let file = screenshot();
+
+    let Err(err) = client(&server).upload(&UploadRequest::new(file.path())) else {
+        panic!("a 401 isn't an upload");
+    };
+
+    assert!(
+        matches!(err, UploadError::HTTPClientError(StatusCode::UNAUTHORIZED)),
+        "unexpected error: {}",
+        err
+    );
+}